@@ -1,11 +1,35 @@
 #ifdef USE_SKUGGBOX_CAMERA
 uniform mat4 sbCameraTransform;
+// tan(fov / 2), aspect, near, far
+uniform vec4 sbCameraProjection;
+// half height of the view in world units, 0 for a perspective projection
+uniform float sbCameraOrthoScale;
+// focus distance, aperture radius
+uniform vec2 sbCameraLens;
 void skuggbox_camera(vec2 uv, inout vec3 ro, inout vec3 rd) {
+    mat3 basis = mat3(sbCameraTransform);
     ro = sbCameraTransform[3].xyz;
-    rd = mat3(sbCameraTransform) * normalize(vec3(uv, 1));
+    if (sbCameraOrthoScale > 0.0) {
+        ro += basis * vec3(uv * sbCameraOrthoScale, 0.0);
+        rd = basis[2];
+    } else {
+        rd = basis * normalize(vec3(uv * sbCameraProjection.x, 1.0));
+    }
+    ro += rd * sbCameraProjection.z;
+}
+// Depth of field: move the ray origin to a point on the lens, `lensSample` in [-1, 1],
+// and aim it at the same point on the focus plane. Call after skuggbox_camera.
+void skuggbox_camera_lens(vec2 lensSample, inout vec3 ro, inout vec3 rd) {
+    mat3 basis = mat3(sbCameraTransform);
+    vec3 focus = ro + rd * (sbCameraLens.x / max(dot(rd, basis[2]), 1e-4));
+    ro += basis * vec3(lensSample * sbCameraLens.y, 0.0);
+    rd = normalize(focus - ro);
 }
 #else
 void skuggbox_camera(vec2 uv, inout vec3 ro, inout vec3 rd) {
     // empty
 }
+void skuggbox_camera_lens(vec2 lensSample, inout vec3 ro, inout vec3 rd) {
+    // empty
+}
 #endif
\ No newline at end of file
@@ -1,35 +1,123 @@
-use crate::camera::OrbitCamera;
-use crate::{seek, AppState, Mouse, PlayMode, PlaybackControl, PreProcessorConfig, ShaderService};
+use crate::camera::CameraKind;
+use crate::{
+    external_editor_command, open_in_external_editor, seek, set_bookmark, AppState, CameraPose,
+    CameraRecording, FullscreenMode, Mouse, PlayMode, PlaybackControl, PreProcessorConfig,
+    RecordingMode, RenderResolution, ShaderService, ShaderSettings, TimeStep, UpscaleFilter,
+};
+use glam::{IVec2, Vec3};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use winit::event_loop::ControlFlow;
 
 /// First person camera movement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CameraMovement {
     MoveForward,
     MoveBackward,
     StrafeLeft,
     StrafeRight,
+    MoveUp,
+    MoveDown,
     Reset,
 }
 
+/// Everything the user can make the app do. Key bindings are written in the config file the
+/// way serde writes these, e.g. `"TogglePlayPause"` or `{ TimeForward = 5.0 }`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Action {
     AppExit,
+    ToggleCommandPalette,
+    ToggleHelp,
     TimePlay,
     TimePause,
     TogglePlayPause,
     TimeStop,
     TimeForward(f32),
     TimeRewind(f32),
+    TimeSeek(f32),
+    TimeSpeed(f32),
+    MarkerAdd,
+    MarkerRemove(usize),
+    MarkerNext,
+    MarkerPrevious,
+    LoopToggle,
+    LoopSetStart,
+    LoopSetEnd,
+    FrameStepForward,
+    FrameStepBack,
+    ToggleTimeStep,
     WindowClose,
     WindowResize((i32, i32)),
     // size
     CameraToggleIntegration(bool),
     CameraReset,
+    CameraSelect(CameraKind),
     CameraMove(CameraMovement),
+    /// Save the current view under a number key
+    CameraBookmarkSave(u8),
+    CameraBookmarkRecall(u8),
+    CameraBookmarkRemove(u8),
+    /// Add a keyframe to the camera path at the current time, or update the one there
+    CameraKeyframeAdd,
+    /// Move a keyframe to the current view
+    CameraKeyframeUpdate(usize),
+    CameraKeyframeRemove(usize),
+    CameraPathToggle,
+    /// Keep the camera where it is and revolve around this point from now on
+    CameraOrbitAround(Vec3),
+    /// Start recording the camera from the current time on, or stop
+    CameraRecordToggle,
+    CameraReplayToggle,
+    /// Smooth the recording over a window in seconds
+    CameraRecordingSmooth(f32),
+    /// Replace the camera path with keyframes resampled from the recording every N seconds
+    CameraRecordingToPath(f32),
+    CameraRecordingExport,
+    CameraRecordingClear,
     UIToggleVisible,
+    ToggleProfiler,
+    ToggleInspector,
+    InspectorPin,
+    InspectorRemovePin(usize),
+    ToggleProbes,
+    ToggleGizmos,
+    ProbeSelectPixel(IVec2),
+    /// Probe the pixel under the cursor, if probing is on
+    ProbeAtCursor,
+    TogglePicking,
+    /// Read back the `sbPick` outputs at a render target pixel
+    PickPixel(IVec2),
+    /// Pick the pixel under the cursor, if pick mode is on and no handle is in the way
+    PickAtCursor,
+    SetRenderResolution(RenderResolution),
+    RenderScaleStep(i32),
+    ToggleUpscaleFilter,
     ToggleFullscreen,
+    SetFullscreenMode(FullscreenMode),
+    /// Go fullscreen on the monitor with this index
+    FullscreenMonitor(usize),
+    ToggleAlwaysOnTop,
+    ToggleBorderless,
+    ToggleVsync,
     Screenshot,
+    RenderPoster,
     PrintSource,
     TakeSnapshot,
+    ToggleEditor,
+    /// Code over the running shader, with all other panels out of the way
+    ToggleEditorOverlay,
+    /// Compile the shaders with the text in the editor right away
+    EditorCompile,
+    /// Write the active editor tab to disk
+    EditorSave,
+    ToggleFileTree,
+    /// Show a shader file at a line in the built-in editor
+    OpenFile(PathBuf, usize),
+    /// Open a shader file at a line in the configured editor, `$VISUAL` or `$EDITOR`
+    OpenFileExternal(PathBuf, usize),
+    /// Open the file and line of the first compile error in the configured editor, or in the
+    /// built-in one if there is none
+    OpenFirstError,
 }
 
 pub fn handle_actions(
@@ -41,10 +129,13 @@ pub fn handle_actions(
     for action in actions.drain(..) {
         match action {
             Action::AppExit => {
+                save_settings(app_state, shader_service);
                 log::info!("Bye now...");
                 app_state.is_running = false;
                 *control_flow = ControlFlow::Exit
             }
+            Action::ToggleCommandPalette => app_state.command_palette.toggle(),
+            Action::ToggleHelp => app_state.help_visible = !app_state.help_visible,
             Action::TimePlay => {
                 app_state.timer.start();
                 app_state.play_mode = PlayMode::Playing;
@@ -53,11 +144,11 @@ pub fn handle_actions(
                 app_state.play_mode = PlayMode::Paused;
             }
             Action::TimeStop => {
-                app_state.playback_time = seek(
+                app_state.set_playback_time(seek(
                     app_state.playback_time,
                     &app_state.modifier,
                     PlaybackControl::Stop,
-                );
+                ));
                 app_state.play_mode = PlayMode::Paused;
                 log::debug!("Stopped");
             }
@@ -72,19 +163,66 @@ pub fn handle_actions(
                     log::debug!("Playing");
                 }
             },
-            Action::TimeForward(time) => {
-                app_state.playback_time = seek(
-                    app_state.playback_time,
-                    &app_state.modifier,
-                    PlaybackControl::Forward(time),
-                )
+            Action::TimeForward(time) => app_state.set_playback_time(seek(
+                app_state.playback_time,
+                &app_state.modifier,
+                PlaybackControl::Forward(time),
+            )),
+            Action::TimeRewind(time) => app_state.set_playback_time(seek(
+                app_state.playback_time,
+                &app_state.modifier,
+                PlaybackControl::Rewind(time),
+            )),
+            Action::TimeSeek(time) => app_state.set_playback_time(time),
+            Action::TimeSpeed(speed) => app_state.timeline.set_speed(speed),
+            Action::MarkerAdd => {
+                app_state.timeline.add_marker(app_state.playback_time);
+                save_settings(app_state, shader_service);
             }
-            Action::TimeRewind(time) => {
-                app_state.playback_time = seek(
-                    app_state.playback_time,
-                    &app_state.modifier,
-                    PlaybackControl::Rewind(time),
-                )
+            Action::MarkerRemove(index) => {
+                app_state.timeline.remove_marker(index);
+                save_settings(app_state, shader_service);
+            }
+            Action::MarkerNext => {
+                if let Some(marker) = app_state.timeline.next_marker(app_state.playback_time) {
+                    let time = marker.time;
+                    app_state.set_playback_time(time);
+                }
+            }
+            Action::MarkerPrevious => {
+                if let Some(marker) = app_state.timeline.previous_marker(app_state.playback_time) {
+                    let time = marker.time;
+                    app_state.set_playback_time(time);
+                }
+            }
+            Action::LoopToggle => {
+                app_state.timeline.toggle_loop();
+                save_settings(app_state, shader_service);
+            }
+            Action::LoopSetStart => {
+                app_state.timeline.set_loop_start(app_state.playback_time);
+                save_settings(app_state, shader_service);
+            }
+            Action::LoopSetEnd => {
+                app_state.timeline.set_loop_end(app_state.playback_time);
+                save_settings(app_state, shader_service);
+            }
+            Action::FrameStepForward => {
+                app_state.play_mode = PlayMode::Paused;
+                app_state.step_frames(1);
+            }
+            Action::FrameStepBack => {
+                app_state.play_mode = PlayMode::Paused;
+                app_state.step_frames(-1);
+            }
+            Action::ToggleTimeStep => {
+                app_state.time_step = match app_state.time_step {
+                    TimeStep::Variable => TimeStep::Fixed,
+                    TimeStep::Fixed => TimeStep::Variable,
+                };
+                // snap to a whole frame when going fixed
+                app_state.set_playback_time(app_state.playback_time);
+                log::debug!("Time step: {:?}", app_state.time_step);
             }
             Action::WindowClose => {}
             Action::WindowResize((width, height)) => {
@@ -99,6 +237,7 @@ pub fn handle_actions(
                         shader_service.use_camera_integration = true;
                         shader_service.reload(PreProcessorConfig {
                             use_camera_integration: true,
+                            ..shader_service.pre_processor_config()
                         });
                         //.expect("Expected successful shader reload");
                     }
@@ -109,28 +248,230 @@ pub fn handle_actions(
                         shader_service.use_camera_integration = false;
                         shader_service.reload(PreProcessorConfig {
                             use_camera_integration: false,
+                            ..shader_service.pre_processor_config()
                         });
                         //.expect("Expected successful shader reload");
                     }
                 }
             },
+            Action::CameraSelect(kind) => app_state.select_camera(kind),
+            Action::CameraBookmarkSave(slot) => {
+                let pose = app_state.camera_pose();
+                set_bookmark(&mut app_state.camera_bookmarks, slot, pose);
+                log::info!("Camera: Saved view {}", slot);
+                save_settings(app_state, shader_service);
+            }
+            Action::CameraBookmarkRecall(slot) => {
+                let bookmark = app_state
+                    .camera_bookmarks
+                    .iter()
+                    .find(|bookmark| bookmark.slot == slot)
+                    .cloned();
+                match bookmark {
+                    Some(bookmark) => {
+                        app_state.set_camera_pose(&bookmark.pose);
+                        log::info!("Camera: {}", bookmark.name);
+                    }
+                    None => log::info!("Camera: No view saved under {}", slot),
+                }
+            }
+            Action::CameraBookmarkRemove(slot) => {
+                app_state
+                    .camera_bookmarks
+                    .retain(|bookmark| bookmark.slot != slot);
+                save_settings(app_state, shader_service);
+            }
+            Action::CameraKeyframeAdd => {
+                let pose = app_state.camera_pose();
+                app_state
+                    .camera_path
+                    .set_keyframe(app_state.playback_time, pose);
+                save_settings(app_state, shader_service);
+            }
+            Action::CameraKeyframeUpdate(index) => {
+                let pose = app_state.camera_pose();
+                if let Some(keyframe) = app_state.camera_path.keyframes.get_mut(index) {
+                    keyframe.pose = pose;
+                    save_settings(app_state, shader_service);
+                }
+            }
+            Action::CameraKeyframeRemove(index) => {
+                app_state.camera_path.remove_keyframe(index);
+                save_settings(app_state, shader_service);
+            }
+            Action::CameraPathToggle => {
+                let path = &mut app_state.camera_path;
+                path.enabled = !path.enabled;
+                // hand over without a jump when leaving the path
+                if !path.enabled {
+                    if let Some(pose) = path.sample(app_state.playback_time) {
+                        app_state.set_camera_pose(&pose);
+                    }
+                }
+                save_settings(app_state, shader_service);
+            }
+            Action::CameraOrbitAround(target) => {
+                app_state.camera.set_target(target);
+            }
+            Action::CameraRecordToggle => {
+                if app_state.recording_mode == RecordingMode::Recording {
+                    app_state.recording_mode = RecordingMode::Off;
+                    log::info!("Camera: Recording stopped");
+                } else {
+                    app_state.camera_recording.truncate(app_state.playback_time);
+                    app_state.recording_mode = RecordingMode::Recording;
+                    app_state.timer.start();
+                    app_state.play_mode = PlayMode::Playing;
+                    log::info!("Camera: Recording");
+                }
+            }
+            Action::CameraReplayToggle => {
+                if app_state.recording_mode == RecordingMode::Replaying {
+                    // hand over without a jump
+                    if let Some(sample) = app_state.camera_replay() {
+                        let pose = CameraPose::from_transform(sample.transform, sample.fov);
+                        app_state.set_camera_pose(&pose);
+                    }
+                    app_state.recording_mode = RecordingMode::Off;
+                } else if !app_state.camera_recording.is_empty() {
+                    app_state.recording_mode = RecordingMode::Replaying;
+                }
+            }
+            Action::CameraRecordingSmooth(window) => {
+                app_state.camera_recording = app_state.camera_recording.smoothed(window);
+            }
+            Action::CameraRecordingToPath(interval) => {
+                app_state.camera_path = app_state.camera_recording.to_path(interval);
+                if app_state.recording_mode == RecordingMode::Replaying {
+                    app_state.recording_mode = RecordingMode::Off;
+                }
+                save_settings(app_state, shader_service);
+            }
+            Action::CameraRecordingExport => {
+                if let Some(path) = shader_service.main_path() {
+                    let path = CameraRecording::path(path);
+                    if let Err(err) = app_state.camera_recording.save(&path) {
+                        log::error!("Failed to save camera recording {:?}: {:?}", path, err);
+                    }
+                }
+            }
+            Action::CameraRecordingClear => {
+                app_state.camera_recording = Default::default();
+                app_state.recording_mode = RecordingMode::Off;
+            }
+            Action::ToggleGizmos => {
+                app_state.gizmos.enabled = !app_state.gizmos.enabled;
+            }
             Action::CameraReset => {
-                app_state.camera = Box::from(OrbitCamera::default());
+                let projection = app_state.camera.projection().clone();
+                app_state.camera = app_state.camera_kind.create();
+                *app_state.camera.projection_mut() = projection;
                 app_state.mouse = Mouse::default();
             }
             Action::UIToggleVisible => {
                 app_state.ui_visible = !app_state.ui_visible;
                 log::debug!("Action::UIToggleVisible {:?}", app_state.ui_visible);
             }
+            Action::ToggleProfiler => {
+                app_state.profiler_visible = !app_state.profiler_visible;
+            }
+            Action::ToggleInspector => {
+                app_state.inspector.enabled = !app_state.inspector.enabled;
+            }
+            Action::InspectorPin => {
+                app_state.inspector.pin_hovered();
+            }
+            Action::InspectorRemovePin(index) => {
+                app_state.inspector.remove_pin(index);
+            }
+            Action::ToggleProbes => {
+                app_state.probes.enabled = !app_state.probes.enabled;
+                log::info!(
+                    "Probing {}. Use 'sbProbe(\"name\", value);' in your shader and middle click a pixel",
+                    if app_state.probes.enabled { "enabled" } else { "disabled" }
+                );
+                shader_service.reload(PreProcessorConfig {
+                    use_probes: app_state.probes.enabled,
+                    ..shader_service.pre_processor_config()
+                });
+            }
+            Action::ProbeSelectPixel(pixel) => {
+                app_state.probes.pixel = Some(pixel);
+            }
+            Action::TogglePicking => {
+                app_state.picking.enabled = !app_state.picking.enabled;
+                log::info!(
+                    "Picking {}. Use 'sbPick(position, normal);' in your shader and click the scene",
+                    if app_state.picking.enabled { "enabled" } else { "disabled" }
+                );
+            }
+            Action::PickPixel(pixel) => {
+                app_state.picking.pending = Some(pixel);
+            }
+            Action::ProbeAtCursor => {
+                if app_state.probes.enabled {
+                    if let Some(pixel) = app_state.cursor_pixel() {
+                        app_state.probes.pixel = Some(pixel);
+                    }
+                }
+            }
+            Action::PickAtCursor => {
+                if app_state.picking.enabled && app_state.gizmos.hovered.is_none() {
+                    if let Some(pixel) = app_state.cursor_pixel() {
+                        app_state.picking.pending = Some(pixel);
+                    }
+                }
+            }
+            Action::SetRenderResolution(resolution) => {
+                app_state.render_resolution = resolution;
+            }
+            Action::RenderScaleStep(steps) => {
+                app_state.render_resolution = app_state.render_resolution.step(steps);
+                log::debug!("Render resolution: {:?}", app_state.render_resolution);
+            }
+            Action::ToggleUpscaleFilter => {
+                app_state.upscale_filter = match app_state.upscale_filter {
+                    UpscaleFilter::Nearest => UpscaleFilter::Linear,
+                    UpscaleFilter::Linear => UpscaleFilter::Nearest,
+                };
+            }
             Action::ToggleFullscreen => {
-                app_state.is_fullscreen = !app_state.is_fullscreen;
+                app_state.window.fullscreen = !app_state.window.fullscreen;
+            }
+            Action::SetFullscreenMode(mode) => {
+                app_state.window.fullscreen_mode = mode;
+                app_state.window.fullscreen = true;
+            }
+            Action::FullscreenMonitor(monitor) => {
+                app_state.window.monitor = Some(monitor);
+                app_state.window.fullscreen = true;
+            }
+            Action::ToggleAlwaysOnTop => {
+                app_state.window.always_on_top = !app_state.window.always_on_top;
+            }
+            Action::ToggleBorderless => {
+                app_state.window.borderless = !app_state.window.borderless;
+            }
+            Action::ToggleVsync => {
+                app_state.window.vsync = !app_state.window.vsync;
+                log::info!(
+                    "Vsync {}",
+                    if app_state.window.vsync { "on" } else { "off" }
+                );
             }
             Action::Screenshot => {
                 log::debug!("Take screenshot. To be implemented.");
             }
+            Action::RenderPoster => {
+                app_state.poster_requested = true;
+            }
             Action::PrintSource => {
                 shader_service.source();
             }
+            // the dev camera moves when the shader uses it, otherwise iCamPos does
+            Action::CameraMove(camera_movement) if shader_service.use_camera_integration => {
+                app_state.camera.nudge(&camera_movement);
+            }
             Action::CameraMove(camera_movement) => match camera_movement {
                 CameraMovement::MoveForward => {
                     app_state.camera_pos.z += 0.2;
@@ -144,6 +485,12 @@ pub fn handle_actions(
                 CameraMovement::StrafeRight => {
                     app_state.camera_pos.x += 0.2;
                 }
+                CameraMovement::MoveUp => {
+                    app_state.camera_pos.y += 0.2;
+                }
+                CameraMovement::MoveDown => {
+                    app_state.camera_pos.y -= 0.2;
+                }
                 CameraMovement::Reset => {
                     app_state.camera_pos.x = 0.0;
                     app_state.camera_pos.y = 0.0;
@@ -152,6 +499,61 @@ pub fn handle_actions(
             },
 
             Action::TakeSnapshot => shader_service.save_snapshot(),
+            Action::ToggleEditor => app_state.editor.open = !app_state.editor.open,
+            Action::ToggleEditorOverlay => app_state.editor.overlay = !app_state.editor.overlay,
+            Action::EditorCompile => {
+                for (path, text) in app_state.editor.take_pending(true) {
+                    shader_service.set_buffer(&path, text);
+                }
+            }
+            Action::ToggleFileTree => app_state.file_tree_visible = !app_state.file_tree_visible,
+            Action::OpenFile(path, line) => {
+                if !app_state.editor.open_file(&path, line) {
+                    log::warn!("{:?} is not part of the shaders", path);
+                }
+            }
+            Action::OpenFileExternal(path, line) => {
+                let template = app_state.external_editor.as_deref();
+                if let Err(err) = open_in_external_editor(template, &path, line) {
+                    log::error!("{:#}", err);
+                }
+            }
+            Action::OpenFirstError => match shader_service.first_error_location() {
+                Some(location) => {
+                    let template = app_state.external_editor.as_deref();
+                    let (path, line) = (&location.path, location.line);
+                    if external_editor_command(template, path, line).is_none() {
+                        app_state.editor.open_file(path, line);
+                    } else if let Err(err) = open_in_external_editor(template, path, line) {
+                        log::error!("{:#}", err);
+                    }
+                }
+                None => log::info!("No errors to jump to"),
+            },
+            Action::EditorSave => {
+                if let Some(tab) = app_state.editor.active_tab() {
+                    match shader_service.save_buffer(&tab.path, tab.text.clone()) {
+                        Ok(()) => app_state.editor.mark_saved(),
+                        Err(err) => log::error!("Failed to save {:?}: {:?}", tab.path, err),
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Persist the per shader settings, like the timeline, next to the main shader
+fn save_settings(app_state: &AppState, shader_service: &ShaderService) {
+    if let Some(path) = shader_service.main_path() {
+        let settings = ShaderSettings {
+            timeline: app_state.timeline.clone(),
+            display: app_state.display.clone(),
+            camera_bookmarks: app_state.camera_bookmarks.clone(),
+            camera_path: app_state.camera_path.clone(),
+            handles: app_state.gizmos.handles.clone(),
+        };
+        if let Err(err) = settings.save(path) {
+            log::error!("Failed to save settings for {:?}: {:?}", path, err);
         }
     }
 }
@@ -1,11 +1,17 @@
 use std::sync::Arc;
+use std::time::Instant;
+use winit::dpi::LogicalSize;
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::platform::run_return::EventLoopExtRunReturn;
 
 use crate::renderer::Renderer;
 use crate::{
-    handle_actions, handle_events, top_bar, Action, AppConfig, AppState, AppWindow, PlayMode,
-    ShaderService,
+    command_palette, editor_overlay, editor_panel, file_tree_window, handle_actions, handle_events,
+    help_window, inspector_window, pick_window, probe_window, profiler_overlay, render_poster,
+    render_video, save_png, timeline_panel, top_bar, uniforms_window, update_gizmos,
+    update_inspector, Action, AppConfig, AppState, AppWindow, CameraRecording, GpuTimer, PlayMode,
+    PosterConfig, Preferences, Project, RecordingMode, RenderResolution, Session, ShaderService,
+    ShaderSettings, TimeStep, WindowOptions, FLOATING_WINDOWS,
 };
 use ui_backend::Ui;
 
@@ -15,11 +21,94 @@ pub struct App {
     pub app_state: AppState,
     pub ui: Option<Ui>,
     pub gl: Option<Arc<glow::Context>>,
+    /// Loaded from the project file given on the command line
+    pub project: Option<Project>,
+    /// Restored once the shaders and project are loaded
+    pub session: Option<Session>,
 }
 
 impl App {
     pub fn from_config(config: AppConfig) -> Self {
-        let app_state = AppState::default();
+        let mut app_state = AppState::default();
+        if let Some((width, height)) = config.poster {
+            app_state.poster.width = width;
+            app_state.poster.height = height;
+            app_state.poster_requested = true;
+        }
+        app_state.poster.tile_size = config.poster_tile_size;
+        app_state.frame_rate = config.fps.max(1);
+        app_state.upscale_filter = config.upscale_filter;
+        app_state.scene_format = config.scene_format;
+        app_state.camera_kind = config.camera;
+        app_state.camera = config.camera.create();
+        if let Some(path) = &config.camera_recording {
+            match CameraRecording::load(path) {
+                Ok(recording) => {
+                    app_state.camera_recording = recording;
+                    app_state.recording_mode = RecordingMode::Replaying;
+                }
+                Err(err) => log::error!("Failed to load camera recording {:?}: {:?}", path, err),
+            }
+        }
+        if let Some(scale) = config.render_scale {
+            app_state.render_resolution = RenderResolution::Scale(scale.clamp(0.05, 4.0));
+        }
+        if let Some((width, height)) = config.render_size {
+            app_state.render_resolution = RenderResolution::Fixed { width, height };
+        }
+        if config.fixed_timestep {
+            app_state.time_step = TimeStep::Fixed;
+        }
+        app_state.poster.supersample = config.poster_supersample;
+
+        let mut window_options = WindowOptions::default();
+        if let Some(path) = config.config.clone().or_else(Preferences::default_path) {
+            let preferences = Preferences::load(&path);
+            for problem in app_state.key_bindings.apply(&preferences.keys) {
+                log::warn!("Key bindings in {:?}: {}", path, problem);
+            }
+            window_options = preferences.window;
+            app_state.external_editor = preferences.editor.command;
+        }
+        if let Some((width, height)) = window_options.size {
+            app_state.width = width as i32;
+            app_state.height = height as i32;
+        }
+
+        let project = config
+            .project_file()
+            .and_then(|path| match Project::load(path) {
+                Ok(project) => Some(project),
+                Err(err) => {
+                    log::error!("{:?}", err);
+                    app_state.is_running = false;
+                    None
+                }
+            });
+        if let Some(project) = &project {
+            app_state.width = project.window.width.unwrap_or(app_state.width);
+            app_state.height = project.window.height.unwrap_or(app_state.height);
+        }
+        let session = match config.main_path() {
+            Some(path) if !config.fresh => Session::load(&path),
+            _ => None,
+        };
+        if let Some(geometry) = session.as_ref().map(|session| &session.window) {
+            if let Some((width, height)) = geometry.size {
+                app_state.width = width as i32;
+                app_state.height = height as i32;
+            }
+            window_options.position = geometry.position.or(window_options.position);
+            window_options.fullscreen |= geometry.fullscreen;
+        }
+
+        // the command line has the last word on the size
+        app_state.window = config.window_options(window_options);
+        if let Some((width, height)) = config.window_size {
+            app_state.width = width as i32;
+            app_state.height = height as i32;
+        }
+
         let (app_window, event_loop) = AppWindow::new(config, &app_state);
         let ui = None;
         Self {
@@ -28,6 +117,8 @@ impl App {
             app_state,
             ui,
             gl: None,
+            project,
+            session,
         }
     }
 
@@ -38,36 +129,101 @@ impl App {
             app_state,
             gl: _,
             ui: _,
+            project,
+            session,
         } = self;
 
         let mut actions: Vec<Action> = vec![];
 
-        let gl = app_window.create_window_context();
+        let gl = app_window.create_window_context(app_state.window.vsync);
         let mut ui = Ui::new(event_loop, gl.clone());
 
-        let shader_files = config.files.unwrap();
-        log::debug!("Shader files: {:?}", shader_files);
-        let mut shader_service = ShaderService::new(gl.clone(), shader_files);
+        let video = config.video_config();
+        let mut shader_service = match project.take() {
+            Some(project) => ShaderService::from_project(gl.clone(), project),
+            None => {
+                let shader_files = config.shader_files();
+                log::debug!("Shader files: {:?}", shader_files);
+                ShaderService::new(gl.clone(), shader_files)
+            }
+        };
         shader_service.watch();
+
+        // `--fresh` starts without the settings too, not just the session
+        if let Some(path) = shader_service.main_path().filter(|_| !config.fresh) {
+            let settings = ShaderSettings::load(path);
+            app_state.timeline = settings.timeline;
+            app_state.display = settings.display;
+            app_state.camera_bookmarks = settings.camera_bookmarks;
+            app_state.camera_path = settings.camera_path;
+            app_state.gizmos.handles = settings.handles;
+        }
         let _ = shader_service.run(gl.as_ref());
 
-        let renderer = Renderer::new(gl.clone());
+        let mut renderer = Renderer::new(gl.clone());
+        let mut applied_project: Option<Project> = None;
+        let mut applied_window = app_state.window.clone();
+        let mut ui_timer = GpuTimer::new(gl.clone());
+        let mut last_frame_start: Option<Instant> = None;
 
         log::debug!("MainLoop: Start");
 
         while app_state.is_running {
+            let frame_start = Instant::now();
+            let mut frame_delta = 0.0;
+            if let Some(last) = last_frame_start.replace(frame_start) {
+                frame_delta = frame_start.duration_since(last).as_secs_f32();
+                app_state.frame_times.frame.push(frame_delta * 1000.0);
+            }
+
             let _ = shader_service.run(gl.as_ref());
             app_state.shader_error = shader_service.last_error.clone();
+            if let Some(shader) = shader_service.output_shader() {
+                app_state.gizmos.sync_handles(&shader.content.handles);
+            }
+            app_state.editor.sync_tabs(&shader_service.source_files());
+            for path in shader_service.take_changed_files() {
+                if app_state.editor.reload(&path) {
+                    shader_service.discard_buffer(&path);
+                }
+            }
+            if app_state.editor.compile_due(frame_start) {
+                for (path, text) in app_state.editor.take_pending(false) {
+                    shader_service.set_buffer(&path, text);
+                }
+            }
+            if shader_service.project != applied_project {
+                if let Some(project) = &shader_service.project {
+                    project.apply(applied_project.as_ref(), app_state, &mut actions);
+                    let size = (project.window.width, project.window.height);
+                    let resized = applied_project
+                        .as_ref()
+                        .map_or(false, |old| (old.window.width, old.window.height) != size);
+                    if let (true, Some(window)) = (resized, &app_window.window) {
+                        let (width, height) = (
+                            size.0.unwrap_or(app_state.width),
+                            size.1.unwrap_or(app_state.height),
+                        );
+                        window.set_inner_size(LogicalSize::new(width, height));
+                    }
+                }
+                renderer.load_project(shader_service.project.as_ref());
+                applied_project = shader_service.project.clone();
+            }
+            // after the project, so the session wins over its defaults
+            if let Some(session) = session.take() {
+                session.restore(app_state, &mut actions);
+            }
 
-            // force UI open if we have a shader error
-            if app_state.shader_error.is_some() {
+            // force UI open if we have a shader error, the overlay has its own status line
+            if app_state.shader_error.is_some() && !app_state.editor.overlay {
                 app_state.ui_visible = true;
             }
 
             if matches!(app_state.play_mode, PlayMode::Playing) {
                 app_state.timer.start();
                 // TODO(mathias): Remove this. Only use `app_state.timer.delta_time`
-                app_state.delta_time = app_state.timer.delta_time;
+                app_state.delta_time = app_state.step_delta_time();
             }
 
             event_loop.run_return(|event, _window_target, control_flow| {
@@ -76,13 +232,80 @@ impl App {
                 // TODO: No unwrap on the window object
 
                 let _repaint_after = ui.run(app_window.window.as_ref().unwrap(), |egui_ctx| {
+                    app_state.pointer_over_ui = egui_ctx.is_pointer_over_area();
+
+                    if app_state.profiler_visible {
+                        profiler_overlay(egui_ctx, app_state);
+                    }
+
+                    if app_state.inspector.enabled {
+                        inspector_window(egui_ctx, app_state, &mut actions);
+                    }
+
+                    if app_state.command_palette.open {
+                        command_palette(egui_ctx, app_state, &mut actions);
+                    }
+
+                    if app_state.help_visible {
+                        help_window(egui_ctx, app_state);
+                    }
+
+                    // the overlay takes the whole window, the other panels would only get
+                    // in the way of the shader
+                    if app_state.editor.overlay {
+                        editor_overlay(egui_ctx, app_state, &shader_service, &mut actions);
+                        return;
+                    }
+
+                    if !app_state.ui_visible {
+                        return;
+                    }
+
                     egui::TopBottomPanel::top("view_top").show(egui_ctx, |ui| {
                         top_bar(ui, app_state, &mut actions, &shader_service);
                     });
 
+                    egui::TopBottomPanel::bottom("timeline").show(egui_ctx, |ui| {
+                        timeline_panel(ui, app_state, &mut actions);
+                    });
+
+                    if app_state.editor.open {
+                        editor_panel(egui_ctx, app_state, &shader_service, &mut actions);
+                    }
+
+                    if app_state.probes.enabled {
+                        probe_window(egui_ctx, app_state);
+                    }
+
+                    if app_state.file_tree_visible {
+                        file_tree_window(egui_ctx, app_state, &shader_service, &mut actions);
+                    }
+
+                    if app_state.picking.enabled {
+                        pick_window(egui_ctx, app_state, &mut actions);
+                    }
+
+                    if let Some(project) = &shader_service.project {
+                        if !project.uniforms.is_empty() {
+                            uniforms_window(egui_ctx, app_state, project);
+                        }
+                    }
+
                     if let Some(error) = &app_state.shader_error {
                         let mut error = format!("{}", error);
+                        let location = shader_service.first_error_location();
                         egui::TopBottomPanel::bottom("view_bottom").show(egui_ctx, |ui| {
+                            if let Some(location) = location {
+                                let file = location.path.file_name().unwrap_or_default();
+                                let label = format!(
+                                    "Open {}:{} in editor",
+                                    file.to_string_lossy(),
+                                    location.line
+                                );
+                                if ui.button(label).clicked() {
+                                    actions.push(Action::OpenFirstError);
+                                }
+                            }
                             ui.horizontal(|ui| {
                                 ui.add(
                                     egui::TextEdit::multiline(&mut error)
@@ -102,15 +325,67 @@ impl App {
                 handle_actions(&mut actions, app_state, &mut shader_service, control_flow);
             });
 
+            if app_state.poster_requested {
+                app_state.poster_requested = false;
+                save_poster(&mut renderer, app_state, &shader_service);
+
+                // a poster requested from the command line is all we came for
+                if config.poster.is_some() {
+                    app_state.is_running = false;
+                }
+            }
+
+            if let Some(video) = &video {
+                if let Err(err) = render_video(&mut renderer, app_state, &shader_service, video) {
+                    log::error!("Video: {:?}", err);
+                }
+                app_state.is_running = false;
+                break;
+            }
+
+            if app_state.window != applied_window {
+                app_window.apply_options(&app_state.window, &applied_window);
+                applied_window = app_state.window.clone();
+            }
+
+            app_state.camera.set_scene_view(&app_state.scene_view);
+            app_state.camera.update(frame_delta, &app_state.modifier);
+            app_state.record_camera();
+            update_gizmos(app_state);
+
             // Render the OpenGL scene
             renderer.draw(app_state, &shader_service);
+            if app_state.inspector.enabled {
+                update_inspector(&renderer, app_state);
+            }
+            renderer.probe(app_state, &shader_service);
+            renderer.pick(app_state, &shader_service);
 
-            // Render UI on top of OpenGL scene
-            if app_state.ui_visible && app_window.window.is_some() {
+            // Render UI on top of OpenGL scene. The editor overlay, profiler, inspector, command
+            // palette and help are painted even when the rest of the UI is hidden.
+            if app_state.ui_visible
+                || app_state.editor.overlay
+                || app_state.profiler_visible
+                || app_state.inspector.enabled
+                || app_state.command_palette.open
+                || app_state.help_visible
+            {
                 if let Some(window) = &app_window.window {
+                    if let Some(timer) = &mut ui_timer {
+                        timer.begin();
+                    }
                     ui.paint(window);
                 }
             }
+            if let Some(timer) = &mut ui_timer {
+                timer.end();
+                for ms in timer.poll() {
+                    app_state.frame_times.gpu_ui.push(ms);
+                }
+            }
+
+            let cpu_time = frame_start.elapsed().as_secs_f32() * 1000.0;
+            app_state.frame_times.cpu.push(cpu_time);
 
             app_window.swap_buffers();
 
@@ -118,5 +393,61 @@ impl App {
         }
 
         log::debug!("MainLoop: Exit");
+
+        // offline renders leave the session as it was
+        if video.is_none() && config.poster.is_none() {
+            save_session(app_state, &shader_service, &ui, app_window);
+        }
+    }
+}
+
+fn save_session(
+    app_state: &mut AppState,
+    shader_service: &ShaderService,
+    ui: &Ui,
+    app_window: &AppWindow,
+) {
+    let path = match shader_service.main_path() {
+        Some(path) => path,
+        None => return,
+    };
+
+    for title in FLOATING_WINDOWS {
+        if let Some(rect) = ui
+            .egui_ctx
+            .memory(|memory| memory.area_rect(egui::Id::new(title)))
+        {
+            let position = [rect.min.x, rect.min.y];
+            app_state
+                .window_positions
+                .insert(title.to_string(), position);
+        }
+    }
+
+    let mut session = Session::capture(app_state, shader_service.use_camera_integration);
+    session.window.fullscreen = app_state.window.fullscreen;
+    if let (Some(window), false) = (&app_window.window, app_state.window.fullscreen) {
+        let size = window.inner_size().to_logical::<u32>(window.scale_factor());
+        session.window.size = Some((size.width, size.height));
+        session.window.position = window.outer_position().ok().map(|p| (p.x, p.y));
+    }
+
+    if let Err(err) = session.save(path) {
+        log::error!("Failed to save session for {:?}: {:?}", path, err);
+    }
+}
+
+fn save_poster(renderer: &mut Renderer, app_state: &mut AppState, shader_service: &ShaderService) {
+    let poster: PosterConfig = app_state.poster.clone();
+    match render_poster(renderer, app_state, shader_service, &poster) {
+        Ok(image) => {
+            if let Some(path) = shader_service.timestamped_path("posters", "poster", "png") {
+                log::info!("Poster: Saving to {:?}", path);
+                if let Err(err) = save_png(&path, poster.width, poster.height, &image) {
+                    log::error!("Poster: Failed to save {:?}: {:?}", path, err);
+                }
+            }
+        }
+        Err(err) => log::error!("Poster: {}", err),
     }
 }
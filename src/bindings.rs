@@ -0,0 +1,403 @@
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use winit::event::{ElementState, ModifiersState, MouseButton, VirtualKeyCode, WindowEvent};
+
+use crate::camera::CameraKind;
+use crate::{Action, CameraMovement};
+
+/// What has to be pressed for a binding to fire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+}
+
+/// A key or mouse button together with the modifiers held with it, written like `Ctrl+Shift+P`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chord {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    pub trigger: Trigger,
+}
+
+impl Chord {
+    pub fn key(keycode: VirtualKeyCode) -> Self {
+        Self {
+            ctrl: false,
+            shift: false,
+            alt: false,
+            trigger: Trigger::Key(keycode),
+        }
+    }
+
+    pub fn mouse(button: MouseButton) -> Self {
+        Self {
+            ctrl: false,
+            shift: false,
+            alt: false,
+            trigger: Trigger::Mouse(button),
+        }
+    }
+
+    pub fn ctrl(self) -> Self {
+        Self { ctrl: true, ..self }
+    }
+
+    pub fn shift(self) -> Self {
+        Self {
+            shift: true,
+            ..self
+        }
+    }
+
+    /// The chord for a key or mouse button press, `None` for anything else
+    pub fn from_event(event: &WindowEvent<'_>, modifiers: ModifiersState) -> Option<Self> {
+        let trigger = match event {
+            WindowEvent::KeyboardInput { input, .. } if input.state == ElementState::Pressed => {
+                Trigger::Key(input.virtual_keycode?)
+            }
+            WindowEvent::MouseInput {
+                button,
+                state: ElementState::Pressed,
+                ..
+            } => Trigger::Mouse(*button),
+            _ => return None,
+        };
+        Some(Self {
+            ctrl: modifiers.ctrl(),
+            shift: modifiers.shift(),
+            alt: modifiers.alt(),
+            trigger,
+        })
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (held, name) in [
+            (self.ctrl, "Ctrl+"),
+            (self.shift, "Shift+"),
+            (self.alt, "Alt+"),
+        ] {
+            if held {
+                f.write_str(name)?;
+            }
+        }
+        match self.trigger {
+            Trigger::Key(keycode) => {
+                let name = format!("{:?}", keycode);
+                // Key1 reads better as 1
+                match name.strip_prefix("Key") {
+                    Some(digit) if digit.len() == 1 => f.write_str(digit),
+                    _ => f.write_str(&name),
+                }
+            }
+            Trigger::Mouse(MouseButton::Left) => f.write_str("MouseLeft"),
+            Trigger::Mouse(MouseButton::Right) => f.write_str("MouseRight"),
+            Trigger::Mouse(MouseButton::Middle) => f.write_str("MouseMiddle"),
+            Trigger::Mouse(MouseButton::Other(button)) => write!(f, "Mouse{}", button),
+        }
+    }
+}
+
+impl FromStr for Chord {
+    type Err = String;
+
+    /// Modifiers are case insensitive, keys use the `VirtualKeyCode` names. Single letters and
+    /// digits can be written as they are, so `ctrl+s` and `Shift+1` work.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<&str> = s.split('+').map(str::trim).collect();
+        let name = parts.pop().unwrap_or_default();
+        let (mut ctrl, mut shift, mut alt) = (false, false, false);
+        for modifier in parts {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => ctrl = true,
+                "shift" => shift = true,
+                "alt" => alt = true,
+                _ => return Err(format!("Unknown modifier '{}' in '{}'", modifier, s)),
+            }
+        }
+
+        let trigger = match name {
+            "MouseLeft" => Trigger::Mouse(MouseButton::Left),
+            "MouseRight" => Trigger::Mouse(MouseButton::Right),
+            "MouseMiddle" => Trigger::Mouse(MouseButton::Middle),
+            _ => match name.strip_prefix("Mouse").map(str::parse::<u16>) {
+                Some(Ok(button)) => Trigger::Mouse(MouseButton::Other(button)),
+                _ => Trigger::Key(
+                    parse_key(name).ok_or_else(|| format!("Unknown key '{}' in '{}'", name, s))?,
+                ),
+            },
+        };
+        Ok(Self {
+            ctrl,
+            shift,
+            alt,
+            trigger,
+        })
+    }
+}
+
+fn parse_key(name: &str) -> Option<VirtualKeyCode> {
+    let name = match name {
+        "Esc" => "Escape".to_string(),
+        "Enter" => "Return".to_string(),
+        _ if name.len() == 1 && name.chars().all(|c| c.is_ascii_digit()) => format!("Key{}", name),
+        _ if name.len() == 1 => name.to_uppercase(),
+        _ => name.to_string(),
+    };
+    let deserializer: serde::de::value::StringDeserializer<serde::de::value::Error> =
+        name.into_deserializer();
+    VirtualKeyCode::deserialize(deserializer).ok()
+}
+
+/// Which action each chord runs. Starts out with the defaults, entries from the `[keys]` table
+/// of the config file replace them chord by chord.
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+    bindings: Vec<(Chord, Action)>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        use VirtualKeyCode::*;
+
+        let mut bindings = vec![
+            (Chord::key(Escape), Action::AppExit),
+            (Chord::key(P).ctrl().shift(), Action::ToggleCommandPalette),
+            (Chord::key(F1), Action::ToggleHelp),
+            // timeline
+            (Chord::key(Space), Action::TogglePlayPause),
+            (Chord::key(Right), Action::TimeForward(1.0)),
+            (Chord::key(Left), Action::TimeRewind(1.0)),
+            (Chord::key(Key0), Action::TimeStop),
+            (Chord::key(PageUp), Action::FrameStepForward),
+            (Chord::key(PageDown), Action::FrameStepBack),
+            (Chord::key(T), Action::ToggleTimeStep),
+            (Chord::key(M), Action::MarkerAdd),
+            (Chord::key(LBracket), Action::MarkerPrevious),
+            (Chord::key(RBracket), Action::MarkerNext),
+            (Chord::key(L), Action::LoopToggle),
+            // camera
+            (
+                Chord::key(W),
+                Action::CameraMove(CameraMovement::MoveForward),
+            ),
+            (
+                Chord::key(S),
+                Action::CameraMove(CameraMovement::MoveBackward),
+            ),
+            (
+                Chord::key(A),
+                Action::CameraMove(CameraMovement::StrafeLeft),
+            ),
+            (
+                Chord::key(D),
+                Action::CameraMove(CameraMovement::StrafeRight),
+            ),
+            (Chord::key(E), Action::CameraMove(CameraMovement::MoveUp)),
+            (Chord::key(Q), Action::CameraMove(CameraMovement::MoveDown)),
+            (Chord::key(X), Action::CameraMove(CameraMovement::Reset)),
+            (Chord::key(C), Action::CameraKeyframeAdd),
+            (Chord::key(V), Action::CameraPathToggle),
+            (Chord::key(R), Action::CameraRecordToggle),
+            (Chord::key(F), Action::CameraReplayToggle),
+            (Chord::key(Key1), Action::CameraToggleIntegration(false)),
+            (Chord::key(Key2), Action::CameraToggleIntegration(true)),
+            (Chord::key(F5), Action::CameraSelect(CameraKind::Orbit)),
+            (Chord::key(F6), Action::CameraSelect(CameraKind::Fly)),
+            (Chord::key(F7), Action::CameraSelect(CameraKind::Arcball)),
+            (Chord::key(F8), Action::CameraSelect(CameraKind::Pan2D)),
+            (Chord::key(Period), Action::CameraReset),
+            // render resolution
+            (Chord::key(Minus), Action::RenderScaleStep(-1)),
+            (Chord::key(Equals), Action::RenderScaleStep(1)),
+            (Chord::key(F9), Action::ToggleUpscaleFilter),
+            // UI and tools
+            (Chord::key(Tab), Action::UIToggleVisible),
+            (Chord::key(F2), Action::ToggleEditor),
+            (Chord::key(F2).ctrl(), Action::ToggleEditorOverlay),
+            (Chord::key(F2).shift(), Action::ToggleFileTree),
+            (Chord::key(G).ctrl(), Action::OpenFirstError),
+            (Chord::key(Return).ctrl(), Action::EditorCompile),
+            (Chord::key(F3), Action::ToggleProfiler),
+            (Chord::key(I), Action::ToggleInspector),
+            (Chord::key(K), Action::InspectorPin),
+            (Chord::key(F4), Action::ToggleProbes),
+            (Chord::mouse(MouseButton::Middle), Action::ProbeAtCursor),
+            (Chord::key(G), Action::ToggleGizmos),
+            (Chord::key(H), Action::TogglePicking),
+            (Chord::mouse(MouseButton::Left), Action::PickAtCursor),
+            (Chord::key(F11), Action::ToggleFullscreen),
+            (Chord::key(P), Action::PrintSource),
+            (Chord::key(F10), Action::RenderPoster),
+            (Chord::key(F12), Action::TakeSnapshot),
+        ];
+
+        // camera bookmarks, ctrl + number saves and shift + number recalls
+        let numbers = [Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9];
+        for (slot, key) in (1..).zip(numbers) {
+            bindings.push((Chord::key(key).ctrl(), Action::CameraBookmarkSave(slot)));
+            bindings.push((Chord::key(key).shift(), Action::CameraBookmarkRecall(slot)));
+        }
+
+        Self { bindings }
+    }
+}
+
+impl KeyBindings {
+    pub fn iter(&self) -> impl Iterator<Item = &(Chord, Action)> {
+        self.bindings.iter()
+    }
+
+    /// Bind `chord` to `action`, replacing whatever it was bound to
+    pub fn bind(&mut self, chord: Chord, action: Action) {
+        match self.bindings.iter_mut().find(|(bound, _)| *bound == chord) {
+            Some(binding) => binding.1 = action,
+            None => self.bindings.push((chord, action)),
+        }
+    }
+
+    /// The action for a chord. Ctrl and Shift also make seeking and moving faster or slower,
+    /// so without a binding for the exact chord the key without them is tried.
+    pub fn action(&self, chord: &Chord) -> Option<&Action> {
+        let find = |chord: &Chord| {
+            self.bindings
+                .iter()
+                .find(|(bound, _)| bound == chord)
+                .map(|(_, action)| action)
+        };
+        find(chord).or_else(|| {
+            find(&Chord {
+                ctrl: false,
+                shift: false,
+                ..*chord
+            })
+        })
+    }
+
+    /// Every chord bound to `action`
+    pub fn chords(&self, action: &Action) -> Vec<Chord> {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| bound == action)
+            .map(|(chord, _)| *chord)
+            .collect()
+    }
+
+    /// Apply the `[keys]` table of the config file. Entries that can't be parsed are skipped,
+    /// as are entries that end up on the same chord, like `ctrl+s` and `Ctrl+S`.
+    /// Returns what was wrong with them.
+    pub fn apply(&mut self, entries: &BTreeMap<String, Action>) -> Vec<String> {
+        let mut problems = vec![];
+        let mut parsed: Vec<(&str, Chord, &Action)> = vec![];
+        for (name, action) in entries {
+            match name.parse::<Chord>() {
+                Ok(chord) => parsed.push((name, chord, action)),
+                Err(err) => problems.push(err),
+            }
+        }
+
+        for (name, chord, action) in &parsed {
+            let conflicts: Vec<&str> = parsed
+                .iter()
+                .filter(|(other, other_chord, _)| other != name && other_chord == chord)
+                .map(|(other, _, _)| *other)
+                .collect();
+            if conflicts.is_empty() {
+                self.bind(*chord, (*action).clone());
+            } else {
+                problems.push(format!(
+                    "'{}' is the same chord as '{}', keeping the default for {}",
+                    name,
+                    conflicts.join("', '"),
+                    chord
+                ));
+            }
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Chord, KeyBindings, Trigger};
+    use crate::Action;
+    use std::collections::BTreeMap;
+    use winit::event::{MouseButton, VirtualKeyCode};
+
+    #[test]
+    fn chord_names() {
+        let chord: Chord = "ctrl+shift+p".parse().unwrap();
+        assert!(chord.ctrl && chord.shift && !chord.alt);
+        assert_eq!(chord.trigger, Trigger::Key(VirtualKeyCode::P));
+        assert_eq!(chord.to_string(), "Ctrl+Shift+P");
+
+        for name in ["Shift+1", "F11", "Alt+PageUp", "MouseMiddle", "Ctrl+Mouse4"] {
+            assert_eq!(name.parse::<Chord>().unwrap().to_string(), name);
+        }
+        assert_eq!(
+            "Esc".parse::<Chord>().unwrap(),
+            Chord::key(VirtualKeyCode::Escape)
+        );
+        assert!("Hyper+A".parse::<Chord>().is_err());
+        assert!("Ctrl+Nope".parse::<Chord>().is_err());
+    }
+
+    #[test]
+    fn modifiers_fall_back_to_the_key() {
+        let bindings = KeyBindings::default();
+        let right = Chord::key(VirtualKeyCode::Right);
+        assert_eq!(
+            bindings.action(&right.shift()),
+            Some(&Action::TimeForward(1.0))
+        );
+
+        let one = Chord::key(VirtualKeyCode::Key1);
+        assert_eq!(
+            bindings.action(&one.ctrl()),
+            Some(&Action::CameraBookmarkSave(1))
+        );
+        assert_eq!(
+            bindings.action(&one),
+            Some(&Action::CameraToggleIntegration(false))
+        );
+        assert_eq!(
+            bindings.action(&Chord::mouse(MouseButton::Middle)),
+            Some(&Action::ProbeAtCursor)
+        );
+    }
+
+    #[test]
+    fn config_replaces_defaults_and_reports_conflicts() {
+        let config = r#"
+            "Space" = "TimeStop"
+            "Right" = { TimeForward = 5.0 }
+            "ctrl+s" = "TakeSnapshot"
+            "Ctrl+S" = "RenderPoster"
+            "Ctrl+Nope" = "AppExit"
+        "#;
+        let entries: BTreeMap<String, Action> = toml::from_str(config).unwrap();
+
+        let mut bindings = KeyBindings::default();
+        let problems = bindings.apply(&entries);
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+
+        let space = Chord::key(VirtualKeyCode::Space);
+        assert_eq!(bindings.action(&space), Some(&Action::TimeStop));
+        assert!(bindings.chords(&Action::TogglePlayPause).is_empty());
+        assert_eq!(
+            bindings.action(&Chord::key(VirtualKeyCode::Right)),
+            Some(&Action::TimeForward(5.0))
+        );
+        // the conflicting entries are left out, Ctrl+S still falls back to S
+        assert_eq!(
+            bindings.chords(&Action::TakeSnapshot),
+            vec![Chord::key(VirtualKeyCode::F12)]
+        );
+    }
+}
@@ -0,0 +1,726 @@
+use glam::{IVec2, Vec3};
+
+use crate::camera::CameraKind;
+use crate::utils::parse_size;
+use crate::{Action, CameraMovement, FullscreenMode, RenderResolution};
+
+/// Groups actions in the command palette and the help window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ActionCategory {
+    App,
+    Timeline,
+    Camera,
+    Tools,
+    Render,
+}
+
+impl ActionCategory {
+    pub const ALL: [ActionCategory; 5] = [
+        ActionCategory::App,
+        ActionCategory::Timeline,
+        ActionCategory::Camera,
+        ActionCategory::Tools,
+        ActionCategory::Render,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ActionCategory::App => "App",
+            ActionCategory::Timeline => "Timeline",
+            ActionCategory::Camera => "Camera",
+            ActionCategory::Tools => "Tools",
+            ActionCategory::Render => "Render",
+        }
+    }
+}
+
+/// How an action presents itself to the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActionInfo {
+    pub name: &'static str,
+    pub category: ActionCategory,
+    pub description: &'static str,
+    /// What the command palette asks for before running the action, `None` if it takes no
+    /// value or the value is part of the command, like the camera in `CameraSelect`
+    pub parameter: Option<&'static str>,
+}
+
+const fn info(
+    name: &'static str,
+    category: ActionCategory,
+    description: &'static str,
+) -> ActionInfo {
+    ActionInfo {
+        name,
+        category,
+        description,
+        parameter: None,
+    }
+}
+
+const fn with_parameter(
+    name: &'static str,
+    category: ActionCategory,
+    description: &'static str,
+    parameter: &'static str,
+) -> ActionInfo {
+    ActionInfo {
+        name,
+        category,
+        description,
+        parameter: Some(parameter),
+    }
+}
+
+impl Action {
+    /// One entry per command, in the order they are listed. Actions that take a number hold a
+    /// default for it, actions that take one of a few choices are listed once per choice.
+    /// `WindowResize` is left out, it only comes from the window, and so are the file actions,
+    /// they come from the file tree. `WindowClose` does nothing yet.
+    pub fn all() -> Vec<Action> {
+        let mut actions = vec![
+            Action::AppExit,
+            Action::ToggleCommandPalette,
+            Action::ToggleHelp,
+            Action::UIToggleVisible,
+            Action::ToggleFullscreen,
+            Action::SetFullscreenMode(FullscreenMode::Borderless),
+            Action::SetFullscreenMode(FullscreenMode::Exclusive),
+            Action::FullscreenMonitor(0),
+            Action::ToggleAlwaysOnTop,
+            Action::ToggleBorderless,
+            Action::ToggleVsync,
+            Action::TimePlay,
+            Action::TimePause,
+            Action::TogglePlayPause,
+            Action::TimeStop,
+            Action::TimeForward(1.0),
+            Action::TimeRewind(1.0),
+            Action::TimeSeek(0.0),
+            Action::TimeSpeed(1.0),
+            Action::FrameStepForward,
+            Action::FrameStepBack,
+            Action::ToggleTimeStep,
+            Action::MarkerAdd,
+            Action::MarkerRemove(0),
+            Action::MarkerNext,
+            Action::MarkerPrevious,
+            Action::LoopToggle,
+            Action::LoopSetStart,
+            Action::LoopSetEnd,
+            Action::CameraToggleIntegration(true),
+            Action::CameraToggleIntegration(false),
+            Action::CameraReset,
+        ];
+        actions.extend(CameraKind::ALL.map(Action::CameraSelect));
+        actions.extend(
+            [
+                CameraMovement::MoveForward,
+                CameraMovement::MoveBackward,
+                CameraMovement::StrafeLeft,
+                CameraMovement::StrafeRight,
+                CameraMovement::MoveUp,
+                CameraMovement::MoveDown,
+                CameraMovement::Reset,
+            ]
+            .map(Action::CameraMove),
+        );
+        actions.extend([
+            Action::CameraBookmarkSave(1),
+            Action::CameraBookmarkRecall(1),
+            Action::CameraBookmarkRemove(1),
+            Action::CameraKeyframeAdd,
+            Action::CameraKeyframeUpdate(0),
+            Action::CameraKeyframeRemove(0),
+            Action::CameraPathToggle,
+            Action::CameraOrbitAround(Vec3::ZERO),
+            Action::CameraRecordToggle,
+            Action::CameraReplayToggle,
+            Action::CameraRecordingSmooth(0.25),
+            Action::CameraRecordingToPath(0.5),
+            Action::CameraRecordingExport,
+            Action::CameraRecordingClear,
+            Action::ToggleProfiler,
+            Action::ToggleInspector,
+            Action::InspectorPin,
+            Action::InspectorRemovePin(0),
+            Action::ToggleProbes,
+            Action::ProbeAtCursor,
+            Action::ProbeSelectPixel(IVec2::ZERO),
+            Action::ToggleGizmos,
+            Action::TogglePicking,
+            Action::PickAtCursor,
+            Action::PickPixel(IVec2::ZERO),
+            Action::PrintSource,
+            Action::TakeSnapshot,
+            Action::ToggleEditor,
+            Action::ToggleEditorOverlay,
+            Action::EditorCompile,
+            Action::EditorSave,
+            Action::ToggleFileTree,
+            Action::OpenFirstError,
+            Action::SetRenderResolution(RenderResolution::default()),
+            Action::RenderScaleStep(1),
+            Action::RenderScaleStep(-1),
+            Action::ToggleUpscaleFilter,
+            Action::Screenshot,
+            Action::RenderPoster,
+        ]);
+        actions
+    }
+
+    pub fn info(&self) -> ActionInfo {
+        use ActionCategory::*;
+
+        match self {
+            Action::AppExit => info("Quit", App, "Save the settings and close skuggbox"),
+            Action::ToggleCommandPalette => {
+                info("Command palette", App, "Search every action and run it")
+            }
+            Action::ToggleHelp => info("Help", App, "List every action and its key binding"),
+            Action::UIToggleVisible => info("Toggle UI", App, "Show or hide all panels"),
+            Action::ToggleFullscreen => info("Toggle fullscreen", App, "Fill the screen"),
+            Action::SetFullscreenMode(FullscreenMode::Borderless) => info(
+                "Borderless fullscreen",
+                App,
+                "Cover the monitor with a borderless window",
+            ),
+            Action::SetFullscreenMode(FullscreenMode::Exclusive) => info(
+                "Exclusive fullscreen",
+                App,
+                "Switch the monitor to its largest video mode",
+            ),
+            Action::FullscreenMonitor(_) => with_parameter(
+                "Fullscreen on monitor",
+                App,
+                "Go fullscreen on another monitor, counting from 0",
+                "monitor",
+            ),
+            Action::ToggleAlwaysOnTop => {
+                info("Toggle always on top", App, "Keep the window above others")
+            }
+            Action::ToggleBorderless => info("Toggle borderless", App, "Hide the title bar"),
+            Action::ToggleVsync => info("Toggle vsync", App, "Wait for the display to swap"),
+            Action::WindowClose => info("Close window", App, "Not implemented yet"),
+            Action::WindowResize(_) => info("Resize window", App, "Sent when the window resizes"),
+
+            Action::TimePlay => info("Play", Timeline, "Start playback"),
+            Action::TimePause => info("Pause", Timeline, "Pause playback"),
+            Action::TogglePlayPause => info("Play / pause", Timeline, "Toggle playback"),
+            Action::TimeStop => info("Stop", Timeline, "Pause and rewind to the start"),
+            Action::TimeForward(_) => with_parameter(
+                "Forward",
+                Timeline,
+                "Jump ahead, Ctrl and Shift make the step larger or smaller",
+                "seconds",
+            ),
+            Action::TimeRewind(_) => with_parameter(
+                "Rewind",
+                Timeline,
+                "Jump back, Ctrl and Shift make the step larger or smaller",
+                "seconds",
+            ),
+            Action::TimeSeek(_) => with_parameter("Seek", Timeline, "Jump to a time", "seconds"),
+            Action::TimeSpeed(_) => {
+                with_parameter("Playback speed", Timeline, "Scale playback", "speed")
+            }
+            Action::FrameStepForward => info("Next frame", Timeline, "Step a single frame ahead"),
+            Action::FrameStepBack => info("Previous frame", Timeline, "Step a single frame back"),
+            Action::ToggleTimeStep => info(
+                "Toggle fixed timestep",
+                Timeline,
+                "Advance exactly one frame per frame or follow the wall clock",
+            ),
+            Action::MarkerAdd => info("Add marker", Timeline, "Mark the current time"),
+            Action::MarkerRemove(_) => {
+                with_parameter("Remove marker", Timeline, "Delete a marker", "marker index")
+            }
+            Action::MarkerNext => info("Next marker", Timeline, "Jump to the next marker"),
+            Action::MarkerPrevious => {
+                info("Previous marker", Timeline, "Jump to the previous marker")
+            }
+            Action::LoopToggle => info("Toggle loop", Timeline, "Loop the A-B region"),
+            Action::LoopSetStart => info("Set loop start", Timeline, "Loop from the current time"),
+            Action::LoopSetEnd => info("Set loop end", Timeline, "Loop up to the current time"),
+
+            Action::CameraToggleIntegration(true) => info(
+                "Dev camera",
+                Camera,
+                "Drive shaders with #pragma skuggbox(camera) from the camera",
+            ),
+            Action::CameraToggleIntegration(false) => info(
+                "Shader camera",
+                Camera,
+                "Let the shader place its own camera",
+            ),
+            Action::CameraReset => info("Reset camera", Camera, "Start over with a fresh camera"),
+            Action::CameraSelect(CameraKind::Orbit) => {
+                info("Orbit camera", Camera, "Revolve around a target")
+            }
+            Action::CameraSelect(CameraKind::Fly) => info(
+                "Fly camera",
+                Camera,
+                "WASD, Q and E to move, right mouse button to look",
+            ),
+            Action::CameraSelect(CameraKind::Arcball) => {
+                info("Arcball camera", Camera, "Rotate freely around a target")
+            }
+            Action::CameraSelect(CameraKind::Pan2D) => {
+                info("2D camera", Camera, "Pan and zoom flat shaders")
+            }
+            Action::CameraMove(CameraMovement::MoveForward) => {
+                info("Move forward", Camera, "Step the camera, or iCamPos")
+            }
+            Action::CameraMove(CameraMovement::MoveBackward) => {
+                info("Move backward", Camera, "Step the camera, or iCamPos")
+            }
+            Action::CameraMove(CameraMovement::StrafeLeft) => {
+                info("Move left", Camera, "Step the camera, or iCamPos")
+            }
+            Action::CameraMove(CameraMovement::StrafeRight) => {
+                info("Move right", Camera, "Step the camera, or iCamPos")
+            }
+            Action::CameraMove(CameraMovement::MoveUp) => {
+                info("Move up", Camera, "Step the camera, or iCamPos")
+            }
+            Action::CameraMove(CameraMovement::MoveDown) => {
+                info("Move down", Camera, "Step the camera, or iCamPos")
+            }
+            Action::CameraMove(CameraMovement::Reset) => {
+                info("Move back home", Camera, "Undo the camera steps")
+            }
+            Action::CameraBookmarkSave(_) => {
+                with_parameter("Save view", Camera, "Bookmark the current view", "slot 1-9")
+            }
+            Action::CameraBookmarkRecall(_) => {
+                with_parameter("Recall view", Camera, "Go to a bookmarked view", "slot 1-9")
+            }
+            Action::CameraBookmarkRemove(_) => with_parameter(
+                "Remove view",
+                Camera,
+                "Delete a bookmarked view",
+                "slot 1-9",
+            ),
+            Action::CameraKeyframeAdd => info(
+                "Add camera keyframe",
+                Camera,
+                "Put the current view on the camera path at the current time",
+            ),
+            Action::CameraKeyframeUpdate(_) => with_parameter(
+                "Update camera keyframe",
+                Camera,
+                "Move a keyframe to the current view",
+                "keyframe index",
+            ),
+            Action::CameraKeyframeRemove(_) => with_parameter(
+                "Remove camera keyframe",
+                Camera,
+                "Delete a keyframe from the camera path",
+                "keyframe index",
+            ),
+            Action::CameraPathToggle => {
+                info("Toggle camera path", Camera, "Fly along the keyframes")
+            }
+            Action::CameraOrbitAround(_) => with_parameter(
+                "Orbit around point",
+                Camera,
+                "Keep the camera in place and revolve around a point",
+                "x y z",
+            ),
+            Action::CameraRecordToggle => info(
+                "Record camera",
+                Camera,
+                "Capture camera moves from the current time on",
+            ),
+            Action::CameraReplayToggle => info(
+                "Replay camera",
+                Camera,
+                "Drive the camera from the recording",
+            ),
+            Action::CameraRecordingSmooth(_) => with_parameter(
+                "Smooth camera recording",
+                Camera,
+                "Average the recording over a time window",
+                "window in seconds",
+            ),
+            Action::CameraRecordingToPath(_) => with_parameter(
+                "Camera recording to path",
+                Camera,
+                "Replace the camera path with keyframes from the recording",
+                "seconds between keyframes",
+            ),
+            Action::CameraRecordingExport => info(
+                "Export camera recording",
+                Camera,
+                "Save the recording next to the shader",
+            ),
+            Action::CameraRecordingClear => info("Clear camera recording", Camera, "Start over"),
+
+            Action::ToggleProfiler => info("Toggle frame times", Tools, "Show CPU and GPU times"),
+            Action::ToggleInspector => info(
+                "Toggle inspector",
+                Tools,
+                "Show the value under the cursor and a zoom lens",
+            ),
+            Action::InspectorPin => info("Pin pixel", Tools, "Keep watching the hovered pixel"),
+            Action::InspectorRemovePin(_) => {
+                with_parameter("Unpin pixel", Tools, "Stop watching a pixel", "pin index")
+            }
+            Action::ToggleProbes => info("Toggle probes", Tools, "Capture sbProbe values"),
+            Action::ProbeAtCursor => info("Probe at cursor", Tools, "Probe the hovered pixel"),
+            Action::ProbeSelectPixel(_) => {
+                with_parameter("Probe pixel", Tools, "Probe a render target pixel", "x y")
+            }
+            Action::ToggleGizmos => info(
+                "Toggle gizmos",
+                Tools,
+                "Show the grid, axes and point handles",
+            ),
+            Action::TogglePicking => info("Toggle pick mode", Tools, "Click to read back sbPick"),
+            Action::PickAtCursor => info("Pick at cursor", Tools, "Pick the hovered pixel"),
+            Action::PickPixel(_) => {
+                with_parameter("Pick pixel", Tools, "Pick a render target pixel", "x y")
+            }
+            Action::PrintSource => info("Print source", Tools, "Log the pre-processed shader"),
+            Action::TakeSnapshot => info(
+                "Take snapshot",
+                Tools,
+                "Save the shader source to shader_dir/snapshots/",
+            ),
+            Action::ToggleEditor => info("Toggle editor", Tools, "Edit the shader files in place"),
+            Action::ToggleEditorOverlay => info(
+                "Toggle editor overlay",
+                Tools,
+                "Live code over the shader, Bonzomatic style",
+            ),
+            Action::EditorCompile => info(
+                "Compile editor text",
+                Tools,
+                "Compile the unsaved text in the editor now",
+            ),
+            Action::ToggleFileTree => info(
+                "Toggle file tree",
+                Tools,
+                "Show the include tree with sizes and errors",
+            ),
+            Action::OpenFile(..) => info("Open file", Tools, "Show a file in the editor"),
+            Action::OpenFileExternal(..) => info(
+                "Open file externally",
+                Tools,
+                "Open a file in your own editor",
+            ),
+            Action::OpenFirstError => info(
+                "Jump to first error",
+                Tools,
+                "Open the file and line of the first error in your editor",
+            ),
+            Action::EditorSave => info("Save editor file", Tools, "Write the active tab to disk"),
+
+            Action::SetRenderResolution(_) => with_parameter(
+                "Render resolution",
+                Render,
+                "Render at a scale of the window or a fixed size",
+                "scale or WIDTHxHEIGHT",
+            ),
+            Action::RenderScaleStep(1) => info(
+                "Raise render resolution",
+                Render,
+                "Next larger render scale",
+            ),
+            Action::RenderScaleStep(-1) => info(
+                "Lower render resolution",
+                Render,
+                "Next smaller render scale",
+            ),
+            Action::RenderScaleStep(_) => with_parameter(
+                "Step render resolution",
+                Render,
+                "Move through the render scales",
+                "steps",
+            ),
+            Action::ToggleUpscaleFilter => info(
+                "Toggle upscale filter",
+                Render,
+                "Nearest or linear upscaling",
+            ),
+            Action::Screenshot => info("Screenshot", Render, "Not implemented yet"),
+            Action::RenderPoster => info(
+                "Render poster",
+                Render,
+                "Render a poster with the current settings",
+            ),
+        }
+    }
+
+    /// The value the command palette suggests, as the user would type it
+    pub fn parameter_value(&self) -> String {
+        match self {
+            Action::TimeForward(value)
+            | Action::TimeRewind(value)
+            | Action::TimeSeek(value)
+            | Action::TimeSpeed(value)
+            | Action::CameraRecordingSmooth(value)
+            | Action::CameraRecordingToPath(value) => value.to_string(),
+            Action::MarkerRemove(index)
+            | Action::CameraKeyframeUpdate(index)
+            | Action::CameraKeyframeRemove(index)
+            | Action::InspectorRemovePin(index)
+            | Action::FullscreenMonitor(index) => index.to_string(),
+            Action::CameraBookmarkSave(slot)
+            | Action::CameraBookmarkRecall(slot)
+            | Action::CameraBookmarkRemove(slot) => slot.to_string(),
+            Action::CameraOrbitAround(point) => format!("{} {} {}", point.x, point.y, point.z),
+            Action::ProbeSelectPixel(pixel) | Action::PickPixel(pixel) => {
+                format!("{} {}", pixel.x, pixel.y)
+            }
+            Action::SetRenderResolution(RenderResolution::Scale(scale)) => scale.to_string(),
+            Action::SetRenderResolution(RenderResolution::Fixed { width, height }) => {
+                format!("{}x{}", width, height)
+            }
+            Action::RenderScaleStep(steps) => steps.to_string(),
+            _ => String::new(),
+        }
+    }
+
+    /// The same action with the value typed into the command palette
+    pub fn with_parameter(&self, value: &str) -> Result<Action, String> {
+        let value = value.trim();
+        let number = || {
+            value
+                .parse::<f32>()
+                .map_err(|_| format!("'{}' is not a number", value))
+        };
+        let index = || {
+            value
+                .parse::<usize>()
+                .map_err(|_| format!("'{}' is not an index", value))
+        };
+        let numbers = |count: usize| {
+            let numbers = value
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|part| !part.is_empty())
+                .map(str::parse::<f32>)
+                .collect::<Result<Vec<f32>, _>>()
+                .ok()
+                .filter(|numbers| numbers.len() == count);
+            numbers.ok_or_else(|| format!("Expected {} numbers, got '{}'", count, value))
+        };
+        let slot = || match value.parse::<u8>() {
+            Ok(slot @ 1..=9) => Ok(slot),
+            _ => Err(format!("Expected a slot from 1 to 9, got '{}'", value)),
+        };
+
+        Ok(match self {
+            Action::TimeForward(_) => Action::TimeForward(number()?),
+            Action::TimeRewind(_) => Action::TimeRewind(number()?),
+            Action::TimeSeek(_) => Action::TimeSeek(number()?),
+            Action::TimeSpeed(_) => Action::TimeSpeed(number()?),
+            Action::CameraRecordingSmooth(_) => Action::CameraRecordingSmooth(number()?),
+            Action::CameraRecordingToPath(_) => Action::CameraRecordingToPath(number()?),
+            Action::MarkerRemove(_) => Action::MarkerRemove(index()?),
+            Action::CameraKeyframeUpdate(_) => Action::CameraKeyframeUpdate(index()?),
+            Action::CameraKeyframeRemove(_) => Action::CameraKeyframeRemove(index()?),
+            Action::InspectorRemovePin(_) => Action::InspectorRemovePin(index()?),
+            Action::FullscreenMonitor(_) => Action::FullscreenMonitor(index()?),
+            Action::CameraBookmarkSave(_) => Action::CameraBookmarkSave(slot()?),
+            Action::CameraBookmarkRecall(_) => Action::CameraBookmarkRecall(slot()?),
+            Action::CameraBookmarkRemove(_) => Action::CameraBookmarkRemove(slot()?),
+            Action::CameraOrbitAround(_) => {
+                Action::CameraOrbitAround(Vec3::from_slice(&numbers(3)?))
+            }
+            Action::ProbeSelectPixel(_) => {
+                Action::ProbeSelectPixel(glam::Vec2::from_slice(&numbers(2)?).as_ivec2())
+            }
+            Action::PickPixel(_) => {
+                Action::PickPixel(glam::Vec2::from_slice(&numbers(2)?).as_ivec2())
+            }
+            Action::SetRenderResolution(_) => match parse_size(value) {
+                Ok((width, height)) => {
+                    Action::SetRenderResolution(RenderResolution::Fixed { width, height })
+                }
+                Err(_) => {
+                    Action::SetRenderResolution(RenderResolution::Scale(number()?.clamp(0.05, 4.0)))
+                }
+            },
+            Action::RenderScaleStep(_) => Action::RenderScaleStep(
+                value
+                    .parse::<i32>()
+                    .map_err(|_| format!("'{}' is not a whole number", value))?,
+            ),
+            action => action.clone(),
+        })
+    }
+}
+
+/// Score how well `query` matches `text`, higher is better. Every character of the query has to
+/// appear in order, case doesn't matter. Matches at the start of words and runs of matching
+/// characters score higher. `None` if it doesn't match at all.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for wanted in query.chars().flat_map(char::to_lowercase) {
+        if wanted.is_whitespace() {
+            continue;
+        }
+        let found = position + text[position..].iter().position(|c| *c == wanted)?;
+        score += 1;
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 8;
+        }
+        if previous.map_or(false, |previous| previous + 1 == found) {
+            score += 5;
+        }
+        // prefer matches early on
+        score -= (found - position).min(10) as i32 / 2;
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+/// State of the command palette window
+#[derive(Debug, Clone, Default)]
+pub struct CommandPalette {
+    pub open: bool,
+    pub query: String,
+    /// Index into the filtered list
+    pub selected: usize,
+    /// Action waiting for its value
+    pub pending: Option<Action>,
+    pub parameter: String,
+    pub error: Option<String>,
+}
+
+impl CommandPalette {
+    pub fn toggle(&mut self) {
+        *self = Self {
+            open: !self.open,
+            ..Default::default()
+        };
+    }
+
+    /// Every action matching the query with its label, best match first
+    pub fn matches(&self) -> Vec<(Action, String)> {
+        let mut matches: Vec<(i32, Action, String)> = Action::all()
+            .into_iter()
+            .filter_map(|action| {
+                let info = action.info();
+                let label = format!("{}: {}", info.category.name(), info.name);
+                let score = fuzzy_score(&self.query, &label)?;
+                Some((score, action, label))
+            })
+            .collect();
+        // stable, so equal scores keep the catalogue order
+        matches.sort_by_key(|(score, _, _)| -score);
+        matches
+            .into_iter()
+            .map(|(_, action, label)| (action, label))
+            .collect()
+    }
+
+    /// Run `action`, or ask for its value first. Returns the action once it's ready to go.
+    pub fn choose(&mut self, action: Action) -> Option<Action> {
+        if action.info().parameter.is_some() {
+            self.parameter = action.parameter_value();
+            self.pending = Some(action);
+            self.error = None;
+            return None;
+        }
+        self.open = false;
+        Some(action)
+    }
+
+    /// Run the pending action with the value typed so far
+    pub fn confirm_parameter(&mut self) -> Option<Action> {
+        let pending = self.pending.as_ref()?;
+        match pending.with_parameter(&self.parameter) {
+            Ok(action) => {
+                self.open = false;
+                self.pending = None;
+                Some(action)
+            }
+            Err(err) => {
+                self.error = Some(err);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fuzzy_score, CommandPalette};
+    use crate::{Action, KeyBindings, RenderResolution};
+    use glam::Vec3;
+
+    #[test]
+    fn every_bound_action_is_listed() {
+        let all = Action::all();
+        for (chord, action) in KeyBindings::default().iter() {
+            let listed = all
+                .iter()
+                .any(|other| other.info().name == action.info().name);
+            assert!(listed, "{} runs {:?} which isn't listed", chord, action);
+        }
+
+        let mut names: Vec<&str> = all.iter().map(|action| action.info().name).collect();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), all.len(), "names have to be unique");
+    }
+
+    #[test]
+    fn fuzzy_matching() {
+        assert!(fuzzy_score("tpp", "Timeline: Play / pause").is_some());
+        assert!(fuzzy_score("xyz", "Timeline: Play / pause").is_none());
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+
+        let word_start = fuzzy_score("fly", "Camera: Fly camera").unwrap();
+        let scattered = fuzzy_score("fly", "App: Fill the display").unwrap();
+        assert!(word_start > scattered);
+
+        let palette = CommandPalette {
+            query: "cam fly".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(palette.matches()[0].1, "Camera: Fly camera");
+    }
+
+    #[test]
+    fn parameters() {
+        let mut palette = CommandPalette {
+            open: true,
+            ..Default::default()
+        };
+        assert_eq!(palette.choose(Action::TimeForward(1.0)), None);
+        assert_eq!(palette.parameter, "1");
+
+        palette.parameter = "soon".to_string();
+        assert_eq!(palette.confirm_parameter(), None);
+        assert!(palette.error.is_some());
+
+        palette.parameter = "2.5".to_string();
+        assert_eq!(palette.confirm_parameter(), Some(Action::TimeForward(2.5)));
+        assert!(!palette.open);
+
+        let orbit = Action::CameraOrbitAround(Vec3::ZERO).with_parameter("1, 2 3");
+        assert_eq!(
+            orbit,
+            Ok(Action::CameraOrbitAround(Vec3::new(1.0, 2.0, 3.0)))
+        );
+        assert_eq!(
+            Action::SetRenderResolution(RenderResolution::default()).with_parameter("640x360"),
+            Ok(Action::SetRenderResolution(RenderResolution::Fixed {
+                width: 640,
+                height: 360
+            }))
+        );
+        assert!(Action::CameraBookmarkSave(1).with_parameter("12").is_err());
+        assert_eq!(
+            palette.choose(Action::TogglePlayPause),
+            Some(Action::TogglePlayPause)
+        );
+    }
+}
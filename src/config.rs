@@ -2,15 +2,216 @@ use std::path::PathBuf;
 
 use clap::Parser;
 
+use crate::camera::CameraKind;
+use crate::utils::{parse_position, parse_size};
+use crate::{FullscreenMode, TargetFormat, UpscaleFilter, VideoConfig, WindowOptions};
+
 #[derive(Parser, Debug, Clone)]
 #[command(about = "skuggbox", long_about = "Skuggbox GLSL shader viewer")]
 pub struct AppConfig {
+    /// Config file with key bindings and other preferences.
+    /// Defaults to skuggbox/config.toml in the user's config directory.
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Shader, or project file ending in .toml, to open
+    #[arg(value_name = "FILE")]
+    pub file: Option<PathBuf>,
+
     #[arg(short, long, name = "SHADER_FILES")]
     pub files: Option<Vec<PathBuf>>,
 
     #[arg(short, long)]
     pub always_on_top: bool,
 
+    /// Window without title bar and border
+    #[arg(long)]
+    pub borderless: bool,
+
+    /// Start fullscreen, borderless unless exclusive is given
+    #[arg(long, value_enum, value_name = "MODE", num_args = 0..=1, default_missing_value = "borderless")]
+    pub fullscreen: Option<FullscreenMode>,
+
+    /// Monitor to go fullscreen on, counting from 0
+    #[arg(long, value_name = "INDEX")]
+    pub monitor: Option<usize>,
+
+    /// Initial window size, e.g. 1280x720
+    #[arg(long, value_parser = parse_size, value_name = "WIDTHxHEIGHT")]
+    pub window_size: Option<(u32, u32)>,
+
+    /// Initial window position in pixels, e.g. 100,50
+    #[arg(long, value_parser = parse_position, value_name = "X,Y", allow_hyphen_values = true)]
+    pub window_position: Option<(i32, i32)>,
+
+    /// Swap buffers as fast as possible instead of waiting for the display
+    #[arg(long)]
+    pub no_vsync: bool,
+
     #[arg(short, long, name = "NEW_FILE")]
     pub new: Option<PathBuf>,
+
+    /// Ignore the saved session and shader settings and start from the defaults
+    #[arg(long)]
+    pub fresh: bool,
+
+    /// Advance time by exactly 1/fps per frame instead of following the wall clock
+    #[arg(long)]
+    pub fixed_timestep: bool,
+
+    /// Frame rate for the fixed timestep and single frame stepping
+    #[arg(long, default_value_t = 60)]
+    pub fps: u32,
+
+    /// Camera model to start with
+    #[arg(long, value_enum, default_value_t = CameraKind::Orbit)]
+    pub camera: CameraKind,
+
+    /// Replay a camera recording exported from the timeline, also when rendering a video
+    #[arg(long, value_name = "FILE")]
+    pub camera_recording: Option<PathBuf>,
+
+    /// Render the scene at a fraction of the window size, e.g. 0.5
+    #[arg(long, value_name = "SCALE", conflicts_with = "render_size")]
+    pub render_scale: Option<f32>,
+
+    /// Render the scene at a fixed size no matter the window size, e.g. 1920x1080
+    #[arg(long, value_parser = parse_size, value_name = "WIDTHxHEIGHT")]
+    pub render_size: Option<(u32, u32)>,
+
+    /// Filter used when the scene is scaled to fit the window
+    #[arg(long, value_enum, default_value_t = UpscaleFilter::Nearest)]
+    pub upscale_filter: UpscaleFilter,
+
+    /// Pixel format of the scene target. Float formats keep values above 1.0 for tonemapping.
+    #[arg(long, value_enum, default_value_t = TargetFormat::Rgba16f)]
+    pub scene_format: TargetFormat,
+
+    /// Render a tiled poster of the given size, e.g. 16384x9216, and exit
+    #[arg(long, value_parser = parse_size, value_name = "WIDTHxHEIGHT")]
+    pub poster: Option<(u32, u32)>,
+
+    /// Size of each poster tile in pixels
+    #[arg(long, default_value_t = 1024)]
+    pub poster_tile_size: u32,
+
+    /// Render each poster tile N times larger in each direction and filter it down
+    #[arg(long, default_value_t = 1, value_name = "N")]
+    pub poster_supersample: u32,
+
+    /// Render a video to this Y4M file and exit
+    #[arg(long, value_name = "OUTPUT")]
+    pub export: Option<PathBuf>,
+
+    #[arg(long, value_parser = parse_size, value_name = "WIDTHxHEIGHT", default_value = "1920x1080")]
+    pub export_size: (u32, u32),
+
+    #[arg(long, default_value_t = 60)]
+    pub export_fps: u32,
+
+    /// Length of the video in seconds
+    #[arg(long, default_value_t = 10.0)]
+    pub export_duration: f32,
+
+    /// Pipe raw RGBA frames to this command instead of writing Y4M.
+    /// {width}, {height}, {fps}, {output} and {audio} are replaced before running it.
+    #[arg(long, value_name = "COMMAND")]
+    pub export_cmd: Option<String>,
+
+    /// WAV soundtrack to pass along with the video
+    #[arg(long, value_name = "WAV")]
+    pub export_audio: Option<PathBuf>,
+}
+
+impl AppConfig {
+    /// `options` from the config file with what was given on the command line on top
+    pub fn window_options(&self, mut options: WindowOptions) -> WindowOptions {
+        options.always_on_top |= self.always_on_top;
+        options.borderless |= self.borderless;
+        if let Some(mode) = self.fullscreen {
+            options.fullscreen = true;
+            options.fullscreen_mode = mode;
+        }
+        options.monitor = self.monitor.or(options.monitor);
+        options.size = self.window_size.or(options.size);
+        options.position = self.window_position.or(options.position);
+        options.vsync &= !self.no_vsync;
+        options
+    }
+
+    /// The positional file, when it's a project
+    pub fn project_file(&self) -> Option<&PathBuf> {
+        self.file
+            .as_ref()
+            .filter(|path| path.extension().map_or(false, |ext| ext == "toml"))
+    }
+
+    /// The project, or the first shader without one. Settings and the session are stored next
+    /// to it.
+    pub fn main_path(&self) -> Option<PathBuf> {
+        self.project_file()
+            .cloned()
+            .or_else(|| self.shader_files().first().cloned())
+    }
+
+    /// Shaders given with `-f` and the positional file when it isn't a project
+    pub fn shader_files(&self) -> Vec<PathBuf> {
+        let positional = self.file.iter().filter(|_| self.project_file().is_none());
+        positional
+            .chain(self.files.iter().flatten())
+            .cloned()
+            .collect()
+    }
+
+    pub fn video_config(&self) -> Option<VideoConfig> {
+        let output = self.export.clone()?;
+        let (width, height) = self.export_size;
+        Some(VideoConfig {
+            output,
+            width,
+            height,
+            fps: self.export_fps.max(1),
+            duration: self.export_duration,
+            command: self.export_cmd.clone(),
+            audio: self.export_audio.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AppConfig;
+    use crate::{FullscreenMode, WindowOptions};
+    use clap::Parser;
+
+    #[test]
+    fn command_line_overrides_window_options() {
+        let file = WindowOptions {
+            size: Some((800, 600)),
+            monitor: Some(1),
+            ..WindowOptions::default()
+        };
+        let config = AppConfig::parse_from([
+            "skuggbox",
+            "--fullscreen",
+            "--window-size",
+            "1280x720",
+            "--window-position",
+            "-100,50",
+            "--no-vsync",
+        ]);
+        let options = config.window_options(file);
+        assert!(options.fullscreen);
+        assert_eq!(options.fullscreen_mode, FullscreenMode::Borderless);
+        assert_eq!(options.monitor, Some(1));
+        assert_eq!(options.size, Some((1280, 720)));
+        assert_eq!(options.position, Some((-100, 50)));
+        assert!(!options.vsync);
+
+        let config =
+            AppConfig::parse_from(["skuggbox", "--fullscreen", "exclusive", "shader.glsl"]);
+        let options = config.window_options(WindowOptions::default());
+        assert_eq!(options.fullscreen_mode, FullscreenMode::Exclusive);
+        assert_eq!(config.file, Some("shader.glsl".into()));
+    }
 }
@@ -0,0 +1,322 @@
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How long typing has to pause before the shader is recompiled
+pub const COMPILE_DELAY: Duration = Duration::from_millis(400);
+
+/// A file open in the built-in editor
+#[derive(Debug, Clone)]
+pub struct EditorTab {
+    pub path: PathBuf,
+    pub name: String,
+    pub text: String,
+    /// What is on disk, the tab has unsaved changes when `text` differs
+    saved: String,
+    /// When the text was last changed, `None` once the shader has it
+    edited: Option<Instant>,
+    /// The file changed on disk while there were unsaved changes
+    pub conflict: bool,
+}
+
+impl EditorTab {
+    fn load(path: &Path) -> Self {
+        let text = fs::read_to_string(path).unwrap_or_default();
+        Self {
+            path: path.to_path_buf(),
+            name: path
+                .file_name()
+                .map_or_else(String::new, |name| name.to_string_lossy().to_string()),
+            saved: text.clone(),
+            text,
+            edited: None,
+            conflict: false,
+        }
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.text != self.saved
+    }
+}
+
+/// Live-coding editor with a tab for every file the shaders are made of
+#[derive(Debug, Clone, Default)]
+pub struct Editor {
+    pub open: bool,
+    /// Draw the code over the whole window instead of in a side panel, for live coding
+    pub overlay: bool,
+    pub tabs: Vec<EditorTab>,
+    pub active: usize,
+    /// Line of the active tab to move the cursor to, from 1
+    pub goto_line: Option<usize>,
+}
+
+impl Editor {
+    /// Follow the files the shaders use. Tabs for files that are no longer used are closed
+    /// unless they have unsaved changes.
+    pub fn sync_tabs(&mut self, files: &[PathBuf]) {
+        let active = self.active_tab().map(|tab| tab.path.clone());
+        self.tabs
+            .retain(|tab| tab.is_dirty() || files.contains(&tab.path));
+        for path in files {
+            if self.tab_index(path).is_none() {
+                self.tabs.push(EditorTab::load(path));
+            }
+        }
+        self.active = active.and_then(|path| self.tab_index(&path)).unwrap_or(0);
+    }
+
+    pub fn tab_index(&self, path: &Path) -> Option<usize> {
+        self.tabs.iter().position(|tab| tab.path == path)
+    }
+
+    pub fn active_tab(&self) -> Option<&EditorTab> {
+        self.tabs.get(self.active)
+    }
+
+    /// Show a file at a line, returns false if the file has no tab
+    pub fn open_file(&mut self, path: &Path, line: usize) -> bool {
+        match self.tab_index(path) {
+            Some(index) => {
+                self.open = true;
+                self.active = index;
+                self.goto_line = Some(line);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The text of a tab was changed by the user
+    pub fn edited(&mut self, index: usize, now: Instant) {
+        if let Some(tab) = self.tabs.get_mut(index) {
+            tab.edited = Some(now);
+        }
+    }
+
+    /// Typing stopped long enough ago to compile
+    pub fn compile_due(&self, now: Instant) -> bool {
+        self.tabs.iter().any(|tab| {
+            tab.edited
+                .map_or(false, |edited| now.duration_since(edited) >= COMPILE_DELAY)
+        })
+    }
+
+    /// Edited text the shaders don't have yet, by path. With `include_active` the active
+    /// tab is always part of it.
+    pub fn take_pending(&mut self, include_active: bool) -> Vec<(PathBuf, String)> {
+        let active = self.active;
+        self.tabs
+            .iter_mut()
+            .enumerate()
+            .filter(|(index, tab)| tab.edited.is_some() || (include_active && *index == active))
+            .map(|(_, tab)| {
+                tab.edited = None;
+                (tab.path.clone(), tab.text.clone())
+            })
+            .collect()
+    }
+
+    /// The active tab was written to disk
+    pub fn mark_saved(&mut self) {
+        if let Some(tab) = self.tabs.get_mut(self.active) {
+            tab.saved = tab.text.clone();
+            tab.edited = None;
+            tab.conflict = false;
+        }
+    }
+
+    /// A file changed on disk. Tabs without unsaved changes take the new text, returns true
+    /// if that happened. Unsaved changes that now match the disk are no longer unsaved.
+    pub fn reload(&mut self, path: &Path) -> bool {
+        let tab = match self.tabs.iter_mut().find(|tab| tab.path == path) {
+            Some(tab) => tab,
+            None => return false,
+        };
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(_) => return false,
+        };
+        if tab.is_dirty() {
+            tab.conflict = text != tab.text;
+            if !tab.conflict {
+                tab.saved = text;
+            }
+            return false;
+        }
+        tab.text = text.clone();
+        tab.saved = text;
+        tab.edited = None;
+        true
+    }
+}
+
+/// What a piece of GLSL is, for colouring it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Plain,
+    Comment,
+    Keyword,
+    Type,
+    Builtin,
+    Number,
+    Preprocessor,
+}
+
+const GLSL_KEYWORDS: &str =
+    "attribute break case centroid const continue default discard do else false flat for highp \
+    if in inout invariant layout lowp mediump noperspective out precision return smooth struct \
+    switch true uniform varying while";
+
+const GLSL_TYPES: &str =
+    "void bool int uint float double vec2 vec3 vec4 ivec2 ivec3 ivec4 uvec2 uvec3 uvec4 bvec2 \
+    bvec3 bvec4 dvec2 dvec3 dvec4 mat2 mat3 mat4 mat2x2 mat2x3 mat2x4 mat3x2 mat3x3 mat3x4 \
+    mat4x2 mat4x3 mat4x4 sampler1D sampler2D sampler3D samplerCube sampler2DArray isampler2D \
+    usampler2D";
+
+/// Built-in functions, and the ones skuggbox adds
+const GLSL_BUILTINS: &str =
+    "abs acos acosh all any asin asinh atan atanh ceil clamp cos cosh cross dFdx dFdy degrees \
+    determinant distance dot equal exp exp2 faceforward floatBitsToInt floatBitsToUint floor \
+    fract fwidth greaterThan intBitsToFloat inverse inversesqrt isinf isnan length lessThan \
+    log log2 matrixCompMult max min mix mod modf normalize not notEqual outerProduct pow \
+    radians reflect refract round sign sin sinh smoothstep sqrt step tan tanh texelFetch \
+    texture textureLod textureSize transpose trunc uintBitsToFloat sbPick sbProbe";
+
+/// Split GLSL into coloured ranges that cover the whole text, in order
+pub fn highlight_glsl(text: &str) -> Vec<(Range<usize>, TokenKind)> {
+    let bytes = text.as_bytes();
+    let mut tokens: Vec<(Range<usize>, TokenKind)> = vec![];
+    let mut line_start = true;
+    let mut i = 0;
+
+    let end_of_line = |from: usize| text[from..].find('\n').map_or(text.len(), |n| from + n);
+
+    while i < bytes.len() {
+        let c = bytes[i];
+        let rest = &text[i..];
+        let (end, kind) = if rest.starts_with("//") {
+            (end_of_line(i), TokenKind::Comment)
+        } else if let Some(body) = rest.strip_prefix("/*") {
+            let end = body.find("*/").map_or(text.len(), |n| i + 2 + n + 2);
+            (end, TokenKind::Comment)
+        } else if c == b'#' && line_start {
+            let line_end = end_of_line(i);
+            let end = text[i..line_end].find("//").map_or(line_end, |n| i + n);
+            (end, TokenKind::Preprocessor)
+        } else if c.is_ascii_digit()
+            || (c == b'.' && bytes.get(i + 1).map_or(false, u8::is_ascii_digit))
+        {
+            let mut end = i + 1;
+            while end < bytes.len() {
+                let exponent_sign = matches!(bytes[end], b'+' | b'-')
+                    && matches!(bytes[end - 1], b'e' | b'E')
+                    && !text[i..end].starts_with("0x");
+                if bytes[end].is_ascii_alphanumeric() || bytes[end] == b'.' || exponent_sign {
+                    end += 1;
+                } else {
+                    break;
+                }
+            }
+            (end, TokenKind::Number)
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            let mut end = i + 1;
+            while end < bytes.len() && (bytes[end].is_ascii_alphanumeric() || bytes[end] == b'_') {
+                end += 1;
+            }
+            let word = &text[i..end];
+            let is_in = |words: &str| words.split_whitespace().any(|known| known == word);
+            let kind = if is_in(GLSL_KEYWORDS) {
+                TokenKind::Keyword
+            } else if is_in(GLSL_TYPES) {
+                TokenKind::Type
+            } else if is_in(GLSL_BUILTINS) || word.starts_with("gl_") {
+                TokenKind::Builtin
+            } else {
+                TokenKind::Plain
+            };
+            (end, kind)
+        } else {
+            let len = rest.chars().next().map_or(1, char::len_utf8);
+            (i + len, TokenKind::Plain)
+        };
+
+        if c == b'\n' {
+            line_start = true;
+        } else if !c.is_ascii_whitespace() {
+            line_start = false;
+        }
+
+        match tokens.last_mut() {
+            Some((range, last)) if *last == kind && kind == TokenKind::Plain => range.end = end,
+            _ => tokens.push((i..end, kind)),
+        }
+        i = end;
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{highlight_glsl, Editor, TokenKind, COMPILE_DELAY};
+    use crate::TempPath;
+    use std::fs;
+    use std::time::Instant;
+
+    #[test]
+    fn glsl_tokens() {
+        let text = "#version 330\nuniform vec3 p; // light\nfloat d = max(1.5e-3, .5) + x;";
+        let tokens: Vec<(&str, TokenKind)> = highlight_glsl(text)
+            .into_iter()
+            .map(|(range, kind)| (&text[range], kind))
+            .collect();
+
+        let covered: String = tokens.iter().map(|(text, _)| *text).collect();
+        assert_eq!(covered, text);
+        assert!(tokens.contains(&("#version 330", TokenKind::Preprocessor)));
+        assert!(tokens.contains(&("uniform", TokenKind::Keyword)));
+        assert!(tokens.contains(&("vec3", TokenKind::Type)));
+        assert!(tokens.contains(&("// light", TokenKind::Comment)));
+        assert!(tokens.contains(&("max", TokenKind::Builtin)));
+        assert!(tokens.contains(&("1.5e-3", TokenKind::Number)));
+        assert!(tokens.contains(&(".5", TokenKind::Number)));
+    }
+
+    #[test]
+    fn edits_compile_after_a_pause_and_survive_reloads() {
+        let temp = TempPath::new("editor-test.glsl");
+        let path = temp.0.clone();
+        fs::write(&path, "void main() {}").unwrap();
+
+        let mut editor = Editor::default();
+        editor.sync_tabs(&[path.clone()]);
+        assert_eq!(editor.tabs[0].text, "void main() {}");
+
+        let start = Instant::now();
+        editor.tabs[0].text.push_str("\n// edit");
+        editor.edited(0, start);
+        assert!(!editor.compile_due(start));
+        assert!(editor.compile_due(start + COMPILE_DELAY));
+        assert_eq!(editor.take_pending(false).len(), 1);
+        assert!(editor.take_pending(false).is_empty());
+
+        // unsaved changes are kept when the file changes on disk
+        fs::write(&path, "// from elsewhere").unwrap();
+        assert!(!editor.reload(&path));
+        assert!(editor.tabs[0].conflict);
+        editor.sync_tabs(&[]);
+        assert_eq!(editor.tabs.len(), 1);
+
+        editor.mark_saved();
+        assert!(editor.reload(&path));
+        assert_eq!(editor.tabs[0].text, "// from elsewhere");
+
+        // the same edit made elsewhere leaves nothing unsaved
+        editor.tabs[0].text.push_str("\n// same");
+        fs::write(&path, &editor.tabs[0].text).unwrap();
+        assert!(!editor.reload(&path));
+        assert!(!editor.tabs[0].is_dirty());
+        assert!(!editor.tabs[0].conflict);
+    }
+}
@@ -0,0 +1,168 @@
+use anyhow::Context;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Editors that need a terminal. skuggbox has none to give them, so they are only used through
+/// a template that starts one, like `alacritty -e nvim +{line} {file}`.
+const TERMINAL_EDITORS: &str = "vi vim nvim nano micro kak hx helix";
+
+/// `$VISUAL`, then `$EDITOR`
+pub fn editor_from_env() -> Option<String> {
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|editor| !editor.trim().is_empty())
+}
+
+/// Name of the program of an editor command, without directory and extension
+fn program_name(editor: &str) -> String {
+    editor
+        .split_whitespace()
+        .next()
+        .and_then(|program| Path::new(program).file_stem())
+        .map_or_else(String::new, |stem| stem.to_string_lossy().to_string())
+}
+
+/// The editor only runs inside a terminal
+pub fn is_terminal_editor(editor: &str) -> bool {
+    let program = program_name(editor);
+    TERMINAL_EDITORS
+        .split_whitespace()
+        .any(|known| known == program)
+}
+
+/// The command line that opens `path` at `line` in `editor`, which may come with arguments of
+/// its own. Editors we don't know only get the path.
+pub fn editor_command_line(editor: &str, path: &Path, line: usize) -> Vec<String> {
+    let mut words: Vec<String> = editor.split_whitespace().map(str::to_string).collect();
+    let program = program_name(editor);
+    let file = path.display().to_string();
+
+    match program.as_str() {
+        "gvim" | "emacs" | "emacsclient" | "gedit" | "kate" => {
+            words.push(format!("+{}", line));
+            words.push(file);
+        }
+        "code" | "code-insiders" | "codium" => {
+            words.push("-g".to_string());
+            words.push(format!("{}:{}", file, line));
+        }
+        "subl" | "sublime_text" | "zed" => words.push(format!("{}:{}", file, line)),
+        "idea" | "clion" | "rider" => {
+            words.push("--line".to_string());
+            words.push(line.to_string());
+            words.push(file);
+        }
+        _ => words.push(file),
+    }
+    words
+}
+
+/// Fill in `{file}` and `{line}` of a template from the config file, like
+/// `code -g {file}:{line}`. The file is added at the end if the template doesn't mention it.
+/// Words are split before filling in, so paths with spaces stay one argument.
+pub fn expand_editor_template(template: &str, path: &Path, line: usize) -> Vec<String> {
+    let file = path.display().to_string();
+    let mut words: Vec<String> = template
+        .split_whitespace()
+        .map(|word| {
+            word.replace("{file}", &file)
+                .replace("{line}", &line.to_string())
+        })
+        .collect();
+    if !template.contains("{file}") {
+        words.push(file);
+    }
+    words
+}
+
+/// The command line that opens `path` at `line`: the template from the config file if there is
+/// one, otherwise `$VISUAL` or `$EDITOR`. `None` without either, or when those are terminal
+/// editors.
+pub fn external_editor_command(
+    template: Option<&str>,
+    path: &Path,
+    line: usize,
+) -> Option<Vec<String>> {
+    match template.filter(|template| !template.trim().is_empty()) {
+        Some(template) => Some(expand_editor_template(template, path, line)),
+        None => editor_from_env()
+            .filter(|editor| !is_terminal_editor(editor))
+            .map(|editor| editor_command_line(&editor, path, line)),
+    }
+}
+
+/// Open a file at a line in the user's own editor, without waiting for it to close.
+/// The editor gets no stdin and is waited for on a thread of its own, so it doesn't linger
+/// as a zombie once closed.
+pub fn open_in_external_editor(
+    template: Option<&str>,
+    path: &Path,
+    line: usize,
+) -> anyhow::Result<()> {
+    let words = external_editor_command(template, path, line).context(
+        "No editor configured, and $VISUAL or $EDITOR is either not set or needs a terminal",
+    )?;
+    log::info!("Opening {:?} at line {} with {:?}", path, line, words);
+
+    let mut child = Command::new(&words[0])
+        .args(&words[1..])
+        .stdin(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to start {}", words[0]))?;
+    std::thread::spawn(move || {
+        if let Err(err) = child.wait() {
+            log::warn!("Failed to wait for the editor: {}", err);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{editor_command_line, expand_editor_template, is_terminal_editor};
+    use std::path::Path;
+
+    #[test]
+    fn line_arguments_per_editor() {
+        let path = Path::new("/shaders/common.glsl");
+        assert_eq!(
+            editor_command_line("gvim", path, 12),
+            vec!["gvim", "+12", "/shaders/common.glsl"]
+        );
+        assert_eq!(
+            editor_command_line("/usr/bin/code --reuse-window", path, 3),
+            vec![
+                "/usr/bin/code",
+                "--reuse-window",
+                "-g",
+                "/shaders/common.glsl:3"
+            ]
+        );
+        assert_eq!(
+            editor_command_line("notepad", path, 3),
+            vec!["notepad", "/shaders/common.glsl"]
+        );
+    }
+
+    #[test]
+    fn terminal_editors() {
+        assert!(is_terminal_editor("/usr/bin/nvim"));
+        assert!(is_terminal_editor("vim -u NONE"));
+        assert!(!is_terminal_editor("code --wait"));
+        assert!(!is_terminal_editor("gvim"));
+    }
+
+    #[test]
+    fn templates() {
+        let path = Path::new("/my shaders/common.glsl");
+        assert_eq!(
+            expand_editor_template("code -g {file}:{line}", path, 7),
+            vec!["code", "-g", "/my shaders/common.glsl:7"]
+        );
+        assert_eq!(
+            expand_editor_template("emacsclient -n +{line}", path, 7),
+            vec!["emacsclient", "-n", "+7", "/my shaders/common.glsl"]
+        );
+    }
+}
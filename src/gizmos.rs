@@ -0,0 +1,187 @@
+use glam::{Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::{AppState, OverlayCamera, OverlayLines, OVERLAY_WHITE, OVERLAY_YELLOW};
+
+/// How close, in window pixels, the cursor has to be to grab a handle
+const HANDLE_PICK_RADIUS: f32 = 10.0;
+
+/// A point in the scene bound to a `vec3` uniform. Declared in the shader with
+/// `#pragma skuggbox(handle: name)` next to `uniform vec3 name;`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PointHandle {
+    pub name: String,
+    pub position: Vec3,
+}
+
+/// Ground grid, axes and camera target drawn over the scene, plus the point handles
+#[derive(Debug, Clone)]
+pub struct Gizmos {
+    pub enabled: bool,
+    /// The grid reaches this many units from the origin
+    pub grid_extent: i32,
+    pub handles: Vec<PointHandle>,
+    pub hovered: Option<usize>,
+    pub dragging: Option<usize>,
+    was_lmb_down: bool,
+}
+
+impl Default for Gizmos {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            grid_extent: 10,
+            handles: vec![],
+            hovered: None,
+            dragging: None,
+            was_lmb_down: false,
+        }
+    }
+}
+
+impl Gizmos {
+    /// Keep a handle for every name in `names`, in that order. Handles that already exist
+    /// keep their position, new ones start at the origin.
+    pub fn sync_handles(&mut self, names: &[String]) {
+        if self
+            .handles
+            .iter()
+            .map(|handle| &handle.name)
+            .eq(names.iter())
+        {
+            return;
+        }
+
+        let mut old = std::mem::take(&mut self.handles);
+        self.handles = names
+            .iter()
+            .map(
+                |name| match old.iter().position(|handle| &handle.name == name) {
+                    Some(index) => old.swap_remove(index),
+                    None => PointHandle {
+                        name: name.clone(),
+                        position: Vec3::ZERO,
+                    },
+                },
+            )
+            .collect();
+        self.hovered = None;
+        self.dragging = None;
+    }
+}
+
+/// The camera of the current frame, as used by the overlay
+pub fn overlay_camera(state: &mut AppState) -> OverlayCamera {
+    let view = state.scene_view;
+    OverlayCamera {
+        transform: state.camera_transform(),
+        projection: state.camera_projection(),
+        aspect: view.render_width as f32 / view.render_height.max(1) as f32,
+    }
+}
+
+/// Everything the overlay draws this frame
+pub fn overlay_lines(state: &AppState, camera: &OverlayCamera) -> OverlayLines {
+    let gizmos = &state.gizmos;
+    let mut lines = OverlayLines::default();
+    lines.grid(gizmos.grid_extent);
+    lines.axes(2.0);
+
+    let camera_position = camera.transform.w_axis.truncate();
+    // keep markers about the same size on screen
+    let marker_size = |position: Vec3| (position - camera_position).length() * 0.03;
+
+    if let Some(target) = state.camera.target() {
+        lines.cross(target, marker_size(target), OVERLAY_YELLOW);
+    }
+
+    for (index, handle) in gizmos.handles.iter().enumerate() {
+        let active = gizmos.dragging == Some(index) || gizmos.hovered == Some(index);
+        let color = if active {
+            OVERLAY_YELLOW
+        } else {
+            OVERLAY_WHITE
+        };
+        lines.cross(handle.position, marker_size(handle.position), color);
+    }
+    lines
+}
+
+/// Hover, grab and drag handles with the left mouse button. Handles move in the plane facing
+/// the camera, so they keep their depth.
+pub fn update_gizmos(state: &mut AppState) {
+    let lmb_down = state.mouse.is_lmb_down;
+    let pressed = lmb_down && !state.gizmos.was_lmb_down;
+    state.gizmos.was_lmb_down = lmb_down;
+
+    if !state.gizmos.enabled || state.gizmos.handles.is_empty() {
+        state.gizmos.hovered = None;
+        state.gizmos.dragging = None;
+        return;
+    }
+    if !lmb_down {
+        state.gizmos.dragging = None;
+    }
+
+    let camera = overlay_camera(state);
+    let view = state.scene_view;
+    let render_size = Vec2::new(view.render_width as f32, view.render_height as f32);
+    let cursor = match view.window_to_render(state.mouse.cursor) {
+        Some(cursor) => cursor,
+        None => {
+            state.gizmos.hovered = None;
+            return;
+        }
+    };
+    let cursor_ndc = cursor / render_size * 2.0 - 1.0;
+
+    let pointer_over_ui = state.pointer_over_ui;
+    let gizmos = &mut state.gizmos;
+    if let Some(index) = gizmos.dragging {
+        let handle = &mut gizmos.handles[index];
+        if let Some(position) = camera.drag_in_view_plane(handle.position, cursor_ndc) {
+            handle.position = position;
+        }
+        return;
+    }
+
+    // pick radius in render pixels
+    let radius = HANDLE_PICK_RADIUS * view.render_width as f32 / view.viewport[2].max(1) as f32;
+    gizmos.hovered = gizmos
+        .handles
+        .iter()
+        .enumerate()
+        .filter_map(|(index, handle)| {
+            let ndc = camera.project(handle.position)?;
+            let pixel = (ndc + 1.0) / 2.0 * render_size;
+            let distance = pixel.distance(cursor);
+            (distance <= radius).then_some((index, distance))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(index, _)| index);
+
+    if pressed && !pointer_over_ui {
+        gizmos.dragging = gizmos.hovered;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Gizmos;
+    use glam::Vec3;
+
+    #[test]
+    fn handles_follow_the_shader() {
+        let mut gizmos = Gizmos::default();
+        gizmos.sync_handles(&["light".to_string(), "center".to_string()]);
+        gizmos.handles[0].position = Vec3::ONE;
+
+        gizmos.sync_handles(&["center".to_string(), "light".to_string()]);
+        assert_eq!(gizmos.handles[0].name, "center");
+        assert_eq!(gizmos.handles[1].position, Vec3::ONE);
+
+        gizmos.sync_handles(&["light".to_string()]);
+        assert_eq!(gizmos.handles.len(), 1);
+        assert_eq!(gizmos.handles[0].position, Vec3::ONE);
+    }
+}
@@ -1,12 +1,12 @@
 use ui_backend::Ui;
 use winit::{
-    event::{ElementState, Event, VirtualKeyCode, WindowEvent},
+    event::{ElementState, Event, KeyboardInput, WindowEvent},
     event_loop::ControlFlow,
 };
 
 use crate::{
     state::{AppState, PlayMode},
-    Action, ActionModifier, CameraMovement, WindowEventHandler,
+    Action, ActionModifier, Chord, WindowEventHandler,
 };
 
 pub fn handle_events<T>(
@@ -20,6 +20,8 @@ pub fn handle_events<T>(
 
     match event {
         Event::WindowEvent { event, .. } => {
+            let event_response = ui.on_event(event);
+
             match event {
                 WindowEvent::CloseRequested => {
                     actions.push(Action::AppExit);
@@ -31,6 +33,7 @@ pub fn handle_events<T>(
                 }
 
                 WindowEvent::ModifiersChanged(modifier_state) => {
+                    app_state.held_modifiers = *modifier_state;
                     let internal =
                         i32::from(modifier_state.shift()) + 2 * i32::from(modifier_state.ctrl());
                     app_state.modifier = match internal {
@@ -41,84 +44,39 @@ pub fn handle_events<T>(
                     }
                 }
 
-                WindowEvent::KeyboardInput { input, .. } => {
-                    if input.state == ElementState::Pressed {
-                        if let Some(keycode) = input.virtual_keycode {
-                            match keycode {
-                                VirtualKeyCode::Escape => {
-                                    actions.push(Action::AppExit);
-                                }
-
-                                // Timeline controls
-                                VirtualKeyCode::Space => {
-                                    actions.push(Action::TogglePlayPause);
-                                }
-                                VirtualKeyCode::Right => {
-                                    actions.push(Action::TimeForward(1.0));
-                                }
-                                VirtualKeyCode::Left => {
-                                    actions.push(Action::TimeRewind(1.0));
-                                }
-                                VirtualKeyCode::Key0 => {
-                                    actions.push(Action::TimeStop);
-                                }
-
-                                // Movement controls
-                                VirtualKeyCode::A => {
-                                    actions.push(Action::CameraMove(CameraMovement::StrafeLeft));
-                                }
-                                VirtualKeyCode::D => {
-                                    actions.push(Action::CameraMove(CameraMovement::StrafeRight));
-                                }
-                                VirtualKeyCode::S => {
-                                    actions.push(Action::CameraMove(CameraMovement::MoveBackward));
-                                }
-                                VirtualKeyCode::W => {
-                                    actions.push(Action::CameraMove(CameraMovement::MoveForward));
-                                }
-                                VirtualKeyCode::X => {
-                                    actions.push(Action::CameraMove(CameraMovement::Reset));
-                                }
-
-                                // Feature controls
-                                VirtualKeyCode::Key1 => {
-                                    actions.push(Action::CameraToggleIntegration(false));
-                                }
-                                VirtualKeyCode::Key2 => {
-                                    actions.push(Action::CameraToggleIntegration(true));
-                                }
-                                VirtualKeyCode::Period => {
-                                    // reset all camera settings
-                                    actions.push(Action::CameraReset);
-                                }
-
-                                // UI controls
-                                VirtualKeyCode::Tab => actions.push(Action::UIToggleVisible),
-                                VirtualKeyCode::F11 => actions.push(Action::ToggleFullscreen),
-
-                                VirtualKeyCode::P => actions.push(Action::PrintSource),
-                                VirtualKeyCode::F12 => actions.push(Action::TakeSnapshot),
-                                _ => {}
-                            }
-                        }
-                    }
-                }
-
                 _ => {}
             }
 
             app_state.mouse.handle_window_events(event);
-            app_state.camera.handle_window_events(event);
+
+            // presses egui takes, like typing in a text field, are not for the scene. Releases
+            // always go through so nothing gets stuck.
+            let for_ui = event_response.consumed
+                && match event {
+                    WindowEvent::KeyboardInput { input, .. } => {
+                        input.state == ElementState::Pressed
+                    }
+                    WindowEvent::MouseInput { state, .. } => *state == ElementState::Pressed,
+                    WindowEvent::MouseWheel { .. } => true,
+                    _ => false,
+                };
+
+            if !for_ui {
+                app_state.camera.handle_window_events(event);
+                let action = Chord::from_event(event, app_state.held_modifiers)
+                    .and_then(|chord| app_state.key_bindings.action(&chord))
+                    .cloned();
+                hold_camera_moves(event, action.as_ref(), app_state);
+                actions.extend(action);
+            }
             app_state
                 .camera
                 .handle_mouse(&app_state.mouse, app_state.delta_time);
-
-            let _event_response = ui.on_event(event);
         }
 
         Event::MainEventsCleared => {
             if matches!(app_state.play_mode, PlayMode::Playing) {
-                app_state.playback_time += app_state.delta_time;
+                app_state.advance_playback();
             }
 
             *control_flow = ControlFlow::Exit;
@@ -128,3 +86,39 @@ pub fn handle_events<T>(
         _ => (),
     }
 }
+
+/// Cameras like the fly camera keep moving while a key bound to `CameraMove` is held.
+/// `action` is what the event is bound to.
+fn hold_camera_moves(event: &WindowEvent<'_>, action: Option<&Action>, app_state: &mut AppState) {
+    match event {
+        WindowEvent::KeyboardInput {
+            input:
+                KeyboardInput {
+                    state,
+                    virtual_keycode: Some(keycode),
+                    ..
+                },
+            ..
+        } => {
+            let held = app_state
+                .held_moves
+                .iter()
+                .position(|(held, _)| held == keycode);
+            // with key repeat a held key is pressed again and again
+            match (state, held, action) {
+                (ElementState::Pressed, None, Some(Action::CameraMove(movement))) => {
+                    app_state.camera.hold(movement, true);
+                    app_state.held_moves.push((*keycode, *movement));
+                }
+                (ElementState::Released, Some(index), _) => {
+                    let (_, movement) = app_state.held_moves.remove(index);
+                    app_state.camera.hold(&movement, false);
+                }
+                _ => {}
+            }
+        }
+        // losing focus means we won't see the key releases
+        WindowEvent::Focused(false) => app_state.held_moves.clear(),
+        _ => {}
+    }
+}
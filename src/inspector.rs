@@ -0,0 +1,183 @@
+use glam::IVec2;
+
+use crate::{AppState, Renderer};
+
+/// A pixel in the scene target that is read back every frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct PixelProbe {
+    pub name: String,
+    /// Render target pixel, origin in the lower left corner
+    pub pixel: IVec2,
+    pub value: Option<[f32; 4]>,
+}
+
+/// Reads back the rendered scene under the cursor. Values come from the scene target, not
+/// the window, so they are the raw shader output at render resolution.
+#[derive(Debug, Clone)]
+pub struct Inspector {
+    pub enabled: bool,
+    /// Pixel under the cursor, `None` when the cursor is outside of the scene
+    pub hovered: Option<IVec2>,
+    pub hovered_value: Option<[f32; 4]>,
+    /// The lens shows this many pixels on each side of the hovered one
+    pub lens_radius: i32,
+    /// Pixels around the hovered one, top row first. Pixels outside of the scene are `None`.
+    pub lens: Vec<Option<[f32; 4]>>,
+    pub pins: Vec<PixelProbe>,
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            hovered: None,
+            hovered_value: None,
+            lens_radius: 8,
+            lens: vec![],
+            pins: vec![],
+        }
+    }
+}
+
+impl Inspector {
+    /// Width and height of the lens in pixels
+    pub fn lens_size(&self) -> i32 {
+        self.lens_radius * 2 + 1
+    }
+
+    /// Pin the hovered pixel so it keeps being read back
+    pub fn pin_hovered(&mut self) {
+        if let Some(pixel) = self.hovered {
+            if self.pins.iter().any(|pin| pin.pixel == pixel) {
+                return;
+            }
+            self.pins.push(PixelProbe {
+                name: format!("P{}", self.pins.len() + 1),
+                pixel,
+                value: self.hovered_value,
+            });
+        }
+    }
+
+    pub fn remove_pin(&mut self, index: usize) {
+        if index < self.pins.len() {
+            self.pins.remove(index);
+        }
+    }
+}
+
+/// Part of the lens that lies inside a `width` x `height` target, as `(x, y, width, height)`.
+/// Returns `None` if nothing of it does.
+pub fn lens_region(center: IVec2, radius: i32, width: i32, height: i32) -> Option<[i32; 4]> {
+    let min = (center - radius).max(IVec2::ZERO);
+    let max = (center + radius).min(IVec2::new(width - 1, height - 1));
+    if min.x > max.x || min.y > max.y {
+        return None;
+    }
+    Some([min.x, min.y, max.x - min.x + 1, max.y - min.y + 1])
+}
+
+/// Place the pixels of `region`, bottom row first, into a lens around `center`, top row first
+pub fn fill_lens(
+    center: IVec2,
+    radius: i32,
+    region: [i32; 4],
+    pixels: &[[f32; 4]],
+) -> Vec<Option<[f32; 4]>> {
+    let size = radius * 2 + 1;
+    let [x, y, width, height] = region;
+    let mut lens = vec![None; (size * size) as usize];
+    for row in 0..height {
+        for col in 0..width {
+            let lens_x = x + col - (center.x - radius);
+            let lens_y = (center.y + radius) - (y + row);
+            lens[(lens_y * size + lens_x) as usize] = Some(pixels[(row * width + col) as usize]);
+        }
+    }
+    lens
+}
+
+/// Read back the hovered pixel, the lens around it and all pins from the last rendered frame.
+/// The hovered pixel stays put while the cursor is over the ui, so the pin button pins the
+/// pixel it was moved away from.
+pub fn update_inspector(renderer: &Renderer, state: &mut AppState) {
+    let view = state.scene_view;
+    let inspector = &mut state.inspector;
+
+    if !state.pointer_over_ui {
+        inspector.hovered = view
+            .window_to_render(state.mouse.cursor)
+            .map(|pos| pos.floor().as_ivec2());
+    }
+    inspector.hovered_value = None;
+    inspector.lens.clear();
+
+    if let Some(center) = inspector.hovered {
+        let radius = inspector.lens_radius;
+        if let Some(region) = lens_region(center, radius, view.render_width, view.render_height) {
+            let [x, y, width, height] = region;
+            if let Some(pixels) = renderer.read_scene(x, y, width, height) {
+                inspector.lens = fill_lens(center, radius, region, &pixels);
+                let middle = (radius * inspector.lens_size() + radius) as usize;
+                inspector.hovered_value = inspector.lens[middle];
+            }
+        }
+    }
+
+    for pin in &mut inspector.pins {
+        let inside = pin.pixel.x < view.render_width && pin.pixel.y < view.render_height;
+        pin.value = if inside {
+            renderer
+                .read_scene(pin.pixel.x, pin.pixel.y, 1, 1)
+                .and_then(|pixels| pixels.first().copied())
+        } else {
+            None
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fill_lens, lens_region, Inspector};
+    use glam::IVec2;
+
+    #[test]
+    fn lens_is_clipped_to_target() {
+        assert_eq!(
+            lens_region(IVec2::new(10, 10), 2, 100, 100),
+            Some([8, 8, 5, 5])
+        );
+        assert_eq!(
+            lens_region(IVec2::new(0, 99), 2, 100, 100),
+            Some([0, 97, 3, 3])
+        );
+        assert_eq!(lens_region(IVec2::new(200, 10), 2, 100, 100), None);
+    }
+
+    #[test]
+    fn lens_is_top_row_first() {
+        // 3x3 lens in the lower left corner, only the upper right 2x2 is inside the target
+        let center = IVec2::new(0, 0);
+        let region = lens_region(center, 1, 10, 10).unwrap();
+        let pixels = [[0.0; 4], [1.0; 4], [2.0; 4], [3.0; 4]];
+        let lens = fill_lens(center, 1, region, &pixels);
+
+        assert_eq!(lens[0], None);
+        assert_eq!(lens[1], Some([2.0; 4]));
+        assert_eq!(lens[2], Some([3.0; 4]));
+        assert_eq!(lens[4], Some([0.0; 4]));
+        assert_eq!(lens[6], None);
+    }
+
+    #[test]
+    fn pins_are_unique() {
+        let mut inspector = Inspector {
+            hovered: Some(IVec2::new(4, 2)),
+            ..Default::default()
+        };
+        inspector.pin_hovered();
+        inspector.pin_hovered();
+        assert_eq!(inspector.pins.len(), 1);
+        assert_eq!(inspector.pins[0].name, "P1");
+    }
+}
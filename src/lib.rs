@@ -2,15 +2,26 @@
 
 pub mod actions;
 pub mod app;
+pub mod bindings;
+pub mod commands;
+pub mod editor;
 
 pub mod config;
 pub mod event;
+pub mod external_editor;
+pub mod gizmos;
 pub mod input;
+pub mod inspector;
 pub mod minime;
 pub mod mouse;
+pub mod preferences;
+pub mod project;
 pub mod render;
+pub mod session;
+pub mod settings;
 pub mod shader;
 pub mod state;
+pub mod timeline;
 pub mod timer;
 pub mod ui;
 pub mod utils;
@@ -18,16 +29,39 @@ pub mod window;
 
 pub use actions::*;
 pub use app::*;
+pub use bindings::*;
+pub use camera_path::*;
+pub use camera_recording::*;
+pub use commands::*;
 pub use config::*;
+pub use display::*;
+pub use editor::*;
 pub use event::*;
+pub use external_editor::*;
+pub use gizmos::*;
 pub use input::*;
+pub use inspector::*;
 pub use minime::*;
 pub use mouse::*;
+pub use overlay::*;
+pub use poster::*;
+pub use preferences::*;
+pub use probe::*;
+pub use profiler::*;
+pub use project::*;
 pub use render::*;
+pub use render_target::*;
+pub use renderer::*;
+pub use scaling::*;
+pub use session::*;
+pub use settings::*;
 pub use shader::*;
 pub use state::*;
+pub use texture::*;
+pub use timeline::*;
 pub use timer::*;
 pub use ui::*;
 pub use uniforms::*;
 pub use utils::*;
+pub use video::*;
 pub use window::*;
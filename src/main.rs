@@ -38,7 +38,7 @@ fn main() -> anyhow::Result<(), anyhow::Error> {
         }
     }
 
-    if config.files.is_some() {
+    if config.project_file().is_some() || !config.shader_files().is_empty() {
         log::info!("loading existing shader");
         app.run(config);
     }
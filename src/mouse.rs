@@ -6,6 +6,8 @@ use crate::event::WindowEventHandler;
 #[derive(Debug)]
 pub struct Mouse {
     pub pos: Vec2,
+    /// Where the cursor is, whether a button is held or not
+    pub cursor: Vec2,
     pub last_pos: Vec2,
     pub delta: Vec2,
     /// Only keep track of the direction the mouse is going in range -1 to 1
@@ -30,6 +32,7 @@ impl Default for Mouse {
     fn default() -> Self {
         Self {
             pos: Vec2::new(0.0, 0.0),
+            cursor: Vec2::ZERO,
             last_pos: Vec2::ZERO,
             delta: Vec2::new(0.0, 0.0),
 
@@ -46,6 +49,7 @@ impl WindowEventHandler for Mouse {
     fn handle_window_events(&mut self, event: &WindowEvent<'_>) -> bool {
         match event {
             WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = Vec2::new(position.x as f32, position.y as f32);
                 if self.is_rmb_down {
                     self.delta = Vec2::new(
                         position.x as f32 - self.pos.x,
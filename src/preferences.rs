@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{Action, WindowOptions};
+
+/// Settings that belong to the user rather than to a shader, like key bindings.
+/// Read from `--config` or from `skuggbox/config.toml` in the user's config directory.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    /// Chord to action, e.g. `"Ctrl+Right" = { TimeForward = 10.0 }`
+    pub keys: BTreeMap<String, Action>,
+    pub window: WindowOptions,
+    pub editor: EditorPreferences,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EditorPreferences {
+    /// How to open a file at a line in an external editor, e.g. `code -g {file}:{line}`.
+    /// `$VISUAL` or `$EDITOR` is used without it, unless it needs a terminal.
+    pub command: Option<String>,
+}
+
+impl Preferences {
+    /// `$XDG_CONFIG_HOME/skuggbox/config.toml`, falling back to `~/.config` and `%APPDATA%`
+    pub fn default_path() -> Option<PathBuf> {
+        let dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+            .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+        Some(dir.join("skuggbox").join("config.toml"))
+    }
+
+    /// Load the preferences. A missing file gives the defaults, a broken one is reported and
+    /// gives the defaults as well.
+    pub fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(content) => toml::from_str(&content).unwrap_or_else(|err| {
+                log::warn!("Ignoring invalid config {:?}: {}", path, err);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Preferences;
+    use crate::{Action, CameraMovement};
+
+    #[test]
+    fn keys_table() {
+        let config = r#"
+            [keys]
+            "Ctrl+Right" = { TimeForward = 10.0 }
+            "Q" = { CameraMove = "StrafeLeft" }
+            "F1" = "ToggleProfiler"
+
+            [editor]
+            command = "code -g {file}:{line}"
+        "#;
+        let preferences: Preferences = toml::from_str(config).unwrap();
+        assert_eq!(preferences.keys["Ctrl+Right"], Action::TimeForward(10.0));
+        assert_eq!(
+            preferences.keys["Q"],
+            Action::CameraMove(CameraMovement::StrafeLeft)
+        );
+        assert_eq!(preferences.keys["F1"], Action::ToggleProfiler);
+        assert_eq!(
+            preferences.editor.command.as_deref(),
+            Some("code -g {file}:{line}")
+        );
+    }
+}
@@ -0,0 +1,407 @@
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::camera::{CameraKind, Projection};
+use crate::{Action, AppState, TextureWrap, UpscaleFilter};
+
+/// Number of `iChannelN` samplers a pass can read from
+pub const MAX_CHANNELS: usize = 4;
+
+/// A project ties passes, textures and uniforms together. Opened with `skuggbox project.toml`.
+/// Relative paths are relative to the project file.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Project {
+    /// Where the project was loaded from
+    #[serde(skip)]
+    pub path: PathBuf,
+    pub window: ProjectWindow,
+    pub timeline: ProjectTimeline,
+    pub camera: ProjectCamera,
+    /// Drawn in order. The last pass goes to the screen, the others render into buffers that
+    /// any pass can read through its channels.
+    pub passes: Vec<Pass>,
+    pub textures: Vec<TextureBinding>,
+    pub uniforms: BTreeMap<String, UniformDefinition>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectWindow {
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectTimeline {
+    /// Length of the timeline in seconds
+    pub duration: Option<f32>,
+    pub bpm: Option<f32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectCamera {
+    pub kind: Option<CameraKind>,
+    /// Inject `skuggbox_camera` for `#pragma skuggbox(camera)`
+    pub integration: Option<bool>,
+    pub projection: Option<Projection>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Pass {
+    pub name: String,
+    pub shader: PathBuf,
+    /// Texture or pass names bound to `iChannel0`, `iChannel1`...
+    /// A pass reads the current frame of the passes before it and the previous frame of itself
+    /// and the passes after it.
+    pub channels: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TextureBinding {
+    pub name: String,
+    /// PNG image
+    pub path: PathBuf,
+    pub filter: UpscaleFilter,
+    pub wrap: TextureWrap,
+}
+
+impl Default for TextureBinding {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            path: PathBuf::new(),
+            filter: UpscaleFilter::Linear,
+            wrap: TextureWrap::default(),
+        }
+    }
+}
+
+/// A `float` or `vecN` uniform the project sets by name, with an optional range for the UI
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UniformDefinition {
+    pub value: UniformValue,
+    #[serde(default)]
+    pub min: Option<f32>,
+    #[serde(default)]
+    pub max: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum UniformValue {
+    Float(f32),
+    Vector(Vec<f32>),
+}
+
+impl UniformValue {
+    pub fn components(&self) -> Vec<f32> {
+        match self {
+            UniformValue::Float(value) => vec![*value],
+            UniformValue::Vector(values) => values.clone(),
+        }
+    }
+}
+
+/// What a pass channel reads from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelSource {
+    /// Index into `Project::textures`
+    Texture(usize),
+    /// Index into `Project::passes`
+    Pass(usize),
+}
+
+impl Project {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content =
+            fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        Self::parse(&content, path).with_context(|| format!("Invalid project {:?}", path))
+    }
+
+    /// Parse and validate a project, resolving its paths against the directory of `path`
+    pub fn parse(content: &str, path: &Path) -> anyhow::Result<Self> {
+        let mut project: Project = toml::from_str(content)?;
+        project.path = path.to_owned();
+
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        for pass in &mut project.passes {
+            pass.shader = dir.join(&pass.shader);
+        }
+        for texture in &mut project.textures {
+            texture.path = dir.join(&texture.path);
+        }
+
+        project.validate()?;
+        Ok(project)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        if self.passes.is_empty() {
+            bail!("At least one pass is needed");
+        }
+
+        let mut names = HashSet::new();
+        let all_names = self
+            .passes
+            .iter()
+            .map(|pass| &pass.name)
+            .chain(self.textures.iter().map(|texture| &texture.name));
+        for name in all_names {
+            if name.is_empty() {
+                bail!("Passes and textures need a name");
+            }
+            if !names.insert(name) {
+                bail!("The name {:?} is used more than once", name);
+            }
+        }
+
+        for pass in &self.passes {
+            if pass.channels.len() > MAX_CHANNELS {
+                bail!(
+                    "Pass {:?} has {} channels, at most {} are supported",
+                    pass.name,
+                    pass.channels.len(),
+                    MAX_CHANNELS
+                );
+            }
+            for channel in &pass.channels {
+                match self.channel_source(channel) {
+                    None => bail!("Pass {:?} reads unknown channel {:?}", pass.name, channel),
+                    Some(ChannelSource::Pass(index)) if index == self.output_index() => bail!(
+                        "Pass {:?} can't read {:?}, it's drawn to the screen",
+                        pass.name,
+                        channel
+                    ),
+                    Some(_) => {}
+                }
+            }
+        }
+
+        for (name, uniform) in &self.uniforms {
+            let components = uniform.value.components().len();
+            if !(1..=4).contains(&components) {
+                bail!("Uniform {:?} has {} components", name, components);
+            }
+            if let (Some(min), Some(max)) = (uniform.min, uniform.max) {
+                if min > max {
+                    bail!("Uniform {:?} has min above max", name);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Index of the pass that is drawn to the screen
+    pub fn output_index(&self) -> usize {
+        self.passes.len().saturating_sub(1)
+    }
+
+    pub fn shader_files(&self) -> Vec<PathBuf> {
+        self.passes.iter().map(|pass| pass.shader.clone()).collect()
+    }
+
+    pub fn channel_source(&self, name: &str) -> Option<ChannelSource> {
+        if let Some(index) = self.textures.iter().position(|t| t.name == name) {
+            return Some(ChannelSource::Texture(index));
+        }
+        self.passes
+            .iter()
+            .position(|pass| pass.name == name)
+            .map(ChannelSource::Pass)
+    }
+
+    /// Apply the settings that changed since `previous` was applied, or all of them the first
+    /// time. Whatever was tweaked at runtime is kept until the file touches that setting.
+    /// The window size is up to the caller.
+    pub fn apply(
+        &self,
+        previous: Option<&Project>,
+        state: &mut AppState,
+        actions: &mut Vec<Action>,
+    ) {
+        if previous.map(|p| &p.timeline) != Some(&self.timeline) {
+            if let Some(duration) = self.timeline.duration {
+                state.timeline.duration = duration.max(1.0);
+            }
+            state.timeline.bpm = self.timeline.bpm;
+        }
+
+        if previous.map(|p| &p.camera) != Some(&self.camera) {
+            if let Some(projection) = &self.camera.projection {
+                *state.camera.projection_mut() = projection.clone();
+            }
+            if let Some(kind) = self.camera.kind {
+                state.select_camera(kind);
+            }
+            if let Some(integration) = self.camera.integration {
+                actions.push(Action::CameraToggleIntegration(integration));
+            }
+        }
+
+        state
+            .project_uniforms
+            .retain(|name, _| self.uniforms.contains_key(name));
+        for (name, uniform) in &self.uniforms {
+            let unchanged = previous.and_then(|p| p.uniforms.get(name)) == Some(uniform);
+            if !unchanged || !state.project_uniforms.contains_key(name) {
+                state
+                    .project_uniforms
+                    .insert(name.clone(), uniform.value.components());
+            }
+        }
+    }
+
+    /// Save a self contained copy in `dir`: the project as `project.toml` and each pass as
+    /// `<pass>.glsl` with `sources[i]` as its content. Textures are referenced where they are.
+    pub fn save_snapshot(&self, dir: &Path, sources: &[String]) -> anyhow::Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+        let mut snapshot = self.clone();
+        for (pass, source) in snapshot.passes.iter_mut().zip(sources) {
+            let file_name = format!("{}.glsl", pass.name);
+            fs::write(dir.join(&file_name), source)?;
+            pass.shader = PathBuf::from(file_name);
+        }
+        for texture in &mut snapshot.textures {
+            texture.path = fs::canonicalize(&texture.path).unwrap_or_else(|_| texture.path.clone());
+        }
+
+        let path = dir.join("project.toml");
+        fs::write(&path, toml::to_string_pretty(&snapshot)?)?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChannelSource, Project, UniformValue};
+    use crate::camera::CameraKind;
+    use crate::{PreProcessor, PreProcessorConfig, TempPath, SKUGGBOX_TILE_OFFSET};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    const PROJECT: &str = r#"
+        [window]
+        width = 1280
+        height = 720
+
+        [timeline]
+        duration = 32.0
+        bpm = 120
+
+        [camera]
+        kind = "Fly"
+
+        [[passes]]
+        name = "feedback"
+        shader = "feedback.glsl"
+        channels = ["feedback", "noise"]
+
+        [[passes]]
+        name = "image"
+        shader = "shaders/image.glsl"
+        channels = ["feedback"]
+
+        [[textures]]
+        name = "noise"
+        path = "noise.png"
+
+        [uniforms.glow]
+        value = 0.5
+        min = 0.0
+        max = 2.0
+
+        [uniforms.tint]
+        value = [1.0, 0.5, 0.25]
+    "#;
+
+    #[test]
+    fn parse_project() {
+        let project = Project::parse(PROJECT, Path::new("demo/project.toml")).unwrap();
+        assert_eq!(project.window.width, Some(1280));
+        assert_eq!(project.timeline.bpm, Some(120.0));
+        assert_eq!(project.camera.kind, Some(CameraKind::Fly));
+        assert_eq!(project.output_index(), 1);
+        assert_eq!(
+            project.shader_files(),
+            vec![
+                PathBuf::from("demo/feedback.glsl"),
+                PathBuf::from("demo/shaders/image.glsl")
+            ]
+        );
+        assert_eq!(project.textures[0].path, PathBuf::from("demo/noise.png"));
+        assert_eq!(
+            project.channel_source("noise"),
+            Some(ChannelSource::Texture(0))
+        );
+        assert_eq!(
+            project.channel_source("feedback"),
+            Some(ChannelSource::Pass(0))
+        );
+        assert_eq!(project.uniforms["glow"].value, UniformValue::Float(0.5));
+        assert_eq!(
+            project.uniforms["tint"].value.components(),
+            vec![1.0, 0.5, 0.25]
+        );
+    }
+
+    #[test]
+    fn snapshot_opens_as_project() {
+        let dir = TempPath::new("project-snapshot-test");
+        let demo = dir.0.join("demo");
+        fs::create_dir_all(demo.join("shaders")).unwrap();
+        fs::write(demo.join("project.toml"), PROJECT).unwrap();
+        let shader = "#version 330 core\nout vec4 c;\nvoid main() { c = gl_FragCoord; }";
+        fs::write(demo.join("feedback.glsl"), shader).unwrap();
+        fs::write(demo.join("shaders/image.glsl"), shader).unwrap();
+
+        let pre_processor = PreProcessor::new(PreProcessorConfig {
+            use_camera_integration: false,
+            use_probes: false,
+        });
+        let project = Project::load(&demo.join("project.toml")).unwrap();
+        let sources: Vec<String> = project
+            .shader_files()
+            .iter()
+            .map(|path| pre_processor.load_file(path).snapshot_src)
+            .collect();
+        let snapshots = dir.0.join("snapshot");
+        let path = project.save_snapshot(&snapshots, &sources).unwrap();
+
+        let snapshot = Project::load(&path).unwrap();
+        assert_eq!(snapshot.passes[1].shader, snapshots.join("image.glsl"));
+        assert_eq!(snapshot.uniforms, project.uniforms);
+        assert_eq!(snapshot.timeline, project.timeline);
+
+        // the passes get skuggbox's uniforms once, like the originals
+        for path in snapshot.shader_files() {
+            let content = pre_processor.load_file(&path);
+            let declarations = content.shader_src.matches("uniform vec2 sbTileOffset;");
+            assert_eq!(declarations.count(), 1);
+            assert!(content.shader_src.contains(SKUGGBOX_TILE_OFFSET));
+        }
+    }
+
+    #[test]
+    fn reject_invalid_projects() {
+        let path = Path::new("project.toml");
+        assert!(Project::parse("", path).is_err());
+
+        let unknown = "[[passes]]\nname = \"a\"\nshader = \"a.glsl\"\nchannels = [\"b\"]";
+        assert!(Project::parse(unknown, path).is_err());
+
+        let output = "[[passes]]\nname = \"a\"\nshader = \"a.glsl\"\nchannels = [\"a\"]";
+        assert!(Project::parse(output, path).is_err());
+
+        let twice = "[[passes]]\nname = \"a\"\nshader = \"a.glsl\"\n[[textures]]\nname = \"a\"";
+        assert!(Project::parse(twice, path).is_err());
+    }
+}
@@ -1,17 +1,163 @@
-use glam::{Mat4, Vec2, Vec3, Vec4};
-use std::f32::consts::PI;
-use winit::event::{ElementState, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+use glam::{Mat3, Mat4, Quat, Vec2, Vec3, Vec4};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{FRAC_PI_2, PI};
+use winit::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
 
 use crate::event::WindowEventHandler;
 use crate::mouse::Mouse;
+use crate::{ActionModifier, CameraMovement, CameraPose, SceneView};
 
 pub trait CameraModel: WindowEventHandler {
     fn handle_mouse(&mut self, mouse: &Mouse, delta_time: f32);
 
+    /// Called once per frame, also when playback is paused.
+    /// * `delta_time` - Wall clock time since the last frame in seconds
+    fn update(&mut self, _delta_time: f32, _modifier: &ActionModifier) {}
+
+    /// Where the scene is drawn in the window, for cameras that map the cursor onto the scene
+    fn set_scene_view(&mut self, _view: &SceneView) {}
+
     fn calculate_uniform_data(&mut self) -> Mat4;
+
+    fn projection(&self) -> &Projection;
+
+    fn projection_mut(&mut self) -> &mut Projection;
+
+    /// Move the camera to a saved pose. Cameras that can't represent it exactly, like the
+    /// orbit camera which always keeps the horizon level, get as close as they can.
+    /// The FOV is left to the caller.
+    fn set_pose(&mut self, _pose: &CameraPose) {}
+
+    /// The point the camera revolves around, if it has one. Shown by the viewport overlay.
+    fn target(&self) -> Option<Vec3> {
+        None
+    }
+
+    /// Step the camera for a `CameraMove` action. Cameras that move while the key is held,
+    /// like the fly camera, ignore it.
+    fn nudge(&mut self, _movement: &CameraMovement) {}
+
+    /// The key bound to a `CameraMove` action went down or up. For cameras that keep moving
+    /// while it is held.
+    fn hold(&mut self, _movement: &CameraMovement, _held: bool) {}
+
+    /// Revolve around `target` from now on. The camera stays where it is and turns to face it.
+    fn set_target(&mut self, _target: Vec3) {}
+
+    /// Value of `sbView2D` for a given `iResolution`. Maps pixels to the pixels they show.
+    fn view_2d(&self, _resolution: Vec2) -> Mat3 {
+        Mat3::IDENTITY
+    }
+}
+
+/// How rays leave the camera. Sent to the shader as `sbCameraProjection`, `sbCameraOrthoScale`
+/// and `sbCameraLens`, and used by `skuggbox_camera`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Projection {
+    /// Vertical field of view in degrees
+    pub fov: f32,
+    pub orthographic: bool,
+    /// Half the height of the view in world units when orthographic
+    pub ortho_scale: f32,
+    /// Rays start this far from the camera
+    pub near: f32,
+    /// Passed on for shaders to limit their marching with
+    pub far: f32,
+    /// Distance to the plane in focus, for shaders doing depth of field
+    pub focus_distance: f32,
+    /// Radius of the lens, 0 keeps everything in focus
+    pub aperture: f32,
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        // 90 degrees matches the `normalize(vec3(uv, 1))` we've always used
+        Self {
+            fov: 90.0,
+            orthographic: false,
+            ortho_scale: 2.0,
+            near: 0.0,
+            far: 100.0,
+            focus_distance: 5.0,
+            aperture: 0.0,
+        }
+    }
+}
+
+impl Projection {
+    /// `tan(fov / 2)`, what the uv has to be scaled by to get the ray direction
+    pub fn tan_half_fov(&self) -> f32 {
+        (self.fov.clamp(1.0, 179.0).to_radians() / 2.0).tan()
+    }
+
+    /// `sbCameraProjection`: tan(fov / 2), aspect, near and far
+    pub fn uniform(&self, aspect: f32) -> Vec4 {
+        Vec4::new(self.tan_half_fov(), aspect, self.near, self.far)
+    }
+
+    /// `sbCameraOrthoScale`, 0 when using a perspective projection
+    pub fn ortho_uniform(&self) -> f32 {
+        if self.orthographic {
+            self.ortho_scale.max(1e-4)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// The camera models that can be used
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum CameraKind {
+    #[default]
+    Orbit,
+    Fly,
+    Arcball,
+    #[value(name = "2d")]
+    Pan2D,
+}
+
+impl CameraKind {
+    pub const ALL: [CameraKind; 4] = [
+        CameraKind::Orbit,
+        CameraKind::Fly,
+        CameraKind::Arcball,
+        CameraKind::Pan2D,
+    ];
+
+    pub fn create(&self) -> Box<dyn CameraModel> {
+        match self {
+            CameraKind::Orbit => Box::<OrbitCamera>::default(),
+            CameraKind::Fly => Box::<FlyCamera>::default(),
+            CameraKind::Arcball => Box::<ArcballCamera>::default(),
+            CameraKind::Pan2D => Box::<Pan2DCamera>::default(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CameraKind::Orbit => "orbit",
+            CameraKind::Fly => "fly",
+            CameraKind::Arcball => "arcball",
+            CameraKind::Pan2D => "2d",
+        }
+    }
+}
+
+/// Build the `sbCameraTransform` matrix: side, up and forward axes followed by the position
+fn camera_matrix(pos: Vec3, forward: Vec3) -> Mat4 {
+    let side = Vec3::Y.cross(forward).normalize_or_zero();
+    let up = forward.cross(side);
+    Mat4::from_cols(
+        side.extend(0.0),
+        up.extend(0.0),
+        forward.extend(0.0),
+        pos.extend(1.0),
+    )
 }
 
 pub struct OrbitCamera {
+    pub projection: Projection,
     pos: Vec3,
     target: Vec3,
     angle: Vec2,
@@ -23,6 +169,7 @@ pub struct OrbitCamera {
 impl Default for OrbitCamera {
     fn default() -> Self {
         Self {
+            projection: Projection::default(),
             pos: Vec3::new(02.0, 2.0, -2.0),
             target: Vec3::new(0.0, 0.0, 0.0),
             angle: Vec2::ZERO,
@@ -33,6 +180,14 @@ impl Default for OrbitCamera {
 }
 
 impl CameraModel for OrbitCamera {
+    fn projection(&self) -> &Projection {
+        &self.projection
+    }
+
+    fn projection_mut(&mut self) -> &mut Projection {
+        &mut self.projection
+    }
+
     fn handle_mouse(&mut self, mouse: &Mouse, delta_time: f32) {
         if mouse.is_rmb_down {
             // scale the x and y differently since the movement range is different
@@ -47,10 +202,41 @@ impl CameraModel for OrbitCamera {
         }
     }
 
+    fn set_pose(&mut self, pose: &CameraPose) {
+        let p = pose.position;
+        self.target = p + pose.forward() * p.length().max(1.0);
+        self.look_from(p);
+    }
+
+    fn target(&self) -> Option<Vec3> {
+        Some(self.target)
+    }
+
+    fn nudge(&mut self, movement: &CameraMovement) {
+        match movement {
+            CameraMovement::StrafeLeft => self.target.x += 0.5,
+            CameraMovement::StrafeRight => self.target.x -= 0.5,
+            CameraMovement::MoveForward => self.target.y += 0.5,
+            CameraMovement::MoveBackward => self.target.y -= 0.5,
+            CameraMovement::MoveUp => self.target.z += 0.5,
+            CameraMovement::MoveDown => self.target.z -= 0.5,
+            CameraMovement::Reset => self.target = Vec3::ZERO,
+        }
+    }
+
+    fn set_target(&mut self, target: Vec3) {
+        let position = self.calculate_uniform_data().w_axis.truncate();
+        self.target = target;
+        self.look_from(position);
+    }
+
     fn calculate_uniform_data(&mut self) -> Mat4 {
-        self.pos.x = (self.angle.x * PI).sin() * self.zoom;
-        self.pos.y = (self.angle.y * 1.53).sin() * self.zoom;
-        self.pos.z = (self.angle.x * PI).cos() * self.zoom;
+        let offset = Vec3::new(
+            (self.angle.x * PI).sin() * self.zoom,
+            (self.angle.y * 1.53).sin() * self.zoom,
+            (self.angle.x * PI).cos() * self.zoom,
+        );
+        self.pos = self.target + offset;
 
         let up = Vec3::new(0.0, 1.0, 0.0);
         let forward = (self.target - self.pos).normalize();
@@ -65,6 +251,16 @@ impl CameraModel for OrbitCamera {
     }
 }
 
+impl OrbitCamera {
+    /// Pick the angles and zoom that put the camera at `position`, as close as the orbit allows
+    fn look_from(&mut self, position: Vec3) {
+        let offset = position - self.target;
+        self.zoom = Vec2::new(offset.x, offset.z).length().max(0.25);
+        self.angle.x = offset.x.atan2(offset.z) / PI;
+        self.angle.y = (offset.y / self.zoom).clamp(-1.0, 1.0).asin() / 1.53;
+    }
+}
+
 impl WindowEventHandler for OrbitCamera {
     fn handle_window_events(&mut self, event: &WindowEvent<'_>) -> bool {
         match event {
@@ -81,28 +277,344 @@ impl WindowEventHandler for OrbitCamera {
                 true
             }
 
-            WindowEvent::KeyboardInput { input, .. } => {
-                if input.state == ElementState::Pressed {
-                    if let Some(keycode) = input.virtual_keycode {
-                        match keycode {
-                            VirtualKeyCode::A => {
-                                self.target.x += 0.5;
-                            }
-                            VirtualKeyCode::D => {
-                                self.target.x -= 0.5;
-                            }
-                            VirtualKeyCode::W => {
-                                self.target.y += 0.5;
-                            }
-                            VirtualKeyCode::S => {
-                                self.target.y -= 0.5;
-                            }
-
-                            _ => (),
-                        }
+            _ => false,
+        }
+    }
+}
+
+/// Movements whose keys are currently held down
+#[derive(Debug, Default, Clone, Copy)]
+struct HeldKeys {
+    forward: bool,
+    backward: bool,
+    left: bool,
+    right: bool,
+    up: bool,
+    down: bool,
+}
+
+impl HeldKeys {
+    /// Wanted direction of movement in camera space, x to the right, y up and z forward
+    fn direction(&self) -> Vec3 {
+        let axis = |positive: bool, negative: bool| f32::from(positive) - f32::from(negative);
+        Vec3::new(
+            axis(self.right, self.left),
+            axis(self.up, self.down),
+            axis(self.forward, self.backward),
+        )
+    }
+}
+
+/// First person camera. Moves while the `CameraMove` keys are held, WASD, Q and E by default,
+/// and dragging with the right mouse button looks around. Movement eases in and out so it doesn't depend on the framerate.
+pub struct FlyCamera {
+    pub projection: Projection,
+    pub position: Vec3,
+    /// Rotation around the world up axis in radians, 0 looks along +z
+    pub yaw: f32,
+    /// Rotation up and down in radians
+    pub pitch: f32,
+    /// Units per second at full speed
+    pub speed: f32,
+    /// Radians per pixel of mouse movement
+    pub look_sensitivity: f32,
+    /// Time in seconds for the velocity to get about 63% of the way to the wanted velocity
+    pub smoothing: f32,
+    velocity: Vec3,
+    keys: HeldKeys,
+    looking: bool,
+    last_cursor: Option<Vec2>,
+}
+
+impl Default for FlyCamera {
+    fn default() -> Self {
+        let position = Vec3::new(0.0, 1.0, -5.0);
+        Self {
+            projection: Projection::default(),
+            position,
+            yaw: 0.0,
+            pitch: 0.0,
+            speed: 2.0,
+            look_sensitivity: 0.003,
+            smoothing: 0.15,
+            velocity: Vec3::ZERO,
+            keys: HeldKeys::default(),
+            looking: false,
+            last_cursor: None,
+        }
+    }
+}
+
+impl FlyCamera {
+    pub fn forward(&self) -> Vec3 {
+        Vec3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        )
+    }
+
+    /// Move the camera `delta_time` seconds ahead.
+    /// The velocity approaches the wanted velocity exponentially and the position is the exact
+    /// integral of that curve, so many short steps end up where one long step does.
+    pub fn step(&mut self, delta_time: f32, speed_factor: f32) {
+        if delta_time <= 0.0 {
+            return;
+        }
+
+        let forward = self.forward();
+        let side = Vec3::Y.cross(forward).normalize_or_zero();
+        let direction = self.keys.direction();
+        let wanted = (side * direction.x + Vec3::Y * direction.y + forward * direction.z)
+            .normalize_or_zero()
+            * self.speed
+            * speed_factor;
+
+        let smoothing = self.smoothing.max(0.001);
+        let decay = (-delta_time / smoothing).exp();
+        self.position += wanted * delta_time + (self.velocity - wanted) * smoothing * (1.0 - decay);
+        self.velocity = wanted + (self.velocity - wanted) * decay;
+    }
+}
+
+impl CameraModel for FlyCamera {
+    fn projection(&self) -> &Projection {
+        &self.projection
+    }
+
+    fn projection_mut(&mut self) -> &mut Projection {
+        &mut self.projection
+    }
+
+    fn handle_mouse(&mut self, _mouse: &Mouse, _delta_time: f32) {
+        // mouse look is handled from the cursor events directly to get exact deltas
+    }
+
+    fn update(&mut self, delta_time: f32, modifier: &ActionModifier) {
+        self.step(delta_time, modifier.factor());
+    }
+
+    fn hold(&mut self, movement: &CameraMovement, held: bool) {
+        let key = match movement {
+            CameraMovement::MoveForward => &mut self.keys.forward,
+            CameraMovement::MoveBackward => &mut self.keys.backward,
+            CameraMovement::StrafeLeft => &mut self.keys.left,
+            CameraMovement::StrafeRight => &mut self.keys.right,
+            CameraMovement::MoveUp => &mut self.keys.up,
+            CameraMovement::MoveDown => &mut self.keys.down,
+            CameraMovement::Reset => return,
+        };
+        *key = held;
+    }
+
+    fn calculate_uniform_data(&mut self) -> Mat4 {
+        camera_matrix(self.position, self.forward())
+    }
+
+    fn set_pose(&mut self, pose: &CameraPose) {
+        let forward = pose.forward();
+        self.position = pose.position;
+        self.yaw = forward.x.atan2(forward.z);
+        self.pitch = forward.y.clamp(-1.0, 1.0).asin();
+        self.velocity = Vec3::ZERO;
+    }
+
+    fn set_target(&mut self, target: Vec3) {
+        let forward = (target - self.position).normalize_or_zero();
+        if forward != Vec3::ZERO {
+            self.yaw = forward.x.atan2(forward.z);
+            self.pitch = forward.y.clamp(-1.0, 1.0).asin();
+        }
+    }
+}
+
+impl WindowEventHandler for FlyCamera {
+    fn handle_window_events(&mut self, event: &WindowEvent<'_>) -> bool {
+        match event {
+            WindowEvent::MouseInput {
+                button: MouseButton::Right,
+                state,
+                ..
+            } => {
+                self.looking = *state == ElementState::Pressed;
+                true
+            }
+
+            WindowEvent::CursorMoved { position, .. } => {
+                let cursor = Vec2::new(position.x as f32, position.y as f32);
+                if let (true, Some(last)) = (self.looking, self.last_cursor) {
+                    let delta = cursor - last;
+                    self.yaw += delta.x * self.look_sensitivity;
+                    self.pitch = (self.pitch - delta.y * self.look_sensitivity)
+                        .clamp(-FRAC_PI_2 + 0.01, FRAC_PI_2 - 0.01);
+                }
+                self.last_cursor = Some(cursor);
+                true
+            }
+
+            // losing focus means we won't see the key releases
+            WindowEvent::Focused(false) => {
+                self.keys = HeldKeys::default();
+                self.looking = false;
+                true
+            }
+
+            _ => false,
+        }
+    }
+}
+
+/// Map a window position onto the arcball sphere, in camera space with z pointing into the scene
+fn arcball_vector(view: &SceneView, window_pos: Vec2) -> Vec3 {
+    let [x, y, width, height] = view.viewport;
+    let radius = (width.min(height) as f32 / 2.0).max(1.0);
+    let center = Vec2::new(
+        x as f32 + width as f32 / 2.0,
+        view.window_height as f32 - (y as f32 + height as f32 / 2.0),
+    );
+    let p = (window_pos - center) / radius * Vec2::new(1.0, -1.0);
+    let length_squared = p.length_squared();
+    if length_squared <= 1.0 {
+        Vec3::new(p.x, p.y, -(1.0 - length_squared).sqrt())
+    } else {
+        p.normalize().extend(0.0)
+    }
+}
+
+/// Trackball camera around a target. Drag with the right mouse button to rotate freely,
+/// hold shift while dragging to pan and use the wheel to zoom.
+pub struct ArcballCamera {
+    pub projection: Projection,
+    pub target: Vec3,
+    /// Camera to world rotation
+    pub orientation: Quat,
+    pub distance: f32,
+    view: SceneView,
+    dragging: bool,
+    panning: bool,
+    last_cursor: Option<Vec2>,
+}
+
+impl Default for ArcballCamera {
+    fn default() -> Self {
+        Self {
+            projection: Projection::default(),
+            target: Vec3::ZERO,
+            orientation: Quat::IDENTITY,
+            distance: 5.0,
+            view: SceneView::default(),
+            dragging: false,
+            panning: false,
+            last_cursor: None,
+        }
+    }
+}
+
+impl ArcballCamera {
+    pub fn position(&self) -> Vec3 {
+        self.target - self.orientation * Vec3::Z * self.distance
+    }
+
+    /// Rotate as if the cursor dragged the scene from `from` to `to`, in window coordinates
+    pub fn rotate(&mut self, from: Vec2, to: Vec2) {
+        let a = arcball_vector(&self.view, from);
+        let b = arcball_vector(&self.view, to);
+        let rotation = Quat::from_rotation_arc(a.normalize(), b.normalize());
+        self.orientation = (self.orientation * rotation.inverse()).normalize();
+    }
+
+    /// Move the target in the camera plane by a cursor movement in window pixels
+    pub fn pan(&mut self, delta: Vec2) {
+        let height = self.view.viewport[3].max(1) as f32;
+        let scale = self.distance / height;
+        let side = self.orientation * Vec3::X;
+        let up = self.orientation * Vec3::Y;
+        self.target += (-side * delta.x + up * delta.y) * scale;
+    }
+}
+
+impl CameraModel for ArcballCamera {
+    fn projection(&self) -> &Projection {
+        &self.projection
+    }
+
+    fn projection_mut(&mut self) -> &mut Projection {
+        &mut self.projection
+    }
+
+    fn handle_mouse(&mut self, _mouse: &Mouse, _delta_time: f32) {}
+
+    fn set_scene_view(&mut self, view: &SceneView) {
+        self.view = *view;
+    }
+
+    fn calculate_uniform_data(&mut self) -> Mat4 {
+        Mat4::from_cols(
+            (self.orientation * Vec3::X).extend(0.0),
+            (self.orientation * Vec3::Y).extend(0.0),
+            (self.orientation * Vec3::Z).extend(0.0),
+            self.position().extend(1.0),
+        )
+    }
+
+    fn set_pose(&mut self, pose: &CameraPose) {
+        self.orientation = pose.orientation;
+        self.target = pose.position + pose.forward() * self.distance;
+    }
+
+    fn target(&self) -> Option<Vec3> {
+        Some(self.target)
+    }
+
+    fn set_target(&mut self, target: Vec3) {
+        let position = self.position();
+        let forward = (target - position).normalize_or_zero();
+        if forward == Vec3::ZERO {
+            return;
+        }
+        let pose = CameraPose::from_transform(camera_matrix(position, forward), 0.0);
+        self.orientation = pose.orientation;
+        self.distance = (target - position).length();
+        self.target = target;
+    }
+}
+
+impl WindowEventHandler for ArcballCamera {
+    fn handle_window_events(&mut self, event: &WindowEvent<'_>) -> bool {
+        match event {
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.panning = modifiers.shift();
+                false
+            }
+
+            WindowEvent::MouseInput {
+                button: MouseButton::Right,
+                state,
+                ..
+            } => {
+                self.dragging = *state == ElementState::Pressed;
+                true
+            }
+
+            WindowEvent::CursorMoved { position, .. } => {
+                let cursor = Vec2::new(position.x as f32, position.y as f32);
+                if let (true, Some(last)) = (self.dragging, self.last_cursor) {
+                    if self.panning {
+                        self.pan(cursor - last);
+                    } else if cursor != last {
+                        self.rotate(last, cursor);
                     }
                 }
+                self.last_cursor = Some(cursor);
+                true
+            }
 
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / 40.0,
+                };
+                self.distance = (self.distance * 0.9f32.powf(lines)).max(0.01);
                 true
             }
 
@@ -110,3 +622,251 @@ impl WindowEventHandler for OrbitCamera {
         }
     }
 }
+
+/// Pan and zoom for flat shaders. Drag with the right mouse button to pan and use the wheel to
+/// zoom around the cursor. Applied through `sbView2D`, which the pre-processor also folds into
+/// `gl_FragCoord`, so 2D shaders work without changes.
+pub struct Pan2DCamera {
+    pub projection: Projection,
+    /// Offset of the view in units of the render height
+    pub center: Vec2,
+    pub zoom: f32,
+    view: SceneView,
+    dragging: bool,
+    last_cursor: Option<Vec2>,
+}
+
+impl Default for Pan2DCamera {
+    fn default() -> Self {
+        Self {
+            projection: Projection::default(),
+            center: Vec2::ZERO,
+            zoom: 1.0,
+            view: SceneView::default(),
+            dragging: false,
+            last_cursor: None,
+        }
+    }
+}
+
+impl Pan2DCamera {
+    /// A window position in the normalized coordinates the view works in: relative to the
+    /// middle of the scene, in units of its height, y up
+    fn normalized(&self, window_pos: Vec2) -> Vec2 {
+        let [x, y, width, height] = self.view.viewport;
+        let height = height.max(1) as f32;
+        let middle = Vec2::new(
+            x as f32 + width as f32 / 2.0,
+            self.view.window_height as f32 - (y as f32 + height / 2.0),
+        );
+        (window_pos - middle) / height * Vec2::new(1.0, -1.0)
+    }
+
+    pub fn pan(&mut self, from: Vec2, to: Vec2) {
+        self.center -= (self.normalized(to) - self.normalized(from)) / self.zoom;
+    }
+
+    /// Zoom by `factor` keeping the point under `window_pos` in place
+    pub fn zoom_at(&mut self, window_pos: Vec2, factor: f32) {
+        let cursor = self.normalized(window_pos);
+        let world = cursor / self.zoom + self.center;
+        self.zoom = (self.zoom * factor).clamp(1e-6, 1e6);
+        self.center = world - cursor / self.zoom;
+    }
+}
+
+impl CameraModel for Pan2DCamera {
+    fn projection(&self) -> &Projection {
+        &self.projection
+    }
+
+    fn projection_mut(&mut self) -> &mut Projection {
+        &mut self.projection
+    }
+
+    fn handle_mouse(&mut self, _mouse: &Mouse, _delta_time: f32) {}
+
+    fn set_scene_view(&mut self, view: &SceneView) {
+        self.view = *view;
+    }
+
+    fn calculate_uniform_data(&mut self) -> Mat4 {
+        camera_matrix(Vec3::new(0.0, 0.0, -5.0), Vec3::Z)
+    }
+
+    fn view_2d(&self, resolution: Vec2) -> Mat3 {
+        let half = resolution / 2.0;
+        Mat3::from_translation(half + self.center * resolution.y)
+            * Mat3::from_scale(Vec2::splat(1.0 / self.zoom))
+            * Mat3::from_translation(-half)
+    }
+}
+
+impl WindowEventHandler for Pan2DCamera {
+    fn handle_window_events(&mut self, event: &WindowEvent<'_>) -> bool {
+        match event {
+            WindowEvent::MouseInput {
+                button: MouseButton::Right,
+                state,
+                ..
+            } => {
+                self.dragging = *state == ElementState::Pressed;
+                true
+            }
+
+            WindowEvent::CursorMoved { position, .. } => {
+                let cursor = Vec2::new(position.x as f32, position.y as f32);
+                if let (true, Some(last)) = (self.dragging, self.last_cursor) {
+                    self.pan(last, cursor);
+                }
+                self.last_cursor = Some(cursor);
+                true
+            }
+
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / 40.0,
+                };
+                if let Some(cursor) = self.last_cursor {
+                    self.zoom_at(cursor, 1.1f32.powf(lines));
+                }
+                true
+            }
+
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        camera_matrix, ArcballCamera, CameraModel, FlyCamera, OrbitCamera, Pan2DCamera, Projection,
+    };
+    use crate::{CameraMovement, SceneView};
+    use glam::{Vec2, Vec3};
+
+    fn fly_for(frames: u32, fps: f32) -> FlyCamera {
+        let mut camera = FlyCamera {
+            position: Vec3::ZERO,
+            ..Default::default()
+        };
+        camera.hold(&CameraMovement::MoveForward, true);
+        for _ in 0..frames / 2 {
+            camera.step(1.0 / fps, 1.0);
+        }
+        // let go half way, inertia keeps it moving for a while
+        camera.hold(&CameraMovement::MoveForward, false);
+        for _ in 0..frames / 2 {
+            camera.step(1.0 / fps, 1.0);
+        }
+        camera
+    }
+
+    #[test]
+    fn movement_is_framerate_independent() {
+        let slow = fly_for(30, 30.0);
+        let fast = fly_for(240, 240.0);
+        assert!((slow.position - fast.position).length() < 1e-4);
+        assert!(slow.position.z > 0.9);
+        assert!(slow.velocity.length() < 0.1);
+    }
+
+    #[test]
+    fn modifier_scales_speed() {
+        let mut camera = FlyCamera::default();
+        let start = camera.position;
+        camera.hold(&CameraMovement::StrafeRight, true);
+        camera.step(10.0, 8.0);
+        // moving right is +x when looking along +z
+        assert!((camera.velocity - Vec3::new(16.0, 0.0, 0.0)).length() < 1e-3);
+        assert!(camera.position.x > start.x);
+    }
+
+    fn view() -> SceneView {
+        SceneView::letterbox((200, 100), (200, 100))
+    }
+
+    #[test]
+    fn pan_2d_zooms_around_cursor() {
+        let mut camera = Pan2DCamera::default();
+        camera.set_scene_view(&view());
+        let resolution = Vec2::new(200.0, 100.0);
+
+        // identity until moved
+        assert_eq!(
+            camera
+                .view_2d(resolution)
+                .transform_point2(Vec2::new(3.0, 4.0)),
+            Vec2::new(3.0, 4.0)
+        );
+
+        // the pixel under the cursor (window 150,25 is render pixel 150,75) stays put
+        camera.zoom_at(Vec2::new(150.0, 25.0), 4.0);
+        let p = camera
+            .view_2d(resolution)
+            .transform_point2(Vec2::new(150.0, 75.0));
+        assert!((p - Vec2::new(150.0, 75.0)).length() < 1e-3);
+
+        // dragging the view right shows what was to the left
+        let before = camera
+            .view_2d(resolution)
+            .transform_point2(Vec2::new(100.0, 50.0));
+        camera.pan(Vec2::new(100.0, 50.0), Vec2::new(120.0, 50.0));
+        let after = camera
+            .view_2d(resolution)
+            .transform_point2(Vec2::new(100.0, 50.0));
+        assert!((before.x - after.x - 5.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn arcball_keeps_distance() {
+        let mut camera = ArcballCamera::default();
+        camera.set_scene_view(&view());
+        camera.rotate(Vec2::new(100.0, 50.0), Vec2::new(130.0, 40.0));
+        assert!((camera.position().length() - camera.distance).abs() < 1e-4);
+
+        let m = camera.calculate_uniform_data();
+        assert!((m.x_axis.truncate().dot(m.z_axis.truncate())).abs() < 1e-5);
+
+        camera.pan(Vec2::new(10.0, 0.0));
+        assert!(camera.target.length() > 0.0);
+    }
+
+    #[test]
+    fn retarget_keeps_position() {
+        let target = Vec3::new(1.0, 0.5, 2.0);
+        let mut cameras: Vec<Box<dyn CameraModel>> = vec![
+            Box::<OrbitCamera>::default(),
+            Box::<FlyCamera>::default(),
+            Box::<ArcballCamera>::default(),
+        ];
+        for camera in &mut cameras {
+            let before = camera.calculate_uniform_data().w_axis.truncate();
+            camera.set_target(target);
+            let m = camera.calculate_uniform_data();
+            let position = m.w_axis.truncate();
+            assert!(position.abs_diff_eq(before, 1e-4), "{:?}", position);
+            let forward = m.z_axis.truncate().normalize();
+            assert!(forward.abs_diff_eq((target - position).normalize(), 1e-4));
+        }
+    }
+
+    #[test]
+    fn default_projection_matches_old_camera() {
+        let projection = Projection::default();
+        assert!((projection.tan_half_fov() - 1.0).abs() < 1e-6);
+        assert_eq!(projection.ortho_uniform(), 0.0);
+        assert_eq!(projection.uniform(2.0).y, 2.0);
+    }
+
+    #[test]
+    fn matrix_layout() {
+        let m = camera_matrix(Vec3::new(1.0, 2.0, 3.0), Vec3::Z);
+        assert_eq!(m.x_axis.truncate(), Vec3::X);
+        assert_eq!(m.y_axis.truncate(), Vec3::Y);
+        assert_eq!(m.z_axis.truncate(), Vec3::Z);
+        assert_eq!(m.w_axis.truncate(), Vec3::new(1.0, 2.0, 3.0));
+    }
+}
@@ -0,0 +1,292 @@
+use glam::{Mat3, Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+/// Where a camera is, where it looks and how wide it sees. Works for every `CameraModel`,
+/// since it's taken from the `sbCameraTransform` they produce.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraPose {
+    pub position: Vec3,
+    pub orientation: Quat,
+    /// Vertical field of view in degrees
+    pub fov: f32,
+}
+
+impl CameraPose {
+    /// Take the pose from a `sbCameraTransform`. The axes are made orthonormal first,
+    /// the orbit camera doesn't keep them that way.
+    pub fn from_transform(transform: Mat4, fov: f32) -> Self {
+        let forward = transform.z_axis.truncate().normalize_or_zero();
+        let side = transform.x_axis.truncate();
+        let side = (side - forward * side.dot(forward)).normalize_or_zero();
+        let up = forward.cross(side);
+        Self {
+            position: transform.w_axis.truncate(),
+            orientation: Quat::from_mat3(&Mat3::from_cols(side, up, forward)).normalize(),
+            fov,
+        }
+    }
+
+    /// The `sbCameraTransform` for this pose
+    pub fn transform(&self) -> Mat4 {
+        Mat4::from_cols(
+            (self.orientation * Vec3::X).extend(0.0),
+            (self.orientation * Vec3::Y).extend(0.0),
+            (self.orientation * Vec3::Z).extend(0.0),
+            self.position.extend(1.0),
+        )
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        self.orientation * Vec3::Z
+    }
+}
+
+/// A pose saved under a number key
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraBookmark {
+    /// Number key, 1 to 9
+    pub slot: u8,
+    pub name: String,
+    pub pose: CameraPose,
+}
+
+/// Save `pose` in `slot`, replacing what was there. Bookmarks are kept sorted by slot.
+pub fn set_bookmark(bookmarks: &mut Vec<CameraBookmark>, slot: u8, pose: CameraPose) {
+    match bookmarks.iter_mut().find(|bookmark| bookmark.slot == slot) {
+        Some(bookmark) => bookmark.pose = pose,
+        None => {
+            bookmarks.push(CameraBookmark {
+                slot,
+                name: format!("View {}", slot),
+                pose,
+            });
+            bookmarks.sort_by_key(|bookmark| bookmark.slot);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraKeyframe {
+    pub time: f32,
+    pub pose: CameraPose,
+}
+
+/// Camera flight along the timeline. Position and FOV follow a Catmull-Rom spline through the
+/// keyframes and the orientation a matching quaternion spline, so the camera passes through
+/// every keyframe without stopping.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraPath {
+    /// Drive the camera from the path instead of the active camera model
+    pub enabled: bool,
+    /// Sorted by time, call `sort` after changing times
+    pub keyframes: Vec<CameraKeyframe>,
+}
+
+/// Keyframes closer together than this are considered the same
+const KEYFRAME_EPSILON: f32 = 1e-3;
+
+impl CameraPath {
+    pub fn is_empty(&self) -> bool {
+        self.keyframes.is_empty()
+    }
+
+    pub fn sort(&mut self) {
+        self.keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+
+    /// Add a keyframe, or replace the one already at `time`. Returns its index.
+    pub fn set_keyframe(&mut self, time: f32, pose: CameraPose) -> usize {
+        let existing = self
+            .keyframes
+            .iter()
+            .position(|keyframe| (keyframe.time - time).abs() < KEYFRAME_EPSILON);
+        if let Some(index) = existing {
+            self.keyframes[index].pose = pose;
+            return index;
+        }
+
+        self.keyframes.push(CameraKeyframe { time, pose });
+        self.sort();
+        self.keyframes
+            .iter()
+            .position(|keyframe| keyframe.time == time)
+            .unwrap_or_default()
+    }
+
+    pub fn remove_keyframe(&mut self, index: usize) {
+        if index < self.keyframes.len() {
+            self.keyframes.remove(index);
+        }
+    }
+
+    /// The pose at `time`. Before the first and after the last keyframe the camera holds still.
+    pub fn sample(&self, time: f32) -> Option<CameraPose> {
+        let keys = &self.keyframes;
+        let first = keys.first()?;
+        let last = keys.last()?;
+        if keys.len() == 1 || time <= first.time {
+            return Some(first.pose);
+        }
+        if time >= last.time {
+            return Some(last.pose);
+        }
+
+        let i = keys.partition_point(|keyframe| keyframe.time <= time) - 1;
+        let (k0, k1) = (&keys[i], &keys[i + 1]);
+        let span = (k1.time - k0.time).max(KEYFRAME_EPSILON);
+        let t = (time - k0.time) / span;
+
+        // neighbours, repeated at the ends
+        let prev = &keys[i.saturating_sub(1)];
+        let next = &keys[(i + 2).min(keys.len() - 1)];
+
+        let tangent = |a: &CameraKeyframe, b: &CameraKeyframe| {
+            let dt = (b.time - a.time).max(KEYFRAME_EPSILON);
+            (
+                (b.pose.position - a.pose.position) / dt * span,
+                (b.pose.fov - a.pose.fov) / dt * span,
+            )
+        };
+        let (m0, fov_m0) = tangent(prev, k1);
+        let (m1, fov_m1) = tangent(k0, next);
+
+        let position = hermite(k0.pose.position, m0, k1.pose.position, m1, t);
+        let fov = hermite(k0.pose.fov, fov_m0, k1.pose.fov, fov_m1, t);
+
+        let q0 = k0.pose.orientation;
+        let q1 = same_hemisphere(q0, k1.pose.orientation);
+        let q_prev = same_hemisphere(q0, prev.pose.orientation);
+        let q_next = same_hemisphere(q1, next.pose.orientation);
+        let s0 = squad_control(q_prev, q0, q1);
+        let s1 = squad_control(q0, q1, q_next);
+        let orientation = squad(q0, q1, s0, s1, t);
+
+        Some(CameraPose {
+            position,
+            orientation,
+            fov,
+        })
+    }
+}
+
+fn hermite<T>(p0: T, m0: T, p1: T, m1: T, t: f32) -> T
+where
+    T: std::ops::Mul<f32, Output = T> + std::ops::Add<Output = T>,
+{
+    let t2 = t * t;
+    let t3 = t2 * t;
+    p0 * (2.0 * t3 - 3.0 * t2 + 1.0)
+        + m0 * (t3 - 2.0 * t2 + t)
+        + p1 * (-2.0 * t3 + 3.0 * t2)
+        + m1 * (t3 - t2)
+}
+
+/// `q` or `-q`, whichever is closer to `reference`. Both are the same rotation.
+fn same_hemisphere(reference: Quat, q: Quat) -> Quat {
+    if reference.dot(q) < 0.0 {
+        -q
+    } else {
+        q
+    }
+}
+
+fn quat_log(q: Quat) -> Vec3 {
+    let v = Vec3::new(q.x, q.y, q.z);
+    let len = v.length();
+    if len < 1e-6 {
+        return v;
+    }
+    v / len * len.atan2(q.w)
+}
+
+fn quat_exp(v: Vec3) -> Quat {
+    let angle = v.length();
+    if angle < 1e-6 {
+        return Quat::from_xyzw(v.x, v.y, v.z, 1.0).normalize();
+    }
+    let s = angle.sin() / angle;
+    Quat::from_xyzw(v.x * s, v.y * s, v.z * s, angle.cos())
+}
+
+/// Inner control point of the quaternion spline at `q`
+fn squad_control(prev: Quat, q: Quat, next: Quat) -> Quat {
+    let inv = q.inverse();
+    let sum = quat_log(inv * next) + quat_log(inv * prev);
+    (q * quat_exp(sum * -0.25)).normalize()
+}
+
+fn squad(q0: Quat, q1: Quat, s0: Quat, s1: Quat, t: f32) -> Quat {
+    let outer = q0.slerp(q1, t);
+    let inner = s0.slerp(s1, t);
+    outer.slerp(inner, 2.0 * t * (1.0 - t)).normalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{set_bookmark, CameraPath, CameraPose};
+    use glam::{Mat4, Quat, Vec3};
+
+    fn pose(x: f32, yaw: f32, fov: f32) -> CameraPose {
+        CameraPose {
+            position: Vec3::new(x, 0.0, 0.0),
+            orientation: Quat::from_rotation_y(yaw),
+            fov,
+        }
+    }
+
+    #[test]
+    fn pose_round_trip() {
+        let original = pose(1.0, 0.5, 60.0);
+        let restored = CameraPose::from_transform(original.transform(), 60.0);
+        assert!(restored.position.abs_diff_eq(original.position, 1e-5));
+        assert!(restored.orientation.abs_diff_eq(original.orientation, 1e-5));
+
+        let identity = CameraPose::from_transform(Mat4::IDENTITY, 90.0);
+        assert!(identity.forward().abs_diff_eq(Vec3::Z, 1e-6));
+    }
+
+    #[test]
+    fn path_passes_through_keyframes() {
+        let mut path = CameraPath::default();
+        path.set_keyframe(2.0, pose(4.0, 1.0, 40.0));
+        path.set_keyframe(0.0, pose(0.0, 0.0, 90.0));
+        path.set_keyframe(1.0, pose(1.0, 0.5, 60.0));
+        assert_eq!(path.keyframes[1].time, 1.0);
+
+        for keyframe in &path.keyframes {
+            let sampled = path.sample(keyframe.time).unwrap();
+            assert!(sampled.position.abs_diff_eq(keyframe.pose.position, 1e-5));
+            assert!(sampled
+                .orientation
+                .abs_diff_eq(keyframe.pose.orientation, 1e-4));
+            assert!((sampled.fov - keyframe.pose.fov).abs() < 1e-4);
+        }
+
+        // held outside of the keyframes
+        assert_eq!(path.sample(-1.0).unwrap(), path.keyframes[0].pose);
+        assert_eq!(path.sample(5.0).unwrap(), path.keyframes[2].pose);
+
+        // smooth in between
+        let mid = path.sample(0.5).unwrap();
+        assert!(mid.position.x > 0.0 && mid.position.x < 1.0);
+        assert!(mid.fov < 90.0 && mid.fov > 60.0);
+    }
+
+    #[test]
+    fn keyframes_at_the_same_time_are_replaced() {
+        let mut path = CameraPath::default();
+        path.set_keyframe(1.0, pose(0.0, 0.0, 90.0));
+        path.set_keyframe(1.0, pose(2.0, 0.0, 90.0));
+        assert_eq!(path.keyframes.len(), 1);
+        assert_eq!(path.keyframes[0].pose.position.x, 2.0);
+
+        let mut bookmarks = vec![];
+        set_bookmark(&mut bookmarks, 3, pose(0.0, 0.0, 90.0));
+        set_bookmark(&mut bookmarks, 1, pose(0.0, 0.0, 90.0));
+        set_bookmark(&mut bookmarks, 3, pose(1.0, 0.0, 90.0));
+        assert_eq!(bookmarks.len(), 2);
+        assert_eq!(bookmarks[0].slot, 1);
+        assert_eq!(bookmarks[1].pose.position.x, 1.0);
+    }
+}
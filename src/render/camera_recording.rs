@@ -0,0 +1,242 @@
+use glam::Mat4;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{CameraPath, CameraPose};
+
+/// What happens with the camera recording every frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RecordingMode {
+    #[default]
+    Off,
+    /// Capture the interactive camera
+    Recording,
+    /// Drive the camera from the recording
+    Replaying,
+}
+
+/// `sbCameraTransform` and FOV at a point in playback time
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraSample {
+    pub time: f32,
+    pub transform: Mat4,
+    pub fov: f32,
+}
+
+/// Camera transforms captured frame by frame while playing. Replaying gives back exactly the
+/// recorded transforms at the recorded times and blends between the two closest samples at
+/// any other time, so it only depends on `playback_time` and renders the same every time.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraRecording {
+    /// Sorted by time
+    pub samples: Vec<CameraSample>,
+}
+
+impl CameraRecording {
+    /// Where `export` puts the recording for a shader
+    pub fn path(shader_path: &Path) -> PathBuf {
+        shader_path.with_extension("camera.toml")
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        fs::write(path, toml::to_string(self)?)?;
+        log::info!("Camera: Saved recording to {:?}", path);
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// First and last recorded time
+    pub fn range(&self) -> Option<(f32, f32)> {
+        Some((self.samples.first()?.time, self.samples.last()?.time))
+    }
+
+    /// Drop everything from `time` on, so recording can pick up from there
+    pub fn truncate(&mut self, time: f32) {
+        let keep = self.samples.partition_point(|sample| sample.time < time);
+        self.samples.truncate(keep);
+    }
+
+    /// Add a sample at the end. A sample at the same time as the last one replaces it.
+    /// Returns `false`, without recording anything, if `time` lies before the last sample,
+    /// e.g. when the loop wrapped around.
+    pub fn push(&mut self, time: f32, transform: Mat4, fov: f32) -> bool {
+        let sample = CameraSample {
+            time,
+            transform,
+            fov,
+        };
+        match self.samples.last_mut() {
+            Some(last) if time < last.time => return false,
+            Some(last) if time == last.time => *last = sample,
+            _ => self.samples.push(sample),
+        }
+        true
+    }
+
+    /// The camera at `time`. Outside of the recording the first or last sample is held.
+    pub fn sample(&self, time: f32) -> Option<CameraSample> {
+        let first = self.samples.first()?;
+        let last = self.samples.last()?;
+        if time <= first.time {
+            return Some(*first);
+        }
+        if time >= last.time {
+            return Some(*last);
+        }
+
+        let next = self.samples.partition_point(|sample| sample.time <= time);
+        let (a, b) = (&self.samples[next - 1], &self.samples[next]);
+        if time == a.time {
+            return Some(*a);
+        }
+        let t = (time - a.time) / (b.time - a.time);
+        let blend = |x: glam::Vec4, y: glam::Vec4| x.lerp(y, t);
+        Some(CameraSample {
+            time,
+            transform: Mat4::from_cols(
+                blend(a.transform.x_axis, b.transform.x_axis),
+                blend(a.transform.y_axis, b.transform.y_axis),
+                blend(a.transform.z_axis, b.transform.z_axis),
+                blend(a.transform.w_axis, b.transform.w_axis),
+            ),
+            fov: a.fov + (b.fov - a.fov) * t,
+        })
+    }
+
+    /// Average every sample with its neighbours less than `window / 2` seconds away.
+    /// Takes the shake out of hand held moves, the timing stays the same.
+    pub fn smoothed(&self, window: f32) -> Self {
+        if window <= 0.0 {
+            return self.clone();
+        }
+
+        let half = window / 2.0;
+        let samples = self
+            .samples
+            .iter()
+            .map(|sample| {
+                let start = self
+                    .samples
+                    .partition_point(|other| other.time < sample.time - half);
+                let end = self
+                    .samples
+                    .partition_point(|other| other.time <= sample.time + half);
+                let neighbours = &self.samples[start..end];
+                let weight = 1.0 / neighbours.len() as f32;
+
+                let mut transform = Mat4::ZERO;
+                let mut fov = 0.0;
+                for other in neighbours {
+                    transform += other.transform * weight;
+                    fov += other.fov * weight;
+                }
+                // averaged axes are no longer orthonormal
+                let transform = CameraPose::from_transform(transform, fov).transform();
+                CameraSample {
+                    time: sample.time,
+                    transform,
+                    fov,
+                }
+            })
+            .collect();
+        Self { samples }
+    }
+
+    /// Resample into camera path keyframes every `interval` seconds, ends included
+    pub fn to_path(&self, interval: f32) -> CameraPath {
+        let mut path = CameraPath {
+            enabled: true,
+            ..Default::default()
+        };
+        let Some((start, end)) = self.range() else {
+            return path;
+        };
+
+        let interval = interval.max(0.01);
+        let steps = ((end - start) / interval).ceil() as usize;
+        for step in 0..=steps {
+            let time = (start + step as f32 * interval).min(end);
+            if let Some(sample) = self.sample(time) {
+                let pose = CameraPose::from_transform(sample.transform, sample.fov);
+                path.set_keyframe(time, pose);
+            }
+        }
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CameraRecording;
+    use glam::{Mat4, Vec3};
+
+    fn at(x: f32) -> Mat4 {
+        Mat4::from_translation(Vec3::new(x, 0.0, 0.0))
+    }
+
+    fn recording() -> CameraRecording {
+        let mut recording = CameraRecording::default();
+        for frame in 0..=10 {
+            let time = frame as f32 * 0.1;
+            assert!(recording.push(time, at(time), 90.0));
+        }
+        recording
+    }
+
+    #[test]
+    fn replays_recorded_samples() {
+        let recording = recording();
+        let sample = recording.sample(0.3).unwrap();
+        assert_eq!(sample.transform, recording.samples[3].transform);
+
+        let between = recording.sample(0.35).unwrap();
+        assert!((between.transform.w_axis.x - 0.35).abs() < 1e-5);
+        assert_eq!(recording.sample(5.0).unwrap().transform, at(1.0));
+    }
+
+    #[test]
+    fn jumping_back_is_refused() {
+        let mut recording = recording();
+        assert!(!recording.push(0.5, at(9.0), 90.0));
+        assert_eq!(recording.samples.len(), 11);
+
+        recording.truncate(0.5);
+        assert_eq!(recording.samples.len(), 5);
+        assert!(recording.push(0.5, at(9.0), 90.0));
+    }
+
+    #[test]
+    fn smoothing_keeps_straight_lines() {
+        let recording = recording();
+        let smoothed = recording.smoothed(0.3);
+        assert_eq!(smoothed.samples.len(), recording.samples.len());
+        let middle = smoothed.sample(0.5).unwrap();
+        assert!((middle.transform.w_axis.x - 0.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn resample_to_keyframes() {
+        let path = recording().to_path(0.25);
+        let times: Vec<f32> = path.keyframes.iter().map(|key| key.time).collect();
+        assert_eq!(times, vec![0.0, 0.25, 0.5, 0.75, 1.0]);
+        assert!(path.enabled);
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let recording = recording();
+        let content = toml::to_string(&recording).unwrap();
+        let loaded: CameraRecording = toml::from_str(&content).unwrap();
+        assert_eq!(loaded, recording);
+    }
+}
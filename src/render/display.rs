@@ -0,0 +1,283 @@
+use glow::{HasContext, Program, Texture, UniformLocation};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::{ShaderProgram, UpscaleFilter};
+
+/// Curve used to bring scene values above 1.0 into display range
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Tonemapper {
+    /// Clip everything above 1.0, what you got before HDR targets
+    #[default]
+    Clamp,
+    Reinhard,
+    /// Krzysztof Narkowicz' fit of the ACES filmic curve
+    Aces,
+    /// Minimal AgX with the default look
+    AgX,
+}
+
+impl Tonemapper {
+    pub const ALL: [Tonemapper; 4] = [
+        Tonemapper::Clamp,
+        Tonemapper::Reinhard,
+        Tonemapper::Aces,
+        Tonemapper::AgX,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tonemapper::Clamp => "clamp",
+            Tonemapper::Reinhard => "Reinhard",
+            Tonemapper::Aces => "ACES",
+            Tonemapper::AgX => "AgX",
+        }
+    }
+}
+
+/// How linear values are encoded for the window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Encoding {
+    /// Values are written as they are. Most shaders do their own gamma correction.
+    #[default]
+    None,
+    Srgb,
+    /// Plain power curve with `DisplayTransform::gamma`
+    Gamma,
+}
+
+/// Everything that happens to the scene between the HDR target and the window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DisplayTransform {
+    /// In stops, the scene is multiplied by `2^exposure`
+    pub exposure: f32,
+    pub tonemapper: Tonemapper,
+    pub encoding: Encoding,
+    pub gamma: f32,
+    /// Add a little noise to hide banding in 8 bit output
+    pub dither: bool,
+}
+
+impl Default for DisplayTransform {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            tonemapper: Tonemapper::default(),
+            encoding: Encoding::default(),
+            gamma: 2.2,
+            dither: false,
+        }
+    }
+}
+
+impl DisplayTransform {
+    pub fn exposure_scale(&self) -> f32 {
+        self.exposure.exp2()
+    }
+}
+
+const DISPLAY_SHADER: &str = r#"#version 330 core
+uniform sampler2D sbScene;
+uniform vec4 sbViewport;
+uniform float sbExposure;
+uniform int sbTonemapper;
+uniform int sbEncoding;
+uniform float sbGamma;
+uniform int sbDither;
+uniform int sbFrame;
+out vec4 color;
+
+vec3 reinhard(vec3 c) {
+    return c / (1.0 + c);
+}
+
+vec3 aces(vec3 x) {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+vec3 agx(vec3 c) {
+    const mat3 agxIn = mat3(
+        0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772, 0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104);
+    const mat3 agxOut = mat3(
+        1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433, 1.15107367264116);
+    const float minEv = -12.47393;
+    const float maxEv = 4.026069;
+
+    c = agxIn * max(c, 0.0);
+    c = clamp(log2(max(c, 1e-10)), minEv, maxEv);
+    c = (c - minEv) / (maxEv - minEv);
+    vec3 x2 = c * c;
+    vec3 x4 = x2 * x2;
+    c = 15.5 * x4 * x2 - 40.14 * x4 * c + 31.96 * x4 - 6.868 * x2 * c + 0.4298 * x2 + 0.1191 * c - 0.00232;
+    c = agxOut * c;
+    // the curve ends up in display space, go back to linear so the encoding applies as usual
+    return pow(clamp(c, 0.0, 1.0), vec3(2.2));
+}
+
+vec3 toSrgb(vec3 c) {
+    return mix(12.92 * c, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, c));
+}
+
+float hash(vec3 p) {
+    p = fract(p * 0.1031);
+    p += dot(p, p.zyx + 31.32);
+    return fract((p.x + p.y) * p.z);
+}
+
+void main() {
+    vec2 uv = (gl_FragCoord.xy - sbViewport.xy) / sbViewport.zw;
+    vec4 scene = texture(sbScene, uv);
+    vec3 c = scene.rgb * sbExposure;
+
+    if (sbTonemapper == 1) c = reinhard(max(c, 0.0));
+    else if (sbTonemapper == 2) c = aces(max(c, 0.0));
+    else if (sbTonemapper == 3) c = agx(c);
+    c = clamp(c, 0.0, 1.0);
+
+    if (sbEncoding == 1) c = toSrgb(c);
+    else if (sbEncoding == 2) c = pow(c, vec3(1.0 / sbGamma));
+
+    if (sbDither == 1) {
+        // triangular noise of +-1 LSB
+        vec3 p = vec3(gl_FragCoord.xy, float(sbFrame % 64));
+        float noise = hash(p) + hash(p + 17.0) - 1.0;
+        c += noise / 255.0;
+    }
+
+    color = vec4(c, 1.0);
+}"#;
+
+#[derive(Debug)]
+struct DisplayLocations {
+    scene: Option<UniformLocation>,
+    viewport: Option<UniformLocation>,
+    exposure: Option<UniformLocation>,
+    tonemapper: Option<UniformLocation>,
+    encoding: Option<UniformLocation>,
+    gamma: Option<UniformLocation>,
+    dither: Option<UniformLocation>,
+    frame: Option<UniformLocation>,
+}
+
+/// Full screen pass that applies a `DisplayTransform` to a scene texture
+pub struct DisplayPass {
+    gl: Arc<glow::Context>,
+    program: Program,
+    locations: DisplayLocations,
+}
+
+impl DisplayPass {
+    pub fn new(gl: Arc<glow::Context>) -> Result<Self, String> {
+        let program = ShaderProgram::from_frag_src(&gl, DISPLAY_SHADER.to_string())?;
+        let locations = unsafe {
+            DisplayLocations {
+                scene: gl.get_uniform_location(program, "sbScene"),
+                viewport: gl.get_uniform_location(program, "sbViewport"),
+                exposure: gl.get_uniform_location(program, "sbExposure"),
+                tonemapper: gl.get_uniform_location(program, "sbTonemapper"),
+                encoding: gl.get_uniform_location(program, "sbEncoding"),
+                gamma: gl.get_uniform_location(program, "sbGamma"),
+                dither: gl.get_uniform_location(program, "sbDither"),
+                frame: gl.get_uniform_location(program, "sbFrame"),
+            }
+        };
+        Ok(Self {
+            gl,
+            program,
+            locations,
+        })
+    }
+
+    /// Draw `texture` into `viewport` of the bound framebuffer.
+    /// Expects a vertex array for the full screen triangle to be bound.
+    pub fn draw(
+        &self,
+        texture: Texture,
+        filter: UpscaleFilter,
+        viewport: [i32; 4],
+        transform: &DisplayTransform,
+        frame: u32,
+    ) {
+        let gl = &self.gl;
+        let locations = &self.locations;
+        let [x, y, width, height] = viewport;
+        let filter = match filter {
+            UpscaleFilter::Nearest => glow::NEAREST,
+            UpscaleFilter::Linear => glow::LINEAR,
+        };
+        let tonemapper = Tonemapper::ALL
+            .iter()
+            .position(|tonemapper| *tonemapper == transform.tonemapper)
+            .unwrap_or_default();
+        let encoding = match transform.encoding {
+            Encoding::None => 0,
+            Encoding::Srgb => 1,
+            Encoding::Gamma => 2,
+        };
+
+        unsafe {
+            gl.viewport(x, y, width, height);
+            gl.use_program(Some(self.program));
+
+            gl.active_texture(glow::TEXTURE0);
+            gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, filter as i32);
+            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, filter as i32);
+
+            gl.uniform_1_i32(locations.scene.as_ref(), 0);
+            gl.uniform_4_f32(
+                locations.viewport.as_ref(),
+                x as f32,
+                y as f32,
+                width as f32,
+                height as f32,
+            );
+            gl.uniform_1_f32(locations.exposure.as_ref(), transform.exposure_scale());
+            gl.uniform_1_i32(locations.tonemapper.as_ref(), tonemapper as i32);
+            gl.uniform_1_i32(locations.encoding.as_ref(), encoding);
+            gl.uniform_1_f32(locations.gamma.as_ref(), transform.gamma.max(0.1));
+            gl.uniform_1_i32(locations.dither.as_ref(), i32::from(transform.dither));
+            gl.uniform_1_i32(locations.frame.as_ref(), frame as i32);
+
+            gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 3);
+            gl.bind_texture(glow::TEXTURE_2D, None);
+            macros::check_for_gl_error!(gl, "display_pass");
+        }
+    }
+}
+
+impl Drop for DisplayPass {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.delete_program(self.program);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DisplayTransform, Encoding, Tonemapper};
+
+    #[test]
+    fn default_is_pass_through() {
+        let transform = DisplayTransform::default();
+        assert_eq!(transform.exposure_scale(), 1.0);
+        assert_eq!(transform.tonemapper, Tonemapper::Clamp);
+        assert_eq!(transform.encoding, Encoding::None);
+        assert!(!transform.dither);
+    }
+
+    #[test]
+    fn exposure_in_stops() {
+        let transform = DisplayTransform {
+            exposure: -2.0,
+            ..Default::default()
+        };
+        assert_eq!(transform.exposure_scale(), 0.25);
+    }
+}
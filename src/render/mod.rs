@@ -1,3 +1,14 @@
 pub mod camera;
+pub mod camera_path;
+pub mod camera_recording;
+pub mod display;
+pub mod overlay;
+pub mod poster;
+pub mod probe;
+pub mod profiler;
+pub mod render_target;
 pub mod renderer;
+pub mod scaling;
+pub mod texture;
 pub mod uniforms;
+pub mod video;
@@ -0,0 +1,307 @@
+use glam::{Mat3, Mat4, Vec2, Vec3, Vec4};
+use glow::{Buffer, HasContext, Program, UniformLocation, VertexArray};
+use std::sync::Arc;
+
+use crate::camera::Projection;
+use crate::shader::program::{compile_shader, link_program};
+
+pub const OVERLAY_RED: [f32; 4] = [0.9, 0.25, 0.25, 1.0];
+pub const OVERLAY_GREEN: [f32; 4] = [0.3, 0.85, 0.3, 1.0];
+pub const OVERLAY_BLUE: [f32; 4] = [0.3, 0.45, 1.0, 1.0];
+pub const OVERLAY_YELLOW: [f32; 4] = [1.0, 0.85, 0.2, 1.0];
+pub const OVERLAY_WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const GRID_MINOR: [f32; 4] = [0.6, 0.6, 0.6, 0.25];
+const GRID_MAJOR: [f32; 4] = [0.75, 0.75, 0.75, 0.5];
+
+/// The camera as the overlay sees it. Projects the same way `skuggbox_camera` casts rays,
+/// assuming the usual `uv = (2.0 * fragCoord - iResolution.xy) / iResolution.y`.
+#[derive(Debug, Clone)]
+pub struct OverlayCamera {
+    /// `sbCameraTransform`
+    pub transform: Mat4,
+    pub projection: Projection,
+    /// Width over height of the scene
+    pub aspect: f32,
+}
+
+impl OverlayCamera {
+    /// World to clip space, ready for the vertex shader
+    pub fn view_projection(&self) -> Mat4 {
+        let projection = &self.projection;
+        let near = projection.near.max(0.01);
+        let far = projection.far.max(near + 0.01);
+        let depth = far - near;
+
+        let clip = if projection.orthographic {
+            let scale = projection.ortho_uniform();
+            Mat4::from_cols(
+                Vec4::new(1.0 / (scale * self.aspect), 0.0, 0.0, 0.0),
+                Vec4::new(0.0, 1.0 / scale, 0.0, 0.0),
+                Vec4::new(0.0, 0.0, 2.0 / depth, 0.0),
+                Vec4::new(0.0, 0.0, -(far + near) / depth, 1.0),
+            )
+        } else {
+            // the ray for uv goes through camera space (uv * tan(fov / 2), 1)
+            let t = projection.tan_half_fov();
+            Mat4::from_cols(
+                Vec4::new(1.0 / self.aspect, 0.0, 0.0, 0.0),
+                Vec4::new(0.0, 1.0, 0.0, 0.0),
+                Vec4::new(0.0, 0.0, t * (far + near) / depth, t),
+                Vec4::new(0.0, 0.0, -t * 2.0 * far * near / depth, 0.0),
+            )
+        };
+        clip * self.transform.inverse()
+    }
+
+    /// Normalized device coordinates of a world position, `None` if it's behind the camera
+    pub fn project(&self, position: Vec3) -> Option<Vec2> {
+        let clip = self.view_projection() * position.extend(1.0);
+        if clip.w <= 1e-6 {
+            return None;
+        }
+        Some(clip.truncate().truncate() / clip.w)
+    }
+
+    /// Origin and direction of the ray through normalized device coordinates `ndc`
+    pub fn ray(&self, ndc: Vec2) -> (Vec3, Vec3) {
+        let basis = Mat3::from_mat4(self.transform);
+        let position = self.transform.w_axis.truncate();
+        let uv = ndc * Vec2::new(self.aspect, 1.0);
+        let projection = &self.projection;
+        if projection.orthographic {
+            let offset = basis * (uv * projection.ortho_uniform()).extend(0.0);
+            (position + offset, basis.z_axis.normalize())
+        } else {
+            let direction = (basis * (uv * projection.tan_half_fov()).extend(1.0)).normalize();
+            (position, direction)
+        }
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        self.transform.z_axis.truncate().normalize_or_zero()
+    }
+
+    /// Where the ray through `ndc` crosses the plane through `point` facing the camera.
+    /// Used to drag points around without changing their depth.
+    pub fn drag_in_view_plane(&self, point: Vec3, ndc: Vec2) -> Option<Vec3> {
+        let (origin, direction) = self.ray(ndc);
+        let normal = self.forward();
+        let denom = direction.dot(normal);
+        if denom.abs() < 1e-6 {
+            return None;
+        }
+        let distance = (point - origin).dot(normal) / denom;
+        (distance > 0.0).then(|| origin + direction * distance)
+    }
+}
+
+/// Colored line segments in world space, two vertices per line
+#[derive(Debug, Clone, Default)]
+pub struct OverlayLines {
+    /// x, y, z, r, g, b, a per vertex
+    vertices: Vec<f32>,
+}
+
+impl OverlayLines {
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.vertices.len() / 14
+    }
+
+    pub fn line(&mut self, from: Vec3, to: Vec3, color: [f32; 4]) {
+        for point in [from, to] {
+            self.vertices.extend_from_slice(&point.to_array());
+            self.vertices.extend_from_slice(&color);
+        }
+    }
+
+    /// Grid on the ground plane, y = 0, from `-extent` to `extent` with a line every unit
+    /// and a brighter one every 5 units
+    pub fn grid(&mut self, extent: i32) {
+        let size = extent as f32;
+        for i in -extent..=extent {
+            let color = if i % 5 == 0 { GRID_MAJOR } else { GRID_MINOR };
+            let offset = i as f32;
+            self.line(
+                Vec3::new(offset, 0.0, -size),
+                Vec3::new(offset, 0.0, size),
+                color,
+            );
+            self.line(
+                Vec3::new(-size, 0.0, offset),
+                Vec3::new(size, 0.0, offset),
+                color,
+            );
+        }
+    }
+
+    /// The x, y and z axes from the origin in red, green and blue
+    pub fn axes(&mut self, length: f32) {
+        self.line(Vec3::ZERO, Vec3::X * length, OVERLAY_RED);
+        self.line(Vec3::ZERO, Vec3::Y * length, OVERLAY_GREEN);
+        self.line(Vec3::ZERO, Vec3::Z * length, OVERLAY_BLUE);
+    }
+
+    /// Three lines crossing at `center`
+    pub fn cross(&mut self, center: Vec3, size: f32, color: [f32; 4]) {
+        for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+            self.line(center - axis * size, center + axis * size, color);
+        }
+    }
+}
+
+const OVERLAY_VERTEX_SHADER: &str = r#"#version 330 core
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec4 color;
+uniform mat4 sbViewProjection;
+out vec4 vColor;
+void main() {
+    vColor = color;
+    gl_Position = sbViewProjection * vec4(position, 1.0);
+}"#;
+
+const OVERLAY_FRAGMENT_SHADER: &str = r#"#version 330 core
+in vec4 vColor;
+out vec4 color;
+void main() {
+    color = vColor;
+}"#;
+
+/// Draws `OverlayLines` on top of the scene, after the display transform
+pub struct OverlayPass {
+    gl: Arc<glow::Context>,
+    program: Program,
+    view_projection: Option<UniformLocation>,
+    vertex_array: VertexArray,
+    buffer: Buffer,
+}
+
+impl OverlayPass {
+    pub fn new(gl: Arc<glow::Context>) -> Result<Self, String> {
+        unsafe {
+            let vert = compile_shader(&gl, glow::VERTEX_SHADER, OVERLAY_VERTEX_SHADER)?;
+            let frag = compile_shader(&gl, glow::FRAGMENT_SHADER, OVERLAY_FRAGMENT_SHADER)?;
+            let program = link_program(&gl, &[vert, frag])?;
+            gl.detach_shader(program, vert);
+            gl.detach_shader(program, frag);
+            gl.delete_shader(vert);
+            gl.delete_shader(frag);
+            let view_projection = gl.get_uniform_location(program, "sbViewProjection");
+
+            let vertex_array = gl.create_vertex_array()?;
+            let buffer = gl.create_buffer()?;
+            gl.bind_vertex_array(Some(vertex_array));
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(buffer));
+            let stride = 7 * std::mem::size_of::<f32>() as i32;
+            gl.enable_vertex_attrib_array(0);
+            gl.vertex_attrib_pointer_f32(0, 3, glow::FLOAT, false, stride, 0);
+            gl.enable_vertex_attrib_array(1);
+            gl.vertex_attrib_pointer_f32(1, 4, glow::FLOAT, false, stride, 3 * 4);
+            gl.bind_vertex_array(None);
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+            macros::check_for_gl_error!(&gl, "overlay_pass_new");
+
+            Ok(Self {
+                gl,
+                program,
+                view_projection,
+                vertex_array,
+                buffer,
+            })
+        }
+    }
+
+    /// Draw `lines` into `viewport` of the bound framebuffer
+    pub fn draw(&self, lines: &OverlayLines, camera: &OverlayCamera, viewport: [i32; 4]) {
+        if lines.is_empty() {
+            return;
+        }
+        let gl = &self.gl;
+        let [x, y, width, height] = viewport;
+        let view_projection = camera.view_projection().to_cols_array();
+        unsafe {
+            gl.viewport(x, y, width, height);
+            gl.enable(glow::BLEND);
+            gl.blend_func(glow::SRC_ALPHA, glow::ONE_MINUS_SRC_ALPHA);
+            gl.use_program(Some(self.program));
+            gl.uniform_matrix_4_f32_slice(self.view_projection.as_ref(), false, &view_projection);
+
+            gl.bind_vertex_array(Some(self.vertex_array));
+            gl.bind_buffer(glow::ARRAY_BUFFER, Some(self.buffer));
+            gl.buffer_data_u8_slice(
+                glow::ARRAY_BUFFER,
+                bytemuck::cast_slice(&lines.vertices),
+                glow::STREAM_DRAW,
+            );
+            gl.draw_arrays(glow::LINES, 0, lines.line_count() as i32 * 2);
+
+            gl.bind_buffer(glow::ARRAY_BUFFER, None);
+            gl.bind_vertex_array(None);
+            gl.disable(glow::BLEND);
+            macros::check_for_gl_error!(gl, "overlay_pass");
+        }
+    }
+}
+
+impl Drop for OverlayPass {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.delete_program(self.program);
+            self.gl.delete_buffer(self.buffer);
+            self.gl.delete_vertex_array(self.vertex_array);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OverlayCamera, OverlayLines};
+    use crate::camera::Projection;
+    use glam::{Mat4, Vec2, Vec3};
+
+    fn camera(projection: Projection) -> OverlayCamera {
+        OverlayCamera {
+            transform: Mat4::from_translation(Vec3::new(0.0, 1.0, -5.0)),
+            projection,
+            aspect: 2.0,
+        }
+    }
+
+    #[test]
+    fn projection_matches_camera_rays() {
+        for orthographic in [false, true] {
+            let camera = camera(Projection {
+                fov: 60.0,
+                orthographic,
+                ..Default::default()
+            });
+            let ndc = Vec2::new(0.25, -0.5);
+            let (origin, direction) = camera.ray(ndc);
+            let projected = camera.project(origin + direction * 3.0).unwrap();
+            assert!(projected.abs_diff_eq(ndc, 1e-5), "{:?}", projected);
+        }
+    }
+
+    #[test]
+    fn dragging_keeps_depth() {
+        let camera = camera(Projection::default());
+        let point = Vec3::new(0.0, 1.0, 0.0);
+        let moved = camera
+            .drag_in_view_plane(point, Vec2::new(0.5, 0.0))
+            .unwrap();
+        assert!((moved.z - point.z).abs() < 1e-5);
+        assert!(moved.x > 0.0);
+        assert!(camera.project(point - Vec3::Z * 10.0).is_none());
+    }
+
+    #[test]
+    fn lines_have_two_vertices() {
+        let mut lines = OverlayLines::default();
+        lines.axes(1.0);
+        lines.grid(2);
+        assert_eq!(lines.line_count(), 3 + 2 * 5);
+    }
+}
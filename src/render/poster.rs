@@ -0,0 +1,249 @@
+use glam::Vec2;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::{AppState, RenderTarget, Renderer, ShaderService};
+
+/// Settings for a high resolution still that is rendered in tiles and stitched on the CPU.
+/// Lets us go way beyond the max framebuffer size of the GPU.
+#[derive(Debug, Clone)]
+pub struct PosterConfig {
+    pub width: u32,
+    pub height: u32,
+    /// Size of a single tile in output pixels, before supersampling
+    pub tile_size: u32,
+    /// Each tile is rendered `supersample` times larger in each direction and box filtered down
+    pub supersample: u32,
+}
+
+impl Default for PosterConfig {
+    fn default() -> Self {
+        Self {
+            width: 7680,
+            height: 4320,
+            tile_size: 1024,
+            supersample: 1,
+        }
+    }
+}
+
+/// A rectangular piece of the poster in output pixels.
+/// The origin is in the lower left corner, same as OpenGL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Split an image into tiles, row by row starting from the bottom.
+/// Tiles along the right and top edges are cropped to fit the image.
+pub fn tiles(width: u32, height: u32, tile_size: u32) -> Vec<Tile> {
+    let tile_size = tile_size.max(1);
+    let mut tiles = vec![];
+    for y in (0..height).step_by(tile_size as usize) {
+        for x in (0..width).step_by(tile_size as usize) {
+            tiles.push(Tile {
+                x,
+                y,
+                width: tile_size.min(width - x),
+                height: tile_size.min(height - y),
+            });
+        }
+    }
+    tiles
+}
+
+/// Box filter RGBA8 pixels down by `factor` in each direction
+pub fn downsample(pixels: &[u8], width: u32, height: u32, factor: u32) -> Vec<u8> {
+    if factor <= 1 {
+        return pixels.to_vec();
+    }
+
+    let (out_width, out_height) = (width / factor, height / factor);
+    let samples = factor * factor;
+    let mut out = vec![0u8; (out_width * out_height * 4) as usize];
+
+    for y in 0..out_height {
+        for x in 0..out_width {
+            let mut sum = [0u32; 4];
+            for sy in 0..factor {
+                for sx in 0..factor {
+                    let src = (((y * factor + sy) * width + x * factor + sx) * 4) as usize;
+                    for (c, s) in sum.iter_mut().enumerate() {
+                        *s += pixels[src + c] as u32;
+                    }
+                }
+            }
+            let dst = ((y * out_width + x) * 4) as usize;
+            for (c, s) in sum.iter().enumerate() {
+                out[dst + c] = ((s + samples / 2) / samples) as u8;
+            }
+        }
+    }
+    out
+}
+
+/// Copy the pixels of a rendered tile into its place in the full image.
+/// Both buffers are RGBA8 with the bottom row first.
+pub fn stitch(image: &mut [u8], image_width: u32, tile: &Tile, pixels: &[u8]) {
+    let row_len = (tile.width * 4) as usize;
+    for row in 0..tile.height {
+        let src = (row * tile.width * 4) as usize;
+        let dst = (((tile.y + row) * image_width + tile.x) * 4) as usize;
+        image[dst..dst + row_len].copy_from_slice(&pixels[src..src + row_len]);
+    }
+}
+
+/// Reverse the row order of an RGBA8 image, OpenGL gives us the bottom row first
+pub fn flip_rows(pixels: &mut [u8], width: u32, height: u32) {
+    let row_len = (width * 4) as usize;
+    for row in 0..(height / 2) as usize {
+        let (top, bottom) = pixels.split_at_mut((height as usize - row - 1) * row_len);
+        top[row * row_len..(row + 1) * row_len].swap_with_slice(&mut bottom[..row_len]);
+    }
+}
+
+pub fn save_png(path: &Path, width: u32, height: u32, pixels: &[u8]) -> anyhow::Result<()> {
+    let file = File::create(path)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    Ok(())
+}
+
+/// Render the output shader as a poster, tile by tile, and return the stitched RGBA8 image
+/// with the top row first.
+///
+/// Each tile gets `iResolution` set to the full (supersampled) poster size and `sbTileOffset`
+/// set to where the tile starts, so shaders using `gl_FragCoord` render their part of the
+/// poster without any changes. Buffer passes of a project are rendered once, at the poster size
+/// or as close to it as the GPU allows.
+pub fn render_poster(
+    renderer: &mut Renderer,
+    state: &mut AppState,
+    shader_service: &ShaderService,
+    config: &PosterConfig,
+) -> Result<Vec<u8>, String> {
+    shader_service
+        .output_shader()
+        .filter(|shader| shader.program.is_some())
+        .ok_or("No compiled shader to render")?;
+
+    let supersample = config.supersample.max(1);
+    let max_size = renderer.max_render_size() as u32;
+    let tile_size = config.tile_size.min(max_size / supersample).max(1);
+    let resolution = Vec2::new(
+        (config.width * supersample) as f32,
+        (config.height * supersample) as f32,
+    );
+
+    renderer.draw_buffers(
+        state,
+        shader_service,
+        (resolution.x as i32).min(max_size as i32),
+        (resolution.y as i32).min(max_size as i32),
+    );
+
+    let mut image = vec![0u8; (config.width * config.height * 4) as usize];
+    let tiles = tiles(config.width, config.height, tile_size);
+    log::info!(
+        "Poster: rendering {}x{} in {} tiles",
+        config.width,
+        config.height,
+        tiles.len()
+    );
+
+    for tile in &tiles {
+        let (width, height) = (
+            (tile.width * supersample) as i32,
+            (tile.height * supersample) as i32,
+        );
+        let scene = RenderTarget::with_format(renderer.gl(), width, height, state.scene_format)?;
+        let output = RenderTarget::new(renderer.gl(), width, height)?;
+        scene.bind();
+
+        let tile_offset = Vec2::new((tile.x * supersample) as f32, (tile.y * supersample) as f32);
+        let pass = shader_service.output_index();
+        renderer.draw_pass(shader_service, pass, state, resolution, tile_offset);
+        renderer.resolve(&scene, &output, state);
+
+        let pixels = downsample(
+            &output.read_rgba8(),
+            tile.width * supersample,
+            tile.height * supersample,
+            supersample,
+        );
+        stitch(&mut image, config.width, tile, &pixels);
+    }
+
+    flip_rows(&mut image, config.width, config.height);
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{downsample, flip_rows, stitch, tiles, Tile};
+
+    #[test]
+    fn tiles_cover_image() {
+        let tiles = tiles(250, 120, 100);
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(
+            tiles[2],
+            Tile {
+                x: 200,
+                y: 0,
+                width: 50,
+                height: 100
+            }
+        );
+        assert_eq!(
+            tiles[5],
+            Tile {
+                x: 200,
+                y: 100,
+                width: 50,
+                height: 20
+            }
+        );
+        let area: u32 = tiles.iter().map(|t| t.width * t.height).sum();
+        assert_eq!(area, 250 * 120);
+    }
+
+    #[test]
+    fn downsample_averages_blocks() {
+        #[rustfmt::skip]
+        let pixels = [
+            0, 0, 0, 255,     100, 0, 0, 255,
+            0, 200, 0, 255,   100, 200, 0, 255,
+        ];
+        assert_eq!(downsample(&pixels, 2, 2, 2), vec![50, 100, 0, 255]);
+        assert_eq!(downsample(&pixels, 2, 2, 1), pixels.to_vec());
+    }
+
+    #[test]
+    fn stitch_places_tile() {
+        let mut image = vec![0u8; 3 * 2 * 4];
+        let tile = Tile {
+            x: 1,
+            y: 1,
+            width: 2,
+            height: 1,
+        };
+        stitch(&mut image, 3, &tile, &[1, 1, 1, 1, 2, 2, 2, 2]);
+        assert_eq!(&image[12..16], &[0, 0, 0, 0]);
+        assert_eq!(&image[16..24], &[1, 1, 1, 1, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn flip_rows_reverses_order() {
+        let mut pixels = vec![1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3];
+        flip_rows(&mut pixels, 1, 3);
+        assert_eq!(pixels, vec![3, 3, 3, 3, 2, 2, 2, 2, 1, 1, 1, 1]);
+    }
+}
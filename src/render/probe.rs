@@ -0,0 +1,251 @@
+use glam::{IVec2, Vec3};
+use glow::{Framebuffer, HasContext, PixelPackData, Texture};
+use std::sync::Arc;
+
+/// Value of a single `sbProbe` call at the probed pixel
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProbeValue {
+    pub name: String,
+    /// 1 for float and int, 2-4 for vectors
+    pub components: usize,
+    pub is_int: bool,
+    pub value: [f32; 4],
+    /// False if the shader never got to the `sbProbe` call for this pixel
+    pub reached: bool,
+}
+
+impl ProbeValue {
+    /// Decode what the probe pass wrote to `sbProbeValue` and `sbProbeInfo`
+    pub fn decode(name: &str, value: [f32; 4], info: [f32; 4]) -> Self {
+        Self {
+            name: name.to_string(),
+            components: (info[0].round() as usize).clamp(1, 4),
+            is_int: info[1] > 0.5,
+            value,
+            reached: info[2] > 0.5,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match (self.is_int, self.components) {
+            (true, _) => "int",
+            (false, 1) => "float",
+            (false, 2) => "vec2",
+            (false, 3) => "vec3",
+            _ => "vec4",
+        }
+    }
+
+    /// The value as it would be written in GLSL
+    pub fn format(&self) -> String {
+        if !self.reached {
+            return "not reached".to_string();
+        }
+        if self.is_int {
+            return format!("{}", self.value[0].round() as i64);
+        }
+        let components: Vec<String> = self.value[..self.components]
+            .iter()
+            .map(|value| format!("{:.5}", value))
+            .collect();
+        match self.components {
+            1 => components[0].clone(),
+            n => format!("vec{}({})", n, components.join(", ")),
+        }
+    }
+}
+
+/// What to probe and the values read back during the last frame
+#[derive(Debug, Clone, Default)]
+pub struct Probes {
+    pub enabled: bool,
+    /// Render target pixel with the origin in the lower left corner
+    pub pixel: Option<IVec2>,
+    pub values: Vec<ProbeValue>,
+}
+
+/// What the shader reported through `sbPick` at a pixel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PickResult {
+    pub pixel: IVec2,
+    /// False if the shader didn't call `sbPick` for this pixel, e.g. the ray missed
+    pub hit: bool,
+    pub position: Vec3,
+    pub normal: Vec3,
+    /// Object or material id, if the shader passed one
+    pub id: Option<f32>,
+}
+
+impl PickResult {
+    /// Decode what the shader wrote to `sbPickPosition` and `sbPickNormal`
+    pub fn decode(pixel: IVec2, position: [f32; 4], normal: [f32; 4]) -> Self {
+        let hit = position[3] > 0.5;
+        Self {
+            pixel,
+            hit,
+            position: Vec3::from_slice(&position[..3]),
+            normal: Vec3::from_slice(&normal[..3]).normalize_or_zero(),
+            id: (hit && position[3] > 1.5).then_some(normal[3]),
+        }
+    }
+}
+
+/// Click in the scene to find out what's under the cursor
+#[derive(Debug, Clone, Default)]
+pub struct Picking {
+    pub enabled: bool,
+    /// Render target pixel to read back during the next frame
+    pub pending: Option<IVec2>,
+    pub result: Option<PickResult>,
+}
+
+/// A 1x1 float framebuffer with the scene color, the two probe outputs and the two pick
+/// outputs attached
+pub struct ProbeTarget {
+    gl: Arc<glow::Context>,
+    pub framebuffer: Framebuffer,
+    textures: Vec<Texture>,
+}
+
+impl ProbeTarget {
+    const ATTACHMENTS: [u32; 5] = [
+        glow::COLOR_ATTACHMENT0,
+        glow::COLOR_ATTACHMENT1,
+        glow::COLOR_ATTACHMENT2,
+        glow::COLOR_ATTACHMENT3,
+        glow::COLOR_ATTACHMENT4,
+    ];
+
+    pub fn new(gl: Arc<glow::Context>) -> Result<Self, String> {
+        unsafe {
+            let framebuffer = gl.create_framebuffer()?;
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+
+            let mut textures = vec![];
+            for attachment in Self::ATTACHMENTS {
+                let texture = gl.create_texture()?;
+                gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+                gl.tex_image_2d(
+                    glow::TEXTURE_2D,
+                    0,
+                    glow::RGBA32F as i32,
+                    1,
+                    1,
+                    0,
+                    glow::RGBA,
+                    glow::FLOAT,
+                    None,
+                );
+                gl.framebuffer_texture_2d(
+                    glow::FRAMEBUFFER,
+                    attachment,
+                    glow::TEXTURE_2D,
+                    Some(texture),
+                    0,
+                );
+                textures.push(texture);
+            }
+            gl.bind_texture(glow::TEXTURE_2D, None);
+            gl.draw_buffers(&Self::ATTACHMENTS);
+
+            let status = gl.check_framebuffer_status(glow::FRAMEBUFFER);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            macros::check_for_gl_error!(&gl, "probe_target");
+
+            let target = Self {
+                gl,
+                framebuffer,
+                textures,
+            };
+            if status != glow::FRAMEBUFFER_COMPLETE {
+                return Err(format!("Incomplete framebuffer, status: {:#x}", status));
+            }
+            Ok(target)
+        }
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            self.gl
+                .bind_framebuffer(glow::FRAMEBUFFER, Some(self.framebuffer));
+            self.gl.viewport(0, 0, 1, 1);
+        }
+    }
+
+    /// Read the pixel of one of the color attachments
+    pub fn read(&self, attachment: usize) -> [f32; 4] {
+        let mut bytes = [0u8; 16];
+        unsafe {
+            self.gl
+                .bind_framebuffer(glow::READ_FRAMEBUFFER, Some(self.framebuffer));
+            self.gl.read_buffer(Self::ATTACHMENTS[attachment]);
+            self.gl.read_pixels(
+                0,
+                0,
+                1,
+                1,
+                glow::RGBA,
+                glow::FLOAT,
+                PixelPackData::Slice(&mut bytes),
+            );
+            self.gl.read_buffer(glow::COLOR_ATTACHMENT0);
+            self.gl.bind_framebuffer(glow::READ_FRAMEBUFFER, None);
+            macros::check_for_gl_error!(&self.gl, "probe_read");
+        }
+        let channel =
+            |i: usize| f32::from_ne_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        [channel(0), channel(4), channel(8), channel(12)]
+    }
+}
+
+impl Drop for ProbeTarget {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.delete_framebuffer(self.framebuffer);
+            for texture in self.textures.drain(..) {
+                self.gl.delete_texture(texture);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PickResult, ProbeValue};
+    use glam::{IVec2, Vec3};
+
+    #[test]
+    fn decode_vector() {
+        let probe = ProbeValue::decode("uv", [0.25, 0.5, 0.0, 0.0], [2.0, 0.0, 1.0, 0.0]);
+        assert_eq!(probe.type_name(), "vec2");
+        assert_eq!(probe.format(), "vec2(0.25000, 0.50000)");
+    }
+
+    #[test]
+    fn decode_int_and_unreached() {
+        let probe = ProbeValue::decode("steps", [42.0, 0.0, 0.0, 0.0], [1.0, 1.0, 1.0, 0.0]);
+        assert_eq!(probe.type_name(), "int");
+        assert_eq!(probe.format(), "42");
+
+        let probe = ProbeValue::decode("d", [0.0; 4], [0.0; 4]);
+        assert_eq!(probe.type_name(), "float");
+        assert_eq!(probe.format(), "not reached");
+    }
+
+    #[test]
+    fn decode_pick() {
+        let pixel = IVec2::new(3, 4);
+        let pick = PickResult::decode(pixel, [1.0, 2.0, 3.0, 2.0], [0.0, 2.0, 0.0, 7.0]);
+        assert!(pick.hit);
+        assert_eq!(pick.position, Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(pick.normal, Vec3::Y);
+        assert_eq!(pick.id, Some(7.0));
+
+        let pick = PickResult::decode(pixel, [1.0, 2.0, 3.0, 1.0], [0.0, 1.0, 0.0, 0.0]);
+        assert_eq!(pick.id, None);
+
+        let pick = PickResult::decode(pixel, [0.0; 4], [0.0; 4]);
+        assert!(!pick.hit);
+        assert_eq!(pick.id, None);
+    }
+}
@@ -0,0 +1,219 @@
+use glow::HasContext;
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/// Number of frames of timing kept around for the graph
+pub const FRAME_HISTORY_LEN: usize = 240;
+
+/// Queries in flight per timer. Results are read a few frames late so we never wait on the GPU.
+const QUERY_RING_LEN: usize = 4;
+
+/// Rolling window of frame times in milliseconds
+#[derive(Debug, Clone, Default)]
+pub struct FrameHistory {
+    samples: VecDeque<f32>,
+}
+
+impl FrameHistory {
+    pub fn push(&mut self, ms: f32) {
+        if self.samples.len() == FRAME_HISTORY_LEN {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(ms);
+    }
+
+    pub fn samples(&self) -> impl Iterator<Item = f32> + '_ {
+        self.samples.iter().copied()
+    }
+
+    pub fn latest(&self) -> Option<f32> {
+        self.samples.back().copied()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn min(&self) -> f32 {
+        self.samples().reduce(f32::min).unwrap_or_default()
+    }
+
+    pub fn max(&self) -> f32 {
+        self.samples().reduce(f32::max).unwrap_or_default()
+    }
+
+    pub fn avg(&self) -> f32 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        self.samples().sum::<f32>() / self.samples.len() as f32
+    }
+}
+
+/// CPU and GPU timings of the last `FRAME_HISTORY_LEN` frames
+#[derive(Debug, Clone, Default)]
+pub struct FrameTimes {
+    /// Time between the start of two frames
+    pub frame: FrameHistory,
+    /// Time the CPU spent on a frame, not counting the wait for vsync
+    pub cpu: FrameHistory,
+    /// GPU time for drawing the scene and scaling it to the window
+    pub gpu_scene: FrameHistory,
+    /// GPU time for painting the UI
+    pub gpu_ui: FrameHistory,
+}
+
+impl FrameTimes {
+    pub fn fps(&self) -> f32 {
+        let avg = self.frame.avg();
+        if avg > 0.0 {
+            1000.0 / avg
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Measures GPU time between `begin` and `end` with `GL_TIME_ELAPSED` queries.
+/// Every frame uses the next query in a small ring and `poll` only picks up results
+/// that are already available, so timing never stalls the pipeline.
+pub struct GpuTimer {
+    gl: Arc<glow::Context>,
+    queries: Vec<glow::Query>,
+    /// Index of the query used for the next measurement
+    next: usize,
+    /// Number of queries waiting for results, the oldest is `next - pending`
+    pending: usize,
+    /// A query has been started and not yet ended
+    active: bool,
+}
+
+impl GpuTimer {
+    /// Returns `None` when the driver doesn't support timer queries
+    pub fn new(gl: Arc<glow::Context>) -> Option<Self> {
+        let version = gl.version();
+        let supported = (version.major, version.minor) >= (3, 3)
+            || gl.supported_extensions().contains("GL_ARB_timer_query");
+        if !supported || version.is_embedded {
+            log::warn!("Profiler: Timer queries are not supported, GPU times are unavailable");
+            return None;
+        }
+
+        let queries = unsafe {
+            (0..QUERY_RING_LEN)
+                .map(|_| gl.create_query())
+                .collect::<Result<Vec<_>, _>>()
+        };
+        match queries {
+            Ok(queries) => Some(Self {
+                gl,
+                queries,
+                next: 0,
+                pending: 0,
+                active: false,
+            }),
+            Err(err) => {
+                log::warn!("Profiler: Failed to create timer queries: {}", err);
+                None
+            }
+        }
+    }
+
+    /// Start a measurement. Skipped if all queries are still waiting for results.
+    pub fn begin(&mut self) {
+        if self.active || self.pending == QUERY_RING_LEN {
+            return;
+        }
+        unsafe {
+            self.gl
+                .begin_query(glow::TIME_ELAPSED, self.queries[self.next]);
+        }
+        self.active = true;
+    }
+
+    /// End the measurement started with `begin`
+    pub fn end(&mut self) {
+        if !std::mem::take(&mut self.active) {
+            return;
+        }
+        unsafe {
+            self.gl.end_query(glow::TIME_ELAPSED);
+        }
+        self.next = (self.next + 1) % QUERY_RING_LEN;
+        self.pending += 1;
+    }
+
+    /// Collect all finished measurements, oldest first, in milliseconds
+    pub fn poll(&mut self) -> Vec<f32> {
+        let mut results = vec![];
+        while self.pending > 0 {
+            let oldest = (self.next + QUERY_RING_LEN - self.pending) % QUERY_RING_LEN;
+            let query = self.queries[oldest];
+            unsafe {
+                if self
+                    .gl
+                    .get_query_parameter_u32(query, glow::QUERY_RESULT_AVAILABLE)
+                    == 0
+                {
+                    break;
+                }
+                let mut nanos = 0u64;
+                self.gl.get_query_parameter_u64_with_offset(
+                    query,
+                    glow::QUERY_RESULT,
+                    &mut nanos as *mut u64 as usize,
+                );
+                results.push(nanos as f32 / 1_000_000.0);
+            }
+            self.pending -= 1;
+        }
+        results
+    }
+}
+
+impl Drop for GpuTimer {
+    fn drop(&mut self) {
+        unsafe {
+            for query in self.queries.drain(..) {
+                self.gl.delete_query(query);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FrameHistory, FrameTimes, FRAME_HISTORY_LEN};
+
+    #[test]
+    fn history_statistics() {
+        let mut history = FrameHistory::default();
+        assert_eq!(history.avg(), 0.0);
+
+        for ms in [4.0, 2.0, 6.0] {
+            history.push(ms);
+        }
+        assert_eq!(history.min(), 2.0);
+        assert_eq!(history.max(), 6.0);
+        assert_eq!(history.avg(), 4.0);
+        assert_eq!(history.latest(), Some(6.0));
+    }
+
+    #[test]
+    fn history_is_rolling() {
+        let mut history = FrameHistory::default();
+        for i in 0..FRAME_HISTORY_LEN + 10 {
+            history.push(i as f32);
+        }
+        assert_eq!(history.samples().count(), FRAME_HISTORY_LEN);
+        assert_eq!(history.min(), 10.0);
+    }
+
+    #[test]
+    fn fps_from_frame_interval() {
+        let mut times = FrameTimes::default();
+        times.frame.push(20.0);
+        times.frame.push(20.0);
+        assert_eq!(times.fps(), 50.0);
+    }
+}
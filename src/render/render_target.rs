@@ -0,0 +1,181 @@
+use glow::{Framebuffer, HasContext, PixelPackData, Texture};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Pixel format of a render target
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum TargetFormat {
+    /// 8 bits per channel, values are clamped to 0..1
+    Rgba8,
+    /// Half floats, enough for HDR scenes
+    #[default]
+    Rgba16f,
+    Rgba32f,
+}
+
+impl TargetFormat {
+    fn internal_format(&self) -> u32 {
+        match self {
+            TargetFormat::Rgba8 => glow::RGBA8,
+            TargetFormat::Rgba16f => glow::RGBA16F,
+            TargetFormat::Rgba32f => glow::RGBA32F,
+        }
+    }
+
+    fn data_type(&self) -> u32 {
+        match self {
+            TargetFormat::Rgba8 => glow::UNSIGNED_BYTE,
+            TargetFormat::Rgba16f => glow::HALF_FLOAT,
+            TargetFormat::Rgba32f => glow::FLOAT,
+        }
+    }
+}
+
+/// An offscreen framebuffer with a single color attachment.
+/// Used whenever we need to render the scene somewhere else than straight to the window.
+pub struct RenderTarget {
+    gl: Arc<glow::Context>,
+    pub framebuffer: Framebuffer,
+    pub texture: Texture,
+    pub width: i32,
+    pub height: i32,
+    pub format: TargetFormat,
+}
+
+impl RenderTarget {
+    pub fn new(gl: Arc<glow::Context>, width: i32, height: i32) -> Result<Self, String> {
+        Self::with_format(gl, width, height, TargetFormat::Rgba8)
+    }
+
+    pub fn with_format(
+        gl: Arc<glow::Context>,
+        width: i32,
+        height: i32,
+        format: TargetFormat,
+    ) -> Result<Self, String> {
+        unsafe {
+            let texture = gl.create_texture()?;
+            gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                format.internal_format() as i32,
+                width,
+                height,
+                0,
+                glow::RGBA,
+                format.data_type(),
+                None,
+            );
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MIN_FILTER,
+                glow::NEAREST as i32,
+            );
+            gl.tex_parameter_i32(
+                glow::TEXTURE_2D,
+                glow::TEXTURE_MAG_FILTER,
+                glow::NEAREST as i32,
+            );
+            gl.bind_texture(glow::TEXTURE_2D, None);
+
+            let framebuffer = gl.create_framebuffer()?;
+            gl.bind_framebuffer(glow::FRAMEBUFFER, Some(framebuffer));
+            gl.framebuffer_texture_2d(
+                glow::FRAMEBUFFER,
+                glow::COLOR_ATTACHMENT0,
+                glow::TEXTURE_2D,
+                Some(texture),
+                0,
+            );
+            let status = gl.check_framebuffer_status(glow::FRAMEBUFFER);
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            macros::check_for_gl_error!(&gl, "render_target");
+
+            if status != glow::FRAMEBUFFER_COMPLETE {
+                gl.delete_framebuffer(framebuffer);
+                gl.delete_texture(texture);
+                return Err(format!("Incomplete framebuffer, status: {:#x}", status));
+            }
+
+            Ok(Self {
+                gl,
+                framebuffer,
+                texture,
+                width,
+                height,
+                format,
+            })
+        }
+    }
+
+    /// Bind the framebuffer and set the viewport to cover all of it
+    pub fn bind(&self) {
+        unsafe {
+            self.gl
+                .bind_framebuffer(glow::FRAMEBUFFER, Some(self.framebuffer));
+            self.gl.viewport(0, 0, self.width, self.height);
+        }
+    }
+
+    /// Read back the whole target as tightly packed RGBA8, bottom row first
+    pub fn read_rgba8(&self) -> Vec<u8> {
+        let mut pixels = vec![0u8; (self.width * self.height * 4) as usize];
+        unsafe {
+            self.gl
+                .bind_framebuffer(glow::READ_FRAMEBUFFER, Some(self.framebuffer));
+            self.gl.pixel_store_i32(glow::PACK_ALIGNMENT, 1);
+            self.gl.read_pixels(
+                0,
+                0,
+                self.width,
+                self.height,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                PixelPackData::Slice(&mut pixels),
+            );
+            self.gl.bind_framebuffer(glow::READ_FRAMEBUFFER, None);
+            macros::check_for_gl_error!(&self.gl, "read_rgba8");
+        }
+        pixels
+    }
+
+    /// Read back a region as RGBA floats, bottom row first. Values aren't clamped for float targets.
+    pub fn read_rgba_f32(&self, x: i32, y: i32, width: i32, height: i32) -> Vec<[f32; 4]> {
+        let mut bytes = vec![0u8; (width * height * 16) as usize];
+        unsafe {
+            self.gl
+                .bind_framebuffer(glow::READ_FRAMEBUFFER, Some(self.framebuffer));
+            self.gl.pixel_store_i32(glow::PACK_ALIGNMENT, 4);
+            self.gl.read_pixels(
+                x,
+                y,
+                width,
+                height,
+                glow::RGBA,
+                glow::FLOAT,
+                PixelPackData::Slice(&mut bytes),
+            );
+            self.gl.bind_framebuffer(glow::READ_FRAMEBUFFER, None);
+            macros::check_for_gl_error!(&self.gl, "read_rgba_f32");
+        }
+        bytes
+            .chunks_exact(16)
+            .map(|pixel| {
+                let channel = |i: usize| {
+                    f32::from_ne_bytes([pixel[i], pixel[i + 1], pixel[i + 2], pixel[i + 3]])
+                };
+                [channel(0), channel(4), channel(8), channel(12)]
+            })
+            .collect()
+    }
+}
+
+impl Drop for RenderTarget {
+    fn drop(&mut self) {
+        unsafe {
+            self.gl.delete_framebuffer(self.framebuffer);
+            self.gl.delete_texture(self.texture);
+        }
+    }
+}
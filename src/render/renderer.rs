@@ -1,10 +1,37 @@
-use crate::{AppState, ShaderService};
+use crate::{
+    overlay_camera, overlay_lines, AppState, ChannelSource, DisplayPass, GpuTimer, OverlayPass,
+    PickResult, ProbeTarget, ProbeValue, Project, RenderTarget, SceneView, ShaderService,
+    SkuggboxShader, Texture, UpscaleFilter,
+};
+use glam::Vec2;
 use glow::{HasContext, VertexArray};
 use std::sync::Arc;
 
+/// Where a buffer pass of a project renders to. The previous frame is kept so passes can read
+/// their own output.
+struct BufferTargets {
+    current: RenderTarget,
+    previous: RenderTarget,
+}
+
 pub struct Renderer {
     gl: Arc<glow::Context>,
     vertex_array: VertexArray,
+    /// The scene is rendered here and then scaled up to the window
+    scene_target: Option<RenderTarget>,
+    /// Exposure, tonemapping and encoding on the way from `scene_target` to the window.
+    /// `None` if it failed to compile, the scene is then blitted as it is.
+    display: Option<DisplayPass>,
+    /// GPU time of `draw`, `None` if timer queries aren't supported
+    scene_timer: Option<GpuTimer>,
+    /// Created the first time `sbProbe` values are read back
+    probe_target: Option<ProbeTarget>,
+    /// Grid, axes and handles, created the first time the gizmos are shown
+    overlay: Option<OverlayPass>,
+    /// Textures of the project, in the same order. `None` if one failed to load.
+    textures: Vec<Option<Texture>>,
+    /// One per project pass, the output pass never has any
+    buffers: Vec<Option<BufferTargets>>,
 }
 
 impl Renderer {
@@ -13,75 +40,552 @@ impl Renderer {
             gl.create_vertex_array()
                 .expect("Cannot create vertex array")
         };
-        Self { gl, vertex_array }
+        let scene_timer = GpuTimer::new(gl.clone());
+        let display = DisplayPass::new(gl.clone())
+            .map_err(|err| log::error!("Failed to create display pass: {}", err))
+            .ok();
+        Self {
+            gl,
+            vertex_array,
+            scene_target: None,
+            display,
+            scene_timer,
+            probe_target: None,
+            overlay: None,
+            textures: vec![],
+            buffers: vec![],
+        }
     }
 
-    pub fn draw(&self, state: &mut AppState, shader_service: &ShaderService) {
-        let gl = self.gl.clone();
-        unsafe {
-            gl.bind_vertex_array(Some(self.vertex_array));
+    /// Load the textures of a new or changed project and start its buffers from scratch
+    pub fn load_project(&mut self, project: Option<&Project>) {
+        self.buffers.clear();
+        self.textures = project
+            .map(|project| {
+                project
+                    .textures
+                    .iter()
+                    .map(|binding| {
+                        Texture::load(self.gl.clone(), &binding.path, binding.filter, binding.wrap)
+                            .map_err(|err| log::error!("Texture {:?}: {:?}", binding.name, err))
+                            .ok()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+    }
 
-            gl.clear_color(0.1, 0.2, 0.1, 1.0);
+    /// Render every pass of the project but the output one into its buffer at `width`x`height`.
+    /// Does nothing without a project.
+    pub fn draw_buffers(
+        &mut self,
+        state: &mut AppState,
+        shader_service: &ShaderService,
+        width: i32,
+        height: i32,
+    ) {
+        let output = shader_service.output_index();
+        self.buffers.resize_with(output, || None);
 
-            if let Some(shader) = shader_service.shaders.get(0) {
-                // kick shader to gpu
-                gl.use_program(shader.program);
+        for pass in 0..output {
+            let targets = match self.buffers[pass].take() {
+                Some(targets)
+                    if targets.current.width == width
+                        && targets.current.height == height
+                        && targets.current.format == state.scene_format =>
+                {
+                    BufferTargets {
+                        current: targets.previous,
+                        previous: targets.current,
+                    }
+                }
+                _ => {
+                    let create = || {
+                        RenderTarget::with_format(
+                            self.gl.clone(),
+                            width,
+                            height,
+                            state.scene_format,
+                        )
+                    };
+                    match create().and_then(|current| {
+                        create().map(|previous| BufferTargets { current, previous })
+                    }) {
+                        Ok(targets) => targets,
+                        Err(err) => {
+                            log::error!("Failed to create buffer target: {}", err);
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            // draw with the new targets in place so the pass can read its previous frame
+            targets.current.bind();
+            self.buffers[pass] = Some(targets);
+            let resolution = Vec2::new(width as f32, height as f32);
+            self.draw_pass(shader_service, pass, state, resolution, Vec2::ZERO);
+        }
+        unsafe { self.gl.bind_framebuffer(glow::FRAMEBUFFER, None) };
+    }
 
-                // set uniforms
-                if let Some(resolution) = shader.locations.resolution {
-                    gl.uniform_2_f32(Some(&resolution), state.width as f32, state.height as f32)
+    /// Bind the channels of `pass` to their texture units and `iChannelN` uniforms
+    fn bind_channels(&self, shader: &SkuggboxShader, project: &Project, pass: usize) {
+        let channels = match project.passes.get(pass) {
+            Some(pass) => &pass.channels,
+            None => return,
+        };
+        unsafe {
+            let gl = &self.gl;
+            gl.use_program(shader.program);
+            for (unit, name) in channels.iter().enumerate() {
+                let texture = match project.channel_source(name) {
+                    Some(ChannelSource::Texture(index)) => self
+                        .textures
+                        .get(index)
+                        .and_then(|texture| texture.as_ref())
+                        .map(|texture| texture.texture),
+                    Some(ChannelSource::Pass(index)) => self
+                        .buffers
+                        .get(index)
+                        .and_then(|targets| targets.as_ref())
+                        .map(|targets| match index < pass {
+                            true => targets.current.texture,
+                            false => targets.previous.texture,
+                        }),
+                    None => None,
+                };
+                gl.active_texture(glow::TEXTURE0 + unit as u32);
+                gl.bind_texture(glow::TEXTURE_2D, texture);
+                if let Some(location) = &shader.locations.channels[unit] {
+                    gl.uniform_1_i32(Some(location), unit as i32);
                 }
+            }
+            gl.active_texture(glow::TEXTURE0);
+            macros::check_for_gl_error!(gl, "bind_channels");
+        }
+    }
+
+    /// Draw pass `pass` with its channels bound, see `draw_scene`
+    pub fn draw_pass(
+        &self,
+        shader_service: &ShaderService,
+        pass: usize,
+        state: &mut AppState,
+        resolution: Vec2,
+        tile_offset: Vec2,
+    ) {
+        let shader = match shader_service.shaders.get(pass) {
+            Some(shader) => shader,
+            None => return,
+        };
+        if let Some(project) = &shader_service.project {
+            self.bind_channels(shader, project, pass);
+        }
+        self.draw_scene(shader, state, resolution, tile_offset);
+    }
 
-                if let Some(time) = shader.locations.time {
-                    gl.uniform_1_f32(Some(&time), state.playback_time)
+    pub fn gl(&self) -> Arc<glow::Context> {
+        self.gl.clone()
+    }
+
+    /// Largest width or height we can render to in a single pass
+    pub fn max_render_size(&self) -> i32 {
+        unsafe {
+            let max_texture_size = self.gl.get_parameter_i32(glow::MAX_TEXTURE_SIZE);
+            let max_renderbuffer_size = self.gl.get_parameter_i32(glow::MAX_RENDERBUFFER_SIZE);
+            max_texture_size.min(max_renderbuffer_size)
+        }
+    }
+
+    /// Copy `target` as it is into `viewport` of the window
+    fn blit(&self, target: &RenderTarget, viewport: [i32; 4], filter: UpscaleFilter) {
+        let filter = match filter {
+            UpscaleFilter::Nearest => glow::NEAREST,
+            UpscaleFilter::Linear => glow::LINEAR,
+        };
+        let [x, y, w, h] = viewport;
+        unsafe {
+            let gl = &self.gl;
+            gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(target.framebuffer));
+            gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, None);
+            gl.blit_framebuffer(
+                0,
+                0,
+                target.width,
+                target.height,
+                x,
+                y,
+                x + w,
+                y + h,
+                glow::COLOR_BUFFER_BIT,
+                filter,
+            );
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            macros::check_for_gl_error!(gl, "blit_framebuffer");
+        }
+    }
+
+    /// Apply the display transform to an HDR `source` and write the result to `output`.
+    /// Used for offline rendering so posters and videos look like the window.
+    pub fn resolve(&self, source: &RenderTarget, output: &RenderTarget, state: &AppState) {
+        output.bind();
+        match &self.display {
+            Some(display) => {
+                unsafe { self.gl.bind_vertex_array(Some(self.vertex_array)) };
+                display.draw(
+                    source.texture,
+                    UpscaleFilter::Nearest,
+                    [0, 0, output.width, output.height],
+                    &state.display,
+                    state.frame,
+                );
+            }
+            None => unsafe {
+                let gl = &self.gl;
+                gl.bind_framebuffer(glow::READ_FRAMEBUFFER, Some(source.framebuffer));
+                gl.bind_framebuffer(glow::DRAW_FRAMEBUFFER, Some(output.framebuffer));
+                gl.blit_framebuffer(
+                    0,
+                    0,
+                    source.width,
+                    source.height,
+                    0,
+                    0,
+                    output.width,
+                    output.height,
+                    glow::COLOR_BUFFER_BIT,
+                    glow::NEAREST,
+                );
+            },
+        }
+        unsafe { self.gl.bind_framebuffer(glow::FRAMEBUFFER, None) };
+    }
+
+    /// Read a region of the last rendered scene as RGBA floats, bottom row first.
+    /// The region is in render target pixels and has to be inside the target.
+    pub fn read_scene(&self, x: i32, y: i32, width: i32, height: i32) -> Option<Vec<[f32; 4]>> {
+        self.scene_target
+            .as_ref()
+            .map(|target| target.read_rgba_f32(x, y, width, height))
+    }
+
+    /// Render the scene offscreen at `state.render_resolution` and draw it to the window through
+    /// the display transform, letterboxed if the aspect ratios differ.
+    pub fn draw(&mut self, state: &mut AppState, shader_service: &ShaderService) {
+        let max_size = self.max_render_size();
+        let (width, height) = state.render_resolution.size(state.width, state.height);
+        let (width, height) = (width.min(max_size), height.min(max_size));
+        state.scene_view = SceneView::letterbox((width, height), (state.width, state.height));
+
+        let target = match self.scene_target.take() {
+            Some(target)
+                if target.width == width
+                    && target.height == height
+                    && target.format == state.scene_format =>
+            {
+                target
+            }
+            _ => {
+                match RenderTarget::with_format(self.gl.clone(), width, height, state.scene_format)
+                {
+                    Ok(target) => target,
+                    Err(err) => {
+                        log::error!("Failed to create scene target: {}", err);
+                        return;
+                    }
                 }
+            }
+        };
+
+        if let Some(timer) = &mut self.scene_timer {
+            timer.begin();
+        }
 
-                if let Some(delta_time) = shader.locations.time_delta {
-                    gl.uniform_1_f32(Some(&delta_time), state.delta_time)
+        self.draw_buffers(state, shader_service, width, height);
+        target.bind();
+        let resolution = Vec2::new(width as f32, height as f32);
+        self.draw_pass(
+            shader_service,
+            shader_service.output_index(),
+            state,
+            resolution,
+            Vec2::ZERO,
+        );
+
+        unsafe {
+            let gl = &self.gl;
+            gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            gl.viewport(0, 0, state.width, state.height);
+            gl.clear_color(0.0, 0.0, 0.0, 1.0);
+            gl.clear(glow::COLOR_BUFFER_BIT);
+        }
+        let viewport = state.scene_view.viewport;
+        match &self.display {
+            Some(display) => {
+                unsafe { self.gl.bind_vertex_array(Some(self.vertex_array)) };
+                display.draw(
+                    target.texture,
+                    state.upscale_filter,
+                    viewport,
+                    &state.display,
+                    state.frame,
+                );
+            }
+            None => self.blit(&target, viewport, state.upscale_filter),
+        }
+
+        if state.gizmos.enabled {
+            self.draw_overlay(state, viewport);
+        }
+
+        if let Some(timer) = &mut self.scene_timer {
+            timer.end();
+            for ms in timer.poll() {
+                state.frame_times.gpu_scene.push(ms);
+            }
+        }
+
+        self.scene_target = Some(target);
+    }
+
+    fn draw_overlay(&mut self, state: &mut AppState, viewport: [i32; 4]) {
+        if self.overlay.is_none() {
+            match OverlayPass::new(self.gl.clone()) {
+                Ok(overlay) => self.overlay = Some(overlay),
+                Err(err) => {
+                    log::error!("Failed to create overlay pass: {}", err);
+                    state.gizmos.enabled = false;
+                    return;
                 }
+            }
+        }
 
-                // Mouse uniforms
-                if let Some(mouse) = shader.locations.mouse {
-                    let x = state.mouse.pos.x;
-                    let y = state.mouse.pos.y;
+        if let Some(overlay) = &self.overlay {
+            let camera = overlay_camera(state);
+            let lines = overlay_lines(state, &camera);
+            overlay.draw(&lines, &camera, viewport);
+        }
+    }
 
-                    let left_mouse = if state.mouse.is_lmb_down { 1.0 } else { 0.0 };
-                    let right_mouse = if state.mouse.is_rmb_down { 1.0 } else { 0.0 };
+    /// Read back the `sbProbe` values at `state.probes.pixel`. The shader is drawn once per probe
+    /// into a single pixel, using `sbTileOffset` to move that pixel to the probed one.
+    pub fn probe(&mut self, state: &mut AppState, shader_service: &ShaderService) {
+        state.probes.values.clear();
+        let pixel = match state.probes.pixel {
+            Some(pixel) if state.probes.enabled => pixel,
+            _ => return,
+        };
+        let shader = match shader_service.output_shader() {
+            Some(shader) if shader.program.is_some() && !shader.content.probes.is_empty() => shader,
+            _ => return,
+        };
+        let slot_location = match shader.locations.sb_probe_slot {
+            Some(location) => location,
+            None => return,
+        };
 
-                    gl.uniform_4_f32(Some(&mouse), x, y, left_mouse, right_mouse);
-                };
+        if !self.create_probe_target() {
+            state.probes.enabled = false;
+            return;
+        }
 
-                if let Some(mouse_dir) = shader.locations.mouse_dir {
-                    gl.uniform_3_f32(
-                        Some(&mouse_dir),
-                        state.mouse.dir.x,
-                        state.mouse.dir.y,
-                        state.mouse.dir.z,
-                    );
+        let view = state.scene_view;
+        let resolution = Vec2::new(view.render_width as f32, view.render_height as f32);
+        let mut values = vec![];
+        if let Some(target) = &self.probe_target {
+            target.bind();
+            for (slot, name) in shader.content.probes.iter().enumerate() {
+                unsafe {
+                    self.gl.use_program(shader.program);
+                    self.gl.uniform_1_i32(Some(&slot_location), slot as i32);
                 }
+                let pass = shader_service.output_index();
+                self.draw_pass(shader_service, pass, state, resolution, pixel.as_vec2());
+                values.push(ProbeValue::decode(name, target.read(1), target.read(2)));
+            }
+            unsafe {
+                self.gl.uniform_1_i32(Some(&slot_location), -1);
+                self.gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            }
+        }
+        state.probes.values = values;
+    }
+
+    /// Read back what the shader passed to `sbPick` at `state.picking.pending`, once.
+    /// Uses the probe target, so the pixel is drawn the same way as for `probe`.
+    pub fn pick(&mut self, state: &mut AppState, shader_service: &ShaderService) {
+        let pixel = match state.picking.pending.take() {
+            Some(pixel) => pixel,
+            None => return,
+        };
+        let pickable = shader_service.output_shader().map_or(false, |shader| {
+            shader.program.is_some() && shader.content.uses_pick
+        });
+        if !pickable {
+            state.picking.result = None;
+            return;
+        }
+        if !self.create_probe_target() {
+            return;
+        }
 
-                if let Some(sb_camera_transform) = shader.locations.sb_camera_transform {
-                    let camera = state.camera.calculate_uniform_data();
-                    let f32_arr = camera.to_cols_array();
-                    gl.uniform_matrix_4_f32_slice(Some(&sb_camera_transform), false, &f32_arr);
+        let view = state.scene_view;
+        let resolution = Vec2::new(view.render_width as f32, view.render_height as f32);
+        if let Some(target) = &self.probe_target {
+            target.bind();
+            let pass = shader_service.output_index();
+            self.draw_pass(shader_service, pass, state, resolution, pixel.as_vec2());
+            state.picking.result = Some(PickResult::decode(pixel, target.read(3), target.read(4)));
+            unsafe {
+                self.gl.bind_framebuffer(glow::FRAMEBUFFER, None);
+            }
+        }
+    }
+
+    /// Create the 1x1 target shared by probing and picking, returns false if that failed
+    fn create_probe_target(&mut self) -> bool {
+        if self.probe_target.is_none() {
+            match ProbeTarget::new(self.gl.clone()) {
+                Ok(target) => self.probe_target = Some(target),
+                Err(err) => {
+                    log::error!("Failed to create probe target: {}", err);
+                    return false;
                 }
+            }
+        }
+        true
+    }
+
+    /// Draw the shader into whatever framebuffer and viewport is currently bound.
+    /// * `resolution` - Value of `iResolution`
+    /// * `tile_offset` - Value of `sbTileOffset`, added to `gl_FragCoord` by the pre-processor
+    pub fn draw_scene(
+        &self,
+        shader: &SkuggboxShader,
+        state: &mut AppState,
+        resolution: Vec2,
+        tile_offset: Vec2,
+    ) {
+        let gl = self.gl.clone();
+        unsafe {
+            gl.bind_vertex_array(Some(self.vertex_array));
+
+            gl.clear_color(0.1, 0.2, 0.1, 1.0);
+
+            // kick shader to gpu
+            gl.use_program(shader.program);
+
+            // set uniforms
+            if let Some(location) = shader.locations.resolution {
+                gl.uniform_2_f32(Some(&location), resolution.x, resolution.y)
+            }
 
-                if let Some(cam_pos) = shader.locations.cam_pos {
-                    let pos = state.camera_pos;
-                    gl.uniform_3_f32(Some(&cam_pos), pos.x, pos.y, pos.z);
+            if let Some(location) = shader.locations.sb_tile_offset {
+                gl.uniform_2_f32(Some(&location), tile_offset.x, tile_offset.y)
+            }
+
+            if let Some(view_2d) = shader.locations.sb_view_2d {
+                let view = state.camera.view_2d(resolution);
+                gl.uniform_matrix_3_f32_slice(Some(&view_2d), false, &view.to_cols_array());
+            }
+
+            if let Some(time) = shader.locations.time {
+                gl.uniform_1_f32(Some(&time), state.playback_time)
+            }
+
+            if let Some(delta_time) = shader.locations.time_delta {
+                gl.uniform_1_f32(Some(&delta_time), state.delta_time)
+            }
+
+            if let Some(frame) = shader.locations.frame {
+                gl.uniform_1_i32(Some(&frame), state.frame as i32)
+            }
+
+            // Mouse uniforms
+            if let Some(mouse) = shader.locations.mouse {
+                let pos = state.scene_view.scale_to_render(state.mouse.pos);
+                let (x, y) = (pos.x, pos.y);
+
+                let left_mouse = if state.mouse.is_lmb_down { 1.0 } else { 0.0 };
+                let right_mouse = if state.mouse.is_rmb_down { 1.0 } else { 0.0 };
+
+                gl.uniform_4_f32(Some(&mouse), x, y, left_mouse, right_mouse);
+            };
+
+            if let Some(mouse_dir) = shader.locations.mouse_dir {
+                gl.uniform_3_f32(
+                    Some(&mouse_dir),
+                    state.mouse.dir.x,
+                    state.mouse.dir.y,
+                    state.mouse.dir.z,
+                );
+            }
+
+            if let Some(sb_camera_transform) = shader.locations.sb_camera_transform {
+                let camera = state.camera_transform();
+                let f32_arr = camera.to_cols_array();
+                gl.uniform_matrix_4_f32_slice(Some(&sb_camera_transform), false, &f32_arr);
+            }
+
+            let projection = &state.camera_projection();
+            if let Some(location) = shader.locations.sb_camera_projection {
+                let aspect = resolution.x / resolution.y.max(1.0);
+                let value = projection.uniform(aspect);
+                gl.uniform_4_f32(Some(&location), value.x, value.y, value.z, value.w);
+            }
+
+            if let Some(location) = shader.locations.sb_camera_ortho_scale {
+                gl.uniform_1_f32(Some(&location), projection.ortho_uniform());
+            }
+
+            if let Some(location) = shader.locations.sb_camera_lens {
+                gl.uniform_2_f32(
+                    Some(&location),
+                    projection.focus_distance,
+                    projection.aperture,
+                );
+            }
+
+            if let Some(cam_pos) = shader.locations.cam_pos {
+                let pos = state.camera_pos;
+                gl.uniform_3_f32(Some(&cam_pos), pos.x, pos.y, pos.z);
+            }
+
+            for handle in &state.gizmos.handles {
+                if let Some(location) = shader.locations.handles.get(&handle.name) {
+                    let p = handle.position;
+                    gl.uniform_3_f32(Some(location), p.x, p.y, p.z);
                 }
+            }
 
-                if let Some(sb_color_a) = shader.locations.sb_color_a {
-                    let col = state.scene_vars.color_a;
-                    gl.uniform_3_f32(Some(&sb_color_a), col[0], col[1], col[2]);
+            for (name, value) in &state.project_uniforms {
+                if let Some(location) = shader.locations.project.get(name) {
+                    match value[..] {
+                        [x] => gl.uniform_1_f32(Some(location), x),
+                        [x, y] => gl.uniform_2_f32(Some(location), x, y),
+                        [x, y, z] => gl.uniform_3_f32(Some(location), x, y, z),
+                        [x, y, z, w] => gl.uniform_4_f32(Some(location), x, y, z, w),
+                        _ => {}
+                    }
                 }
+            }
 
-                // actually render
-                gl.clear(glow::COLOR_BUFFER_BIT);
-                macros::check_for_gl_error!(&gl, "clear");
-                gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 3);
-                macros::check_for_gl_error!(&gl, "draw_arrays");
+            if let Some(location) = shader.locations.sb_beat {
+                let beat = state.timeline.beat(state.playback_time);
+                gl.uniform_1_f32(Some(&location), beat);
             }
+
+            if let Some(sb_color_a) = shader.locations.sb_color_a {
+                let col = state.scene_vars.color_a;
+                gl.uniform_3_f32(Some(&sb_color_a), col[0], col[1], col[2]);
+            }
+
+            // actually render
+            gl.clear(glow::COLOR_BUFFER_BIT);
+            macros::check_for_gl_error!(&gl, "clear");
+            gl.draw_arrays(glow::TRIANGLE_STRIP, 0, 3);
+            macros::check_for_gl_error!(&gl, "draw_arrays");
         }
     }
 }
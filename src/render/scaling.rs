@@ -0,0 +1,208 @@
+use glam::{IVec2, Vec2};
+use serde::{Deserialize, Serialize};
+
+/// Scale factors offered in the UI and stepped through with hotkeys
+pub const RENDER_SCALES: [f32; 7] = [0.25, 0.5, 0.75, 1.0, 1.25, 1.5, 2.0];
+
+/// Size of the offscreen target the scene is rendered into
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum RenderResolution {
+    /// Relative to the window size
+    Scale(f32),
+    /// Fixed size no matter how large the window is
+    Fixed { width: u32, height: u32 },
+}
+
+impl Default for RenderResolution {
+    fn default() -> Self {
+        Self::Scale(1.0)
+    }
+}
+
+impl RenderResolution {
+    pub fn size(&self, window_width: i32, window_height: i32) -> (i32, i32) {
+        match *self {
+            RenderResolution::Scale(scale) => (
+                ((window_width as f32 * scale).round() as i32).max(1),
+                ((window_height as f32 * scale).round() as i32).max(1),
+            ),
+            RenderResolution::Fixed { width, height } => (width as i32, height as i32),
+        }
+    }
+
+    /// Move to the next smaller or larger scale in `RENDER_SCALES`
+    pub fn step(&self, steps: i32) -> Self {
+        let current = match *self {
+            RenderResolution::Scale(scale) => RENDER_SCALES
+                .iter()
+                .position(|s| *s >= scale)
+                .unwrap_or(RENDER_SCALES.len() - 1),
+            RenderResolution::Fixed { .. } => 3,
+        } as i32;
+        let index = (current + steps).clamp(0, RENDER_SCALES.len() as i32 - 1);
+        RenderResolution::Scale(RENDER_SCALES[index as usize])
+    }
+}
+
+/// How the scene is filtered when it's drawn to the window at another size
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum UpscaleFilter {
+    #[default]
+    Nearest,
+    Linear,
+}
+
+/// Where the rendered scene ends up in the window.
+/// The viewport uses OpenGL conventions, in pixels with the origin in the lower left corner.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SceneView {
+    pub render_width: i32,
+    pub render_height: i32,
+    pub window_width: i32,
+    pub window_height: i32,
+    /// x, y, width, height
+    pub viewport: [i32; 4],
+}
+
+impl SceneView {
+    /// Fit the render target in the window, keeping its aspect ratio. Bars are added on the
+    /// sides or at the top and bottom when the aspect ratios differ.
+    pub fn letterbox(render_size: (i32, i32), window_size: (i32, i32)) -> Self {
+        let (render_width, render_height) = render_size;
+        let (window_width, window_height) = window_size;
+
+        let scale = f32::min(
+            window_width as f32 / render_width as f32,
+            window_height as f32 / render_height as f32,
+        );
+        let width = (render_width as f32 * scale).round() as i32;
+        let height = (render_height as f32 * scale).round() as i32;
+
+        Self {
+            render_width,
+            render_height,
+            window_width,
+            window_height,
+            viewport: [
+                (window_width - width) / 2,
+                (window_height - height) / 2,
+                width,
+                height,
+            ],
+        }
+    }
+
+    /// Map a window position, as winit reports it with the origin in the upper left corner,
+    /// to a pixel in the render target with the origin in the lower left corner.
+    /// Returns `None` outside of the scene.
+    pub fn window_to_render(&self, window_pos: Vec2) -> Option<Vec2> {
+        let [x, y, width, height] = self.viewport;
+        if width <= 0 || height <= 0 {
+            return None;
+        }
+
+        let gl_y = self.window_height as f32 - window_pos.y;
+        let u = (window_pos.x - x as f32) / width as f32;
+        let v = (gl_y - y as f32) / height as f32;
+        if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+            return None;
+        }
+
+        Some(Vec2::new(
+            u * self.render_width as f32,
+            v * self.render_height as f32,
+        ))
+    }
+
+    /// Inverse of `window_to_render`, returns the window position of the center of a pixel
+    pub fn render_to_window(&self, pixel: IVec2) -> Vec2 {
+        let [x, y, width, height] = self.viewport;
+        let u = (pixel.x as f32 + 0.5) / self.render_width.max(1) as f32;
+        let v = (pixel.y as f32 + 0.5) / self.render_height.max(1) as f32;
+        Vec2::new(
+            x as f32 + u * width as f32,
+            self.window_height as f32 - (y as f32 + v * height as f32),
+        )
+    }
+
+    /// Scale a window position to render target pixels without flipping the y axis.
+    /// Used for `iMouse` which has always been in window coordinates.
+    pub fn scale_to_render(&self, window_pos: Vec2) -> Vec2 {
+        let [x, y, width, height] = self.viewport;
+        if width <= 0 || height <= 0 {
+            return window_pos;
+        }
+        let top = self.window_height - (y + height);
+        Vec2::new(
+            (window_pos.x - x as f32) * self.render_width as f32 / width as f32,
+            (window_pos.y - top as f32) * self.render_height as f32 / height as f32,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RenderResolution, SceneView};
+    use glam::{IVec2, Vec2};
+
+    #[test]
+    fn resolution_size() {
+        assert_eq!(RenderResolution::Scale(0.5).size(1024, 768), (512, 384));
+        let fixed = RenderResolution::Fixed {
+            width: 1920,
+            height: 1080,
+        };
+        assert_eq!(fixed.size(1024, 768), (1920, 1080));
+    }
+
+    #[test]
+    fn resolution_steps() {
+        assert_eq!(
+            RenderResolution::Scale(1.0).step(-1),
+            RenderResolution::Scale(0.75)
+        );
+        assert_eq!(
+            RenderResolution::Scale(2.0).step(1),
+            RenderResolution::Scale(2.0)
+        );
+        assert_eq!(
+            RenderResolution::Scale(0.3).step(0),
+            RenderResolution::Scale(0.5)
+        );
+    }
+
+    #[test]
+    fn letterbox_wide_target() {
+        let view = SceneView::letterbox((1920, 1080), (1000, 1000));
+        assert_eq!(view.viewport, [0, 218, 1000, 563]);
+    }
+
+    #[test]
+    fn letterbox_same_aspect() {
+        let view = SceneView::letterbox((512, 384), (1024, 768));
+        assert_eq!(view.viewport, [0, 0, 1024, 768]);
+    }
+
+    #[test]
+    fn map_window_to_render() {
+        let view = SceneView::letterbox((100, 50), (200, 200));
+        // the scene covers y 50..150 in the window
+        assert_eq!(view.window_to_render(Vec2::new(0.0, 20.0)), None);
+        assert_eq!(
+            view.window_to_render(Vec2::new(100.0, 100.0)),
+            Some(Vec2::new(50.0, 25.0))
+        );
+        assert_eq!(
+            view.window_to_render(Vec2::new(0.0, 149.0)),
+            Some(Vec2::new(0.0, 0.5))
+        );
+        assert_eq!(
+            view.scale_to_render(Vec2::new(0.0, 50.0)),
+            Vec2::new(0.0, 0.0)
+        );
+        assert_eq!(
+            view.render_to_window(IVec2::new(50, 25)),
+            Vec2::new(101.0, 99.0)
+        );
+    }
+}
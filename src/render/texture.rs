@@ -0,0 +1,129 @@
+use anyhow::{bail, Context};
+use glow::HasContext;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::{flip_rows, UpscaleFilter};
+
+/// What happens when a texture is sampled outside 0..1
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TextureWrap {
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+impl TextureWrap {
+    fn gl_wrap(&self) -> i32 {
+        (match self {
+            TextureWrap::Repeat => glow::REPEAT,
+            TextureWrap::Clamp => glow::CLAMP_TO_EDGE,
+            TextureWrap::Mirror => glow::MIRRORED_REPEAT,
+        }) as i32
+    }
+}
+
+/// An image loaded from disk that shaders can sample through a channel
+pub struct Texture {
+    gl: Arc<glow::Context>,
+    pub texture: glow::Texture,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Texture {
+    pub fn load(
+        gl: Arc<glow::Context>,
+        path: &Path,
+        filter: UpscaleFilter,
+        wrap: TextureWrap,
+    ) -> anyhow::Result<Self> {
+        let (width, height, pixels) = read_png(path)?;
+        let filter = match filter {
+            UpscaleFilter::Nearest => glow::NEAREST,
+            UpscaleFilter::Linear => glow::LINEAR,
+        } as i32;
+
+        unsafe {
+            let texture = gl.create_texture().map_err(anyhow::Error::msg)?;
+            gl.bind_texture(glow::TEXTURE_2D, Some(texture));
+            gl.pixel_store_i32(glow::UNPACK_ALIGNMENT, 1);
+            gl.tex_image_2d(
+                glow::TEXTURE_2D,
+                0,
+                glow::RGBA8 as i32,
+                width as i32,
+                height as i32,
+                0,
+                glow::RGBA,
+                glow::UNSIGNED_BYTE,
+                Some(&pixels),
+            );
+            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MIN_FILTER, filter);
+            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_MAG_FILTER, filter);
+            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_S, wrap.gl_wrap());
+            gl.tex_parameter_i32(glow::TEXTURE_2D, glow::TEXTURE_WRAP_T, wrap.gl_wrap());
+            gl.bind_texture(glow::TEXTURE_2D, None);
+            macros::check_for_gl_error!(&gl, "texture");
+
+            Ok(Self {
+                gl,
+                texture,
+                width,
+                height,
+            })
+        }
+    }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe { self.gl.delete_texture(self.texture) };
+    }
+}
+
+/// Read a PNG as RGBA8 with the bottom row first, so `uv` 0,0 is the lower left corner
+pub fn read_png(path: &Path) -> anyhow::Result<(u32, u32, Vec<u8>)> {
+    let file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buffer = vec![0u8; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer)?;
+    let data = &buffer[..info.buffer_size()];
+
+    let mut pixels: Vec<u8> = match info.color_type {
+        png::ColorType::Rgba => data.to_vec(),
+        png::ColorType::Rgb => data
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => data
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => data.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        color_type => bail!("Unsupported PNG color type {:?} in {:?}", color_type, path),
+    };
+    flip_rows(&mut pixels, info.width, info.height);
+    Ok((info.width, info.height, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_png;
+    use crate::{save_png, TempPath};
+
+    #[test]
+    fn png_is_read_bottom_row_first() {
+        let path = TempPath::new("read-png-test.png");
+        save_png(&path.0, 1, 2, &[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        let (width, height, pixels) = read_png(&path.0).unwrap();
+
+        assert_eq!((width, height), (1, 2));
+        assert_eq!(pixels, vec![5, 6, 7, 8, 1, 2, 3, 4]);
+    }
+}
@@ -0,0 +1,362 @@
+use anyhow::{bail, Context};
+use glam::Vec2;
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use crate::{flip_rows, frame_time, AppState, RenderTarget, Renderer, ShaderService};
+
+/// Settings for rendering the shader to a video file, frame by frame.
+#[derive(Debug, Clone)]
+pub struct VideoConfig {
+    /// Where the YUV4MPEG2 stream is written. Available as `{output}` in `command`.
+    pub output: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    pub fps: u32,
+    /// Length of the video in seconds
+    pub duration: f32,
+    /// Pipe raw RGBA frames to this command instead of writing Y4M.
+    /// `{width}`, `{height}`, `{fps}`, `{output}` and `{audio}` are replaced before running it.
+    pub command: Option<String>,
+    /// WAV soundtrack to pass along with the video
+    pub audio: Option<PathBuf>,
+}
+
+impl VideoConfig {
+    pub fn frame_count(&self) -> u32 {
+        (self.duration * self.fps as f32).ceil() as u32
+    }
+}
+
+/// Something that consumes rendered frames, RGBA8 with the top row first
+pub trait FrameSink {
+    fn write_frame(&mut self, rgba: &[u8]) -> anyhow::Result<()>;
+
+    /// Flush everything and wait for any external process to finish
+    fn finish(self: Box<Self>) -> anyhow::Result<()>;
+}
+
+/// Writes a YUV4MPEG2 stream with 4:2:0 chroma subsampling, readable by ffmpeg, mpv and friends.
+pub struct Y4mWriter<W: Write> {
+    writer: W,
+    width: u32,
+    height: u32,
+}
+
+impl<W: Write> Y4mWriter<W> {
+    pub fn new(mut writer: W, width: u32, height: u32, fps: u32) -> anyhow::Result<Self> {
+        writeln!(
+            writer,
+            "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C420jpeg",
+            width, height, fps
+        )?;
+        Ok(Self {
+            writer,
+            width,
+            height,
+        })
+    }
+}
+
+impl<W: Write> FrameSink for Y4mWriter<W> {
+    fn write_frame(&mut self, rgba: &[u8]) -> anyhow::Result<()> {
+        let (y, u, v) = rgba_to_yuv420(rgba, self.width, self.height);
+        self.writer.write_all(b"FRAME\n")?;
+        self.writer.write_all(&y)?;
+        self.writer.write_all(&u)?;
+        self.writer.write_all(&v)?;
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> anyhow::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Pipes raw RGBA frames into the stdin of an external encoder
+pub struct PipeWriter {
+    child: Child,
+    stdin: Option<BufWriter<ChildStdin>>,
+}
+
+impl PipeWriter {
+    pub fn spawn(command: &str) -> anyhow::Result<Self> {
+        log::info!("Video: piping frames to `{}`", command);
+        let mut child = shell_command(command)
+            .stdin(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run `{}`", command))?;
+        let stdin = child.stdin.take().map(BufWriter::new);
+        Ok(Self { child, stdin })
+    }
+}
+
+impl FrameSink for PipeWriter {
+    fn write_frame(&mut self, rgba: &[u8]) -> anyhow::Result<()> {
+        match &mut self.stdin {
+            Some(stdin) => Ok(stdin.write_all(rgba)?),
+            None => bail!("Encoder stdin is closed"),
+        }
+    }
+
+    fn finish(mut self: Box<Self>) -> anyhow::Result<()> {
+        if let Some(mut stdin) = self.stdin.take() {
+            stdin.flush()?;
+        }
+        let status = self.child.wait()?;
+        if !status.success() {
+            bail!("Encoder exited with {}", status);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+#[cfg(not(target_os = "windows"))]
+fn shell_command(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
+
+/// Quote a path for the shell that runs the encoder command, unless it is plain enough not
+/// to need it
+fn shell_quote(value: &str) -> String {
+    let plain = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-+=.,/:@%".contains(c));
+    if plain {
+        value.to_string()
+    } else if cfg!(target_os = "windows") {
+        // `"` can't be part of a file name on Windows
+        format!("\"{}\"", value)
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+/// Replace the `{placeholders}` in a user given encoder command. Paths are quoted, so they
+/// stay one argument whatever they contain.
+pub fn expand_command(command: &str, config: &VideoConfig) -> String {
+    let audio = config
+        .audio
+        .as_ref()
+        .map(|path| shell_quote(&path.display().to_string()))
+        .unwrap_or_default();
+    command
+        .replace("{width}", &config.width.to_string())
+        .replace("{height}", &config.height.to_string())
+        .replace("{fps}", &config.fps.to_string())
+        .replace(
+            "{output}",
+            &shell_quote(&config.output.display().to_string()),
+        )
+        .replace("{audio}", &audio)
+}
+
+/// Convert RGBA8 to planar YUV 4:2:0 using full range BT.601, which is what `C420jpeg` means.
+/// Chroma is averaged over each 2x2 block.
+pub fn rgba_to_yuv420(rgba: &[u8], width: u32, height: u32) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+    let (width, height) = (width as usize, height as usize);
+    let (chroma_width, chroma_height) = ((width + 1) / 2, (height + 1) / 2);
+
+    let mut y_plane = vec![0u8; width * height];
+    let mut u_sum = vec![0f32; chroma_width * chroma_height];
+    let mut v_sum = vec![0f32; chroma_width * chroma_height];
+    let mut count = vec![0f32; chroma_width * chroma_height];
+
+    for y in 0..height {
+        for x in 0..width {
+            let idx = (y * width + x) * 4;
+            let (r, g, b) = (rgba[idx] as f32, rgba[idx + 1] as f32, rgba[idx + 2] as f32);
+            y_plane[y * width + x] = (0.299 * r + 0.587 * g + 0.114 * b).round() as u8;
+
+            let chroma = (y / 2) * chroma_width + x / 2;
+            u_sum[chroma] += -0.168736 * r - 0.331264 * g + 0.5 * b + 128.0;
+            v_sum[chroma] += 0.5 * r - 0.418688 * g - 0.081312 * b + 128.0;
+            count[chroma] += 1.0;
+        }
+    }
+
+    let average = |sums: Vec<f32>| -> Vec<u8> {
+        sums.iter()
+            .zip(&count)
+            .map(|(sum, n)| (sum / n).round().clamp(0.0, 255.0) as u8)
+            .collect()
+    };
+
+    (y_plane, average(u_sum), average(v_sum))
+}
+
+/// Check that the soundtrack actually is a RIFF/WAVE file
+fn validate_wav(path: &Path) -> anyhow::Result<()> {
+    let mut header = [0u8; 12];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .with_context(|| format!("Failed to read soundtrack {:?}", path))?;
+    if &header[0..4] != b"RIFF" || &header[8..12] != b"WAVE" {
+        bail!("{:?} is not a WAV file", path);
+    }
+    Ok(())
+}
+
+/// Render the output shader, after the buffer passes of a project, to video. Time for each
+/// frame is `frame / fps` no matter how long the frame took to render, so the same command line
+/// always produces the same video.
+pub fn render_video(
+    renderer: &mut Renderer,
+    state: &mut AppState,
+    shader_service: &ShaderService,
+    config: &VideoConfig,
+) -> anyhow::Result<()> {
+    let compiled = shader_service
+        .output_shader()
+        .map_or(false, |shader| shader.program.is_some());
+    if !compiled {
+        bail!("No compiled shader to render");
+    }
+
+    if let Some(audio) = &config.audio {
+        validate_wav(audio)?;
+    }
+
+    let mut sink: Box<dyn FrameSink> = match &config.command {
+        Some(command) => Box::new(PipeWriter::spawn(&expand_command(command, config))?),
+        None => {
+            let file = File::create(&config.output)
+                .with_context(|| format!("Failed to create {:?}", config.output))?;
+            Box::new(Y4mWriter::new(
+                BufWriter::new(file),
+                config.width,
+                config.height,
+                config.fps,
+            )?)
+        }
+    };
+
+    let (width, height) = (config.width as i32, config.height as i32);
+    let scene = RenderTarget::with_format(renderer.gl(), width, height, state.scene_format)
+        .map_err(anyhow::Error::msg)?;
+    let output = RenderTarget::new(renderer.gl(), width, height).map_err(anyhow::Error::msg)?;
+    let resolution = Vec2::new(config.width as f32, config.height as f32);
+    let frame_count = config.frame_count();
+    log::info!(
+        "Video: rendering {} frames at {}x{}@{}",
+        frame_count,
+        config.width,
+        config.height,
+        config.fps
+    );
+
+    state.delta_time = 1.0 / config.fps as f32;
+    for frame in 0..frame_count {
+        state.frame = frame;
+        state.playback_time = frame_time(frame, config.fps);
+
+        renderer.draw_buffers(state, shader_service, width, height);
+        scene.bind();
+        let pass = shader_service.output_index();
+        renderer.draw_pass(shader_service, pass, state, resolution, Vec2::ZERO);
+        renderer.resolve(&scene, &output, state);
+
+        let mut pixels = output.read_rgba8();
+        flip_rows(&mut pixels, config.width, config.height);
+        sink.write_frame(&pixels)?;
+
+        if frame % config.fps == 0 {
+            log::info!("Video: frame {}/{}", frame, frame_count);
+        }
+    }
+    sink.finish()?;
+
+    // Y4M can't carry audio so the soundtrack is put next to it, ready to be muxed
+    if let (Some(audio), None) = (&config.audio, &config.command) {
+        let audio_output = config.output.with_extension("wav");
+        fs::copy(audio, &audio_output)
+            .with_context(|| format!("Failed to copy soundtrack to {:?}", audio_output))?;
+        log::info!("Video: soundtrack copied to {:?}", audio_output);
+    }
+
+    log::info!("Video: done");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand_command, rgba_to_yuv420, FrameSink, VideoConfig, Y4mWriter};
+    use std::path::PathBuf;
+
+    fn config() -> VideoConfig {
+        VideoConfig {
+            output: PathBuf::from("out.y4m"),
+            width: 4,
+            height: 2,
+            fps: 60,
+            duration: 1.5,
+            command: None,
+            audio: Some(PathBuf::from("track.wav")),
+        }
+    }
+
+    #[test]
+    fn frame_count_covers_duration() {
+        assert_eq!(config().frame_count(), 90);
+    }
+
+    #[test]
+    fn command_placeholders() {
+        let cmd = "ffmpeg -s {width}x{height} -r {fps} -i - -i {audio} {output}";
+        assert_eq!(
+            expand_command(cmd, &config()),
+            "ffmpeg -s 4x2 -r 60 -i - -i track.wav out.y4m"
+        );
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn command_paths_are_quoted() {
+        let config = VideoConfig {
+            output: PathBuf::from("my demo; rm -rf ~.mp4"),
+            audio: Some(PathBuf::from("it's.wav")),
+            ..config()
+        };
+        assert_eq!(
+            expand_command("ffmpeg -i {audio} {output}", &config),
+            "ffmpeg -i 'it'\\''s.wav' 'my demo; rm -rf ~.mp4'"
+        );
+    }
+
+    #[test]
+    fn yuv_of_white_and_black() {
+        let mut rgba = vec![255u8; 2 * 2 * 4];
+        rgba.extend([0, 0, 0, 255].repeat(4));
+        let (y, u, v) = rgba_to_yuv420(&rgba, 2, 4);
+        assert_eq!(y, vec![255, 255, 255, 255, 0, 0, 0, 0]);
+        assert_eq!(u, vec![128, 128]);
+        assert_eq!(v, vec![128, 128]);
+    }
+
+    #[test]
+    fn y4m_layout() {
+        let mut buffer = vec![];
+        {
+            let mut writer = Box::new(Y4mWriter::new(&mut buffer, 2, 2, 30).unwrap());
+            writer.write_frame(&[0u8; 16]).unwrap();
+            writer.finish().unwrap();
+        }
+        let header = b"YUV4MPEG2 W2 H2 F30:1 Ip A1:1 C420jpeg\nFRAME\n";
+        assert_eq!(&buffer[..header.len()], header);
+        // 4 luma samples and one sample in each chroma plane
+        assert_eq!(buffer.len(), header.len() + 4 + 1 + 1);
+    }
+}
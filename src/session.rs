@@ -0,0 +1,220 @@
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::camera::{CameraKind, Projection};
+use crate::{Action, AppState, CameraPose, PlayMode, SceneVars};
+
+/// Titles of the floating windows whose positions are kept between sessions
+pub const FLOATING_WINDOWS: [&str; 6] =
+    ["Inspector", "Uniforms", "Probes", "Pick", "Help", "Files"];
+
+/// Where the user left off, restored the next time the same shader or project is opened.
+/// Saved next to it when skuggbox exits, with the last extension replaced by `.session.toml`,
+/// so `tunnel.frag.glsl` gets `tunnel.frag.session.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub playback_time: f32,
+    pub play_mode: PlayMode,
+    pub camera_kind: CameraKind,
+    pub camera_pose: Option<CameraPose>,
+    pub projection: Projection,
+    pub camera_integration: bool,
+    /// `iCamPos`
+    pub camera_pos: Vec3,
+    /// `iMouseDir`
+    pub mouse_dir: Vec3,
+    pub scene_vars: SceneVars,
+    pub project_uniforms: BTreeMap<String, Vec<f32>>,
+    pub layout: UiLayout,
+    pub window: WindowGeometry,
+}
+
+/// Which panels were open and where the floating windows were
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiLayout {
+    pub ui_visible: bool,
+    pub profiler: bool,
+    pub inspector: bool,
+    pub probes: bool,
+    pub picking: bool,
+    pub gizmos: bool,
+    pub help: bool,
+    pub editor: bool,
+    pub editor_overlay: bool,
+    pub file_tree: bool,
+    /// Top left corner of each floating window, by title
+    pub windows: BTreeMap<String, [f32; 2]>,
+}
+
+impl Default for UiLayout {
+    fn default() -> Self {
+        Self {
+            ui_visible: true,
+            profiler: false,
+            inspector: false,
+            probes: false,
+            picking: false,
+            gizmos: false,
+            help: false,
+            editor: false,
+            editor_overlay: false,
+            file_tree: false,
+            windows: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowGeometry {
+    /// Inner size in logical pixels
+    pub size: Option<(u32, u32)>,
+    /// Outer position in physical pixels
+    pub position: Option<(i32, i32)>,
+    pub fullscreen: bool,
+}
+
+impl Session {
+    /// The session file that belongs to a shader or project file
+    pub fn path(main_path: &Path) -> PathBuf {
+        main_path.with_extension("session.toml")
+    }
+
+    /// Load the session saved for a shader or project, `None` if there isn't a usable one
+    pub fn load(main_path: &Path) -> Option<Self> {
+        let path = Self::path(main_path);
+        let content = fs::read_to_string(&path).ok()?;
+        toml::from_str(&content)
+            .map_err(|err| log::warn!("Ignoring invalid session {:?}: {}", path, err))
+            .ok()
+    }
+
+    pub fn save(&self, main_path: &Path) -> anyhow::Result<()> {
+        let path = Self::path(main_path);
+        fs::write(&path, toml::to_string_pretty(self)?)?;
+        log::debug!("Saved session to {:?}", path);
+        Ok(())
+    }
+
+    /// Everything but the window geometry, which the caller knows best
+    pub fn capture(state: &mut AppState, camera_integration: bool) -> Self {
+        Self {
+            playback_time: state.playback_time,
+            play_mode: state.play_mode,
+            camera_kind: state.camera_kind,
+            camera_pose: Some(state.camera_pose()),
+            projection: state.camera.projection().clone(),
+            camera_integration,
+            camera_pos: state.camera_pos,
+            mouse_dir: state.mouse.dir,
+            scene_vars: state.scene_vars.clone(),
+            project_uniforms: state.project_uniforms.clone(),
+            layout: UiLayout {
+                ui_visible: state.ui_visible,
+                profiler: state.profiler_visible,
+                inspector: state.inspector.enabled,
+                probes: state.probes.enabled,
+                picking: state.picking.enabled,
+                gizmos: state.gizmos.enabled,
+                help: state.help_visible,
+                editor: state.editor.open,
+                editor_overlay: state.editor.overlay,
+                file_tree: state.file_tree_visible,
+                windows: state.window_positions.clone(),
+            },
+            window: WindowGeometry::default(),
+        }
+    }
+
+    /// Put the state back the way it was. Things that need the shaders reloaded are pushed as
+    /// actions. Uniforms the project no longer declares are dropped.
+    pub fn restore(&self, state: &mut AppState, actions: &mut Vec<Action>) {
+        state.set_playback_time(self.playback_time);
+        state.play_mode = self.play_mode;
+        state.select_camera(self.camera_kind);
+        *state.camera.projection_mut() = self.projection.clone();
+        if let Some(pose) = &self.camera_pose {
+            state.set_camera_pose(pose);
+        }
+        actions.push(Action::CameraToggleIntegration(self.camera_integration));
+        state.camera_pos = self.camera_pos;
+        state.mouse.dir = self.mouse_dir;
+        state.scene_vars = self.scene_vars.clone();
+        for (name, value) in &self.project_uniforms {
+            if let Some(current) = state.project_uniforms.get_mut(name) {
+                if current.len() == value.len() {
+                    *current = value.clone();
+                }
+            }
+        }
+
+        let layout = &self.layout;
+        state.ui_visible = layout.ui_visible;
+        state.profiler_visible = layout.profiler;
+        state.inspector.enabled = layout.inspector;
+        state.picking.enabled = layout.picking;
+        state.gizmos.enabled = layout.gizmos;
+        state.help_visible = layout.help;
+        state.editor.open = layout.editor;
+        state.editor.overlay = layout.editor_overlay;
+        state.file_tree_visible = layout.file_tree;
+        state.window_positions = layout.windows.clone();
+        if layout.probes != state.probes.enabled {
+            actions.push(Action::ToggleProbes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Session;
+    use crate::camera::CameraKind;
+    use crate::{AppState, PlayMode};
+    use std::path::PathBuf;
+
+    #[test]
+    fn session_path_next_to_shader() {
+        let path = Session::path(&PathBuf::from("shaders/tunnel.glsl"));
+        assert_eq!(path, PathBuf::from("shaders/tunnel.session.toml"));
+    }
+
+    #[test]
+    fn capture_and_restore() {
+        let mut state = AppState::default();
+        state.select_camera(CameraKind::Fly);
+        state.playback_time = 12.5;
+        state.play_mode = PlayMode::Paused;
+        state.scene_vars.color_a = [1.0, 0.5, 0.0];
+        state.project_uniforms.insert("glow".into(), vec![0.75]);
+        state.profiler_visible = true;
+        state
+            .window_positions
+            .insert("Probes".into(), [100.0, 200.0]);
+        let session = Session::capture(&mut state, true);
+
+        let content = toml::to_string_pretty(&session).unwrap();
+        let loaded: Session = toml::from_str(&content).unwrap();
+        assert_eq!(loaded, session);
+
+        let mut restored = AppState::default();
+        restored.project_uniforms.insert("glow".into(), vec![0.5]);
+        let mut actions = vec![];
+        loaded.restore(&mut restored, &mut actions);
+        assert_eq!(restored.camera_kind, CameraKind::Fly);
+        assert_eq!(restored.playback_time, 12.5);
+        assert_eq!(restored.play_mode, PlayMode::Paused);
+        assert_eq!(restored.scene_vars.color_a, [1.0, 0.5, 0.0]);
+        assert_eq!(restored.project_uniforms["glow"], vec![0.75]);
+        assert!(restored.profiler_visible);
+        assert_eq!(restored.window_positions["Probes"], [100.0, 200.0]);
+        assert_eq!(
+            restored.camera_pose().position,
+            state.camera_pose().position
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{CameraBookmark, CameraPath, DisplayTransform, PointHandle, Timeline};
+
+/// Settings that belong to a shader rather than to the app.
+/// Saved next to the shader as `<shader_id>.skuggbox.toml` so they survive restarts.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShaderSettings {
+    pub timeline: Timeline,
+    pub display: DisplayTransform,
+    pub camera_bookmarks: Vec<CameraBookmark>,
+    pub camera_path: CameraPath,
+    pub handles: Vec<PointHandle>,
+}
+
+impl ShaderSettings {
+    pub fn path(shader_path: &Path) -> PathBuf {
+        shader_path.with_extension("skuggbox.toml")
+    }
+
+    /// Load the settings for a shader. Missing or broken files give the defaults.
+    pub fn load(shader_path: &Path) -> Self {
+        let path = Self::path(shader_path);
+        match fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content).unwrap_or_else(|err| {
+                log::warn!("Ignoring invalid settings in {:?}: {}", path, err);
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub fn save(&self, shader_path: &Path) -> anyhow::Result<()> {
+        let path = Self::path(shader_path);
+        fs::write(&path, toml::to_string_pretty(self)?)?;
+        log::debug!("Saved settings to {:?}", path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ShaderSettings;
+    use crate::CameraPose;
+    use std::path::PathBuf;
+
+    #[test]
+    fn settings_path_next_to_shader() {
+        let path = ShaderSettings::path(&PathBuf::from("shaders/tunnel.glsl"));
+        assert_eq!(path, PathBuf::from("shaders/tunnel.skuggbox.toml"));
+    }
+
+    #[test]
+    fn settings_round_trip() {
+        let mut settings = ShaderSettings::default();
+        settings.timeline.add_marker(4.0);
+        settings.timeline.set_loop_start(1.0);
+        settings.timeline.set_speed(0.5);
+        settings.display.exposure = 1.5;
+        settings
+            .camera_path
+            .set_keyframe(2.0, CameraPose::from_transform(glam::Mat4::IDENTITY, 60.0));
+
+        let content = toml::to_string_pretty(&settings).unwrap();
+        let loaded: ShaderSettings = toml::from_str(&content).unwrap();
+        assert_eq!(loaded, settings);
+    }
+}
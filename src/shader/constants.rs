@@ -1,15 +1,80 @@
 pub const SKUGGBOX_CAMERA: &str = "#ifdef USE_SKUGGBOX_CAMERA
     uniform mat4 sbCameraTransform;
+    // tan(fov / 2), aspect, near, far
+    uniform vec4 sbCameraProjection;
+    // half height of the view in world units, 0 for a perspective projection
+    uniform float sbCameraOrthoScale;
+    // focus distance, aperture radius
+    uniform vec2 sbCameraLens;
     void skuggbox_camera(vec2 uv, inout vec3 ro, inout vec3 rd) {
+        mat3 basis = mat3(sbCameraTransform);
         ro = sbCameraTransform[3].xyz;
-        rd = mat3(sbCameraTransform) * normalize(vec3(uv, 1));
+        if (sbCameraOrthoScale > 0.0) {
+            ro += basis * vec3(uv * sbCameraOrthoScale, 0.0);
+            rd = basis[2];
+        } else {
+            rd = basis * normalize(vec3(uv * sbCameraProjection.x, 1.0));
+        }
+        ro += rd * sbCameraProjection.z;
+    }
+    // Depth of field: move the ray origin to a point on the lens, `lensSample` in [-1, 1],
+    // and aim it at the same point on the focus plane. Call after skuggbox_camera.
+    void skuggbox_camera_lens(vec2 lensSample, inout vec3 ro, inout vec3 rd) {
+        mat3 basis = mat3(sbCameraTransform);
+        vec3 focus = ro + rd * (sbCameraLens.x / max(dot(rd, basis[2]), 1e-4));
+        ro += basis * vec3(lensSample * sbCameraLens.y, 0.0);
+        rd = normalize(focus - ro);
     }
     #else
     void skuggbox_camera(vec2 uv, inout vec3 ro, inout vec3 rd) {
         // empty
     }
+    void skuggbox_camera_lens(vec2 lensSample, inout vec3 ro, inout vec3 rd) {
+        // empty
+    }
     #endif";
 
+/// Injected into shaders using `gl_FragCoord`, which is then rewritten to `sbFragCoord`.
+/// Lets us render a shader in tiles, and pan and zoom it with the 2D camera,
+/// without the shader knowing about it.
+pub const SKUGGBOX_TILE_OFFSET: &str = "uniform vec2 sbTileOffset;
+uniform mat3 sbView2D;
+#define sbFragCoord vec4((sbView2D * vec3(gl_FragCoord.xy + sbTileOffset, 1.0)).xy, gl_FragCoord.zw)";
+
+/// Injected into shaders when probing is on. `sbProbe("name", value)` calls are rewritten to
+/// pass a slot index instead of the name. The value only ends up in `sbProbeValue` when the
+/// slot is the one being read back, `sbProbeInfo` holds the component count, if it's an int
+/// and if the probe was reached at all.
+pub const SKUGGBOX_PROBES: &str = "uniform int sbProbeSlot;
+layout(location = 1) out vec4 sbProbeValue;
+layout(location = 2) out vec4 sbProbeInfo;
+void sbProbeWrite(int slot, vec4 value, float components, float isInt) {
+    if (slot == sbProbeSlot) {
+        sbProbeValue = value;
+        sbProbeInfo = vec4(components, isInt, 1.0, 0.0);
+    }
+}
+void sbProbe(int slot, float v) { sbProbeWrite(slot, vec4(v, 0.0, 0.0, 0.0), 1.0, 0.0); }
+void sbProbe(int slot, vec2 v) { sbProbeWrite(slot, vec4(v, 0.0, 0.0), 2.0, 0.0); }
+void sbProbe(int slot, vec3 v) { sbProbeWrite(slot, vec4(v, 0.0), 3.0, 0.0); }
+void sbProbe(int slot, vec4 v) { sbProbeWrite(slot, v, 4.0, 0.0); }
+void sbProbe(int slot, int v) { sbProbeWrite(slot, vec4(float(v), 0.0, 0.0, 0.0), 1.0, 1.0); }";
+
+/// Injected into shaders calling `sbPick`. The hit is written to two extra outputs that are
+/// only read back when the user clicks in pick mode. `sbPickPosition.w` is 0 for no hit,
+/// 1 for a hit and 2 for a hit with an id in `sbPickNormal.w`.
+pub const SKUGGBOX_PICK: &str = "layout(location = 3) out vec4 sbPickPosition;
+layout(location = 4) out vec4 sbPickNormal;
+void sbPick(vec3 position, vec3 normal, float id) {
+    sbPickPosition = vec4(position, 2.0);
+    sbPickNormal = vec4(normal, id);
+}
+void sbPick(vec3 position, vec3 normal, int id) { sbPick(position, normal, float(id)); }
+void sbPick(vec3 position, vec3 normal) {
+    sbPickPosition = vec4(position, 1.0);
+    sbPickNormal = vec4(normal, 0.0);
+}";
+
 pub const VERTEX_SHADER: &str = r#"#version 330 core
                 const vec2 verts[3] = vec2[3](
                 vec2(-1.0f, -1.0f),
@@ -0,0 +1,187 @@
+use std::path::{Path, PathBuf};
+
+/// A line in one of the files that make up a shader, `line` starts at 1
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct SourceLocation {
+    pub path: PathBuf,
+    pub line: usize,
+}
+
+/// Where each line of the pre-processed source came from. Lines skuggbox injects itself have
+/// no location.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd)]
+pub struct SourceMap {
+    lines: Vec<Option<SourceLocation>>,
+}
+
+impl SourceMap {
+    /// Every line of `source` maps to the same line in `path`
+    pub fn for_file(path: &Path, source: &str) -> Self {
+        let lines = (1..=source.lines().count())
+            .map(|line| {
+                Some(SourceLocation {
+                    path: path.to_path_buf(),
+                    line,
+                })
+            })
+            .collect();
+        Self { lines }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Location of a line of the pre-processed source, counting from 1 like the compiler does
+    pub fn locate(&self, line: usize) -> Option<&SourceLocation> {
+        line.checked_sub(1)
+            .and_then(|index| self.lines.get(index))
+            .and_then(Option::as_ref)
+    }
+
+    pub fn push(&mut self, location: Option<SourceLocation>) {
+        self.lines.push(location);
+    }
+
+    pub fn extend(&mut self, other: &SourceMap) {
+        self.lines.extend(other.lines.iter().cloned());
+    }
+
+    /// Make room for `count` injected lines before line index `at`
+    pub fn insert_unmapped(&mut self, at: usize, count: usize) {
+        let at = at.min(self.lines.len());
+        self.lines
+            .splice(at..at, std::iter::repeat(None).take(count));
+    }
+}
+
+/// How bad a diagnostic is, errors sort first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    /// Drivers start warnings with `warning`, everything else is taken as an error
+    pub fn of_message(message: &str) -> Self {
+        if message.to_lowercase().starts_with("warning") {
+            Severity::Warning
+        } else {
+            Severity::Error
+        }
+    }
+}
+
+/// A compiler or pre-processor message for one line
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Diagnostic {
+    /// Line in the pre-processed source, from 1. 0 for messages from the pre-processor.
+    pub line: usize,
+    /// The file and line the user wrote it in, if it wasn't injected by skuggbox
+    pub location: Option<SourceLocation>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Diagnostics from a shader info log, mapped back to the files through `source_map`
+    pub fn from_log(log: &str, source_map: &SourceMap) -> Vec<Self> {
+        parse_compile_log(log)
+            .into_iter()
+            .map(|(line, message)| Diagnostic {
+                line,
+                location: source_map.locate(line).cloned(),
+                severity: Severity::of_message(&message),
+                message,
+            })
+            .collect()
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+/// Line numbers and messages in a shader info log. Understands the Mesa `0:12(5): error: ..`,
+/// NVIDIA `0(12) : error C0000: ..` and `ERROR: 0:12: ..` styles, other lines are skipped.
+pub fn parse_compile_log(log: &str) -> Vec<(usize, String)> {
+    log.lines().filter_map(parse_log_line).collect()
+}
+
+fn parse_log_line(line: &str) -> Option<(usize, String)> {
+    let line = line.trim();
+    let (prefix, rest) = match line.split_once(": ") {
+        // `ERROR: 0:12: message`
+        Some((kind, rest)) if kind == "ERROR" || kind == "WARNING" => {
+            let (position, message) = rest.split_once(": ")?;
+            let (_, number) = position.split_once(':')?;
+            let number = number.trim().parse().ok()?;
+            return Some((
+                number,
+                format!("{}: {}", kind.to_lowercase(), message.trim()),
+            ));
+        }
+        Some(split) => split,
+        None => return None,
+    };
+
+    let prefix = prefix.trim();
+    let number = if let Some((_, rest)) = prefix.split_once(':') {
+        // `0:12(5)`
+        rest.split('(').next()?.parse().ok()?
+    } else {
+        // `0(12)`
+        let (_, rest) = prefix.split_once('(')?;
+        rest.strip_suffix(')')?.parse().ok()?
+    };
+    Some((number, rest.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_compile_log, Diagnostic, Severity, SourceLocation, SourceMap};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn driver_log_styles() {
+        let log = "0:12(5): error: `foo' undeclared\n\
+                   0(7) : error C1008: undefined variable \"bar\"\n\
+                   ERROR: 0:3: 'baz' : syntax error\n\
+                   ERROR: 1 compilation errors.  No code generated.\n";
+        assert_eq!(
+            parse_compile_log(log),
+            vec![
+                (12, "error: `foo' undeclared".to_string()),
+                (7, "error C1008: undefined variable \"bar\"".to_string()),
+                (3, "error: 'baz' : syntax error".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn locations_skip_injected_lines() {
+        let mut map = SourceMap::for_file(Path::new("main.glsl"), "a\nb\nc");
+        map.insert_unmapped(1, 2);
+        assert_eq!(map.len(), 5);
+        assert_eq!(map.locate(2), None);
+        assert_eq!(
+            map.locate(4),
+            Some(&SourceLocation {
+                path: PathBuf::from("main.glsl"),
+                line: 2
+            })
+        );
+
+        let log = "0:5(1): error: nope\n0(1) : warning C7050: unused";
+        let diagnostics = Diagnostic::from_log(log, &map);
+        assert_eq!(diagnostics[0].location.as_ref().unwrap().line, 3);
+        assert_eq!(diagnostics[0].message, "error: nope");
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[1].severity, Severity::Warning);
+    }
+}
@@ -1,10 +1,12 @@
 pub mod constants;
+pub mod diagnostics;
 pub mod pre_processor;
 pub mod program;
 pub mod service;
 pub mod skuggbox_shader;
 
 pub use constants::*;
+pub use diagnostics::*;
 pub use pre_processor::*;
 pub use program::*;
 pub use service::*;
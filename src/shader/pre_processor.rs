@@ -1,11 +1,15 @@
-use crate::{Part, ShaderContent, SKUGGBOX_CAMERA};
-use std::collections::HashSet;
+use crate::{
+    Diagnostic, Part, Severity, ShaderContent, SourceLocation, SourceMap, SKUGGBOX_CAMERA,
+    SKUGGBOX_PICK, SKUGGBOX_PROBES, SKUGGBOX_TILE_OFFSET,
+};
+use std::collections::{BTreeMap, HashSet};
 /// Utility functions to read shader content
 /// and produce the necessary pieces to construct a
 use std::default::Default;
 use std::fs::File;
 use std::io::Read;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 use crate::shader::ShaderError;
 use crate::utils::pragma_shader_name;
@@ -18,16 +22,42 @@ pub enum PragmaDirective {
 #[derive(Clone)]
 pub struct PreProcessorConfig {
     pub use_camera_integration: bool,
+    /// Keep `sbProbe` calls and inject what's needed to read them back.
+    /// When off the calls are removed from the source.
+    pub use_probes: bool,
 }
 
 #[derive(Clone)]
 pub struct PreProcessor {
     pub config: PreProcessorConfig,
+    /// Unsaved editor text that is used instead of the file on disk, by canonical path
+    buffers: BTreeMap<PathBuf, String>,
 }
 
 impl PreProcessor {
     pub fn new(config: PreProcessorConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            buffers: BTreeMap::new(),
+        }
+    }
+
+    /// Use `source` for the file at `path` until the buffer is removed
+    pub fn set_buffer(&mut self, path: PathBuf, source: String) {
+        self.buffers.insert(path, source);
+    }
+
+    /// Go back to the file on disk, returns false if there was no buffer
+    pub fn remove_buffer(&mut self, path: &Path) -> bool {
+        self.buffers.remove(path).is_some()
+    }
+
+    fn read(&self, path: &Path) -> anyhow::Result<String, ShaderError> {
+        let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        match self.buffers.get(&key) {
+            Some(source) => Ok(source.clone()),
+            None => read_file(path.to_path_buf()),
+        }
     }
 
     pub fn load_file(&self, shader_path: &PathBuf) -> ShaderContent {
@@ -42,6 +72,12 @@ impl PreProcessor {
             main_shader_path: shader_path.to_owned(),
             parts: Default::default(),
             shader_src: String::new(),
+            snapshot_src: String::new(),
+            source_map: SourceMap::default(),
+            diagnostics: vec![],
+            probes: vec![],
+            handles: vec![],
+            uses_pick: false,
             ready_to_compile: false,
         };
 
@@ -54,7 +90,26 @@ impl PreProcessor {
                     Err(_) => shader_path.to_owned(),
                 };
                 shader_content.parts.insert(path, main_part.clone());
-                shader_content.shader_src = main_part.shader_src;
+                let (shader_src, probes) = process_probes(
+                    inject_tile_offset(main_part.shader_src.clone()),
+                    self.config.use_probes,
+                );
+                let (shader_src, uses_pick) = process_pick(shader_src);
+
+                // everything injected after the includes goes right after `#version`
+                let mut source_map = main_part.source_map;
+                let injected = shader_src
+                    .lines()
+                    .count()
+                    .saturating_sub(main_part.shader_src.lines().count());
+                source_map.insert_unmapped(after_version(&main_part.shader_src), injected);
+
+                shader_content.shader_src = shader_src;
+                shader_content.snapshot_src = main_part.shader_src;
+                shader_content.source_map = source_map;
+                shader_content.probes = probes;
+                shader_content.uses_pick = uses_pick;
+                shader_content.handles = find_handles(&shader_content.shader_src);
                 shader_content.ready_to_compile = true;
             }
             Err(e) => {
@@ -71,11 +126,13 @@ impl PreProcessor {
         loaded_files: &mut HashSet<PathBuf>,
         shader_path: PathBuf,
     ) -> anyhow::Result<Part, ShaderError> {
-        let result = read_file(shader_path.clone());
+        let result = self.read(&shader_path);
         if result.is_err() {
             return Err(result.err().unwrap());
         }
         let file_contents = result.ok().unwrap();
+        let line_count = file_contents.lines().count();
+        let byte_size = file_contents.len();
 
         // mark the file as read
         loaded_files.insert(shader_path.clone());
@@ -86,15 +143,20 @@ impl PreProcessor {
             .to_str()
             .unwrap()
             .to_string();
-        let shader_source =
+        let (shader_source, source_map, includes) =
             self.process_includes(shader, loaded_files, &shader_path, file_contents);
 
-        let shader_source = self.process_integrations(shader_source);
+        let (shader_source, source_map) = self.process_integrations(shader_source, source_map);
 
         Ok(Part {
             shader_path,
             shader_src: shader_source,
             shader_name,
+            source_map,
+            includes,
+            line_count,
+            byte_size,
+            loaded_at: SystemTime::now(),
         })
     }
 
@@ -104,59 +166,301 @@ impl PreProcessor {
         loaded_files: &mut HashSet<PathBuf>,
         shader_path: &Path,
         source: String,
-    ) -> String {
-        let blocks: Vec<String> = source
-            .lines()
-            .map(|line| {
-                if !is_include_line(line.trim_start()) {
-                    return line.to_string();
-                }
+    ) -> (String, SourceMap, Vec<PathBuf>) {
+        let mut blocks: Vec<String> = vec![];
+        let mut source_map = SourceMap::default();
+        let mut includes: Vec<PathBuf> = vec![];
 
-                let shader_name = pragma_shader_name(line);
-                let base_dir = shader_path.parent().unwrap();
-                let path = base_dir.join(shader_name);
+        for (index, line) in source.lines().enumerate() {
+            let location = Some(SourceLocation {
+                path: shader_path
+                    .canonicalize()
+                    .unwrap_or(shader_path.to_path_buf()),
+                line: index + 1,
+            });
+            if !is_include_line(line.trim_start()) {
+                blocks.push(line.to_string());
+                source_map.push(location);
+                continue;
+            }
 
-                if loaded_files.contains(&path) {
-                    // TODO(mathias): Output this error in the UI
-                    log::warn!("multiple includes of shader: {:?}", path);
-                    return format!("// {}", line);
-                }
+            let shader_name = pragma_shader_name(line);
+            let base_dir = shader_path.parent().unwrap();
+            let path = base_dir.join(&shader_name);
 
-                match self.process_part(shader, loaded_files, path.clone()) {
-                    Ok(part) => {
-                        let source = part.shader_src.clone();
-                        shader.parts.insert(path.canonicalize().unwrap(), part);
-                        source
-                    }
-                    // TODO(mathias): Output this error in the UI
-                    Err(e) => {
-                        log::warn!("failed to load file: {:?}: {:?}", path, e);
-                        format!("// {}", line)
+            if loaded_files.contains(&path) {
+                log::warn!("multiple includes of shader: {:?}", path);
+                shader.diagnostics.push(Diagnostic {
+                    line: 0,
+                    location: location.clone(),
+                    severity: Severity::Warning,
+                    message: format!("warning: {} is already included", shader_name),
+                });
+                blocks.push(format!("// {}", line));
+                source_map.push(location);
+                continue;
+            }
+
+            match self.process_part(shader, loaded_files, path.clone()) {
+                Ok(part) => {
+                    blocks.push(part.shader_src.clone());
+                    // an empty file still takes up the line of the include
+                    if part.source_map.is_empty() {
+                        source_map.push(location);
                     }
+                    source_map.extend(&part.source_map);
+                    let path = path.canonicalize().unwrap();
+                    includes.push(path.clone());
+                    shader.parts.insert(path, part);
+                }
+                Err(e) => {
+                    log::warn!("failed to load file: {:?}: {:?}", path, e);
+                    shader.diagnostics.push(Diagnostic {
+                        line: 0,
+                        location: location.clone(),
+                        severity: Severity::Error,
+                        message: format!("error: can't include {}", shader_name),
+                    });
+                    blocks.push(format!("// {}", line));
+                    source_map.push(location);
+                }
+            }
+        }
+
+        (blocks.join("\n"), source_map, includes)
+    }
+
+    /// Expand `#pragma skuggbox(camera)` when the camera integration is on. The expanded lines
+    /// map to the pragma.
+    pub fn process_integrations(
+        &self,
+        source: String,
+        source_map: SourceMap,
+    ) -> (String, SourceMap) {
+        let mut lines: Vec<String> = vec![];
+        let mut mapped = SourceMap::default();
+
+        for (index, line) in source.lines().enumerate() {
+            let location = source_map.locate(index + 1).cloned();
+            if self.config.use_camera_integration
+                && line.trim().contains("#pragma skuggbox(camera)")
+            {
+                log::info!("Found camera integration in shader code");
+                let expanded = "#define USE_SKUGGBOX_CAMERA\n".to_string() + SKUGGBOX_CAMERA;
+                for _ in expanded.lines() {
+                    mapped.push(location.clone());
                 }
-            })
-            .collect();
+                lines.push(expanded);
+            } else {
+                lines.push(line.to_string());
+                mapped.push(location);
+            }
+        }
+
+        (lines.join("\n"), mapped)
+    }
+}
+
+/// Rewrite `gl_FragCoord` to `sbFragCoord` which includes the `sbTileOffset` uniform.
+/// The declarations are placed right after the `#version` directive, if there is one.
+pub fn inject_tile_offset(source: String) -> String {
+    if !source.contains("gl_FragCoord") {
+        return source;
+    }
+
+    let source = source.replace("gl_FragCoord", "sbFragCoord");
+    let insert_at = after_version(&source);
+    let mut lines: Vec<&str> = source.lines().collect();
+    lines.insert(insert_at, SKUGGBOX_TILE_OFFSET);
+    lines.join("\n")
+}
+
+/// Names of the `vec3` uniforms marked with `#pragma skuggbox(handle: name)`, in source order
+pub fn find_handles(source: &str) -> Vec<String> {
+    let mut names: Vec<String> = vec![];
+    for line in source.lines() {
+        let name = line
+            .trim()
+            .strip_prefix("#pragma skuggbox(handle:")
+            .and_then(|rest| rest.split_once(')'))
+            .map(|(name, _)| name.trim());
+        if let Some(name) = name {
+            if !name.is_empty() && !names.iter().any(|known| known == name) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names
+}
+
+/// A `sbProbe("name", value);` statement found in the source
+struct ProbeCall {
+    /// Byte range of the whole statement, including the semicolon
+    start: usize,
+    end: usize,
+    name: String,
+    /// Byte range of the value expression
+    value: (usize, usize),
+}
+
+/// Find the next `sbProbe("name", value);` statement starting at byte `from`.
+/// Calls that don't look like that, e.g. the overloads we inject ourselves, are skipped.
+fn find_probe_call(source: &str, from: usize) -> Option<ProbeCall> {
+    const CALL: &str = "sbProbe(";
+    let bytes = source.as_bytes();
+    let mut search = from;
+
+    while let Some(offset) = source[search..].find(CALL) {
+        let start = search + offset;
+        search = start + CALL.len();
+
+        let preceded_by_ident =
+            start > 0 && (bytes[start - 1].is_ascii_alphanumeric() || bytes[start - 1] == b'_');
+        if preceded_by_ident {
+            continue;
+        }
+
+        // "name",
+        let rest = &source[search..];
+        let quoted = rest.trim_start();
+        if !quoted.starts_with('"') {
+            continue;
+        }
+        let name_start = search + (rest.len() - quoted.len()) + 1;
+        let name_len = match source[name_start..].find('"') {
+            Some(len) => len,
+            None => continue,
+        };
+        let after_name = name_start + name_len + 1;
+        let comma = &source[after_name..];
+        if !comma.trim_start().starts_with(',') {
+            continue;
+        }
+        let value_start = after_name + (comma.len() - comma.trim_start().len()) + 1;
+
+        // value up to the matching parenthesis
+        let mut depth = 1;
+        let mut value_end = None;
+        for (i, c) in source[value_start..].char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                value_end = Some(value_start + i);
+                break;
+            }
+        }
+        let value_end = match value_end {
+            Some(end) => end,
+            None => continue,
+        };
 
-        blocks.join("\n")
+        let tail = &source[value_end + 1..];
+        if !tail.trim_start().starts_with(';') {
+            continue;
+        }
+        let end = value_end + 1 + (tail.len() - tail.trim_start().len()) + 1;
+
+        return Some(ProbeCall {
+            start,
+            end,
+            name: source[name_start..name_start + name_len].to_string(),
+            value: (value_start, value_end),
+        });
     }
+    None
+}
+
+/// Rewrite `sbProbe("name", value);` statements. When `enabled` the names are replaced by slot
+/// indices and the probe outputs are injected, otherwise the statements are removed.
+/// Returns the new source and the probe names in slot order.
+pub fn process_probes(source: String, enabled: bool) -> (String, Vec<String>) {
+    let mut names: Vec<String> = vec![];
+    let mut result = String::with_capacity(source.len());
+    let mut position = 0;
 
-    pub fn process_integrations(&self, source: String) -> String {
-        let src = source
-            .lines()
-            .map(|line| {
-                if self.config.use_camera_integration
-                    && line.trim().contains("#pragma skuggbox(camera)")
-                {
-                    log::info!("Found camera integration in shader code");
-                    return "#define USE_SKUGGBOX_CAMERA\n".to_string() + SKUGGBOX_CAMERA;
+    while let Some(call) = find_probe_call(&source, position) {
+        result.push_str(&source[position..call.start]);
+        if enabled {
+            let slot = match names.iter().position(|name| *name == call.name) {
+                Some(slot) => slot,
+                None => {
+                    names.push(call.name.clone());
+                    names.len() - 1
                 }
-                line.to_string()
-            })
-            .collect::<Vec<String>>()
-            .join("\n");
+            };
+            let value = source[call.value.0..call.value.1].trim();
+            result.push_str(&format!("sbProbe({}, {});", slot, value));
+        }
+        // keep line numbers intact for error messages
+        let newlines = source[call.start..call.end].matches('\n').count();
+        result.push_str(&"\n".repeat(newlines));
+        position = call.end;
+    }
+    result.push_str(&source[position..]);
+
+    if !enabled || names.is_empty() {
+        return (result, names);
+    }
+
+    // probes that are never reached should read back as such
+    let result = inject_outputs(
+        result,
+        SKUGGBOX_PROBES,
+        " sbProbeValue = vec4(0.0); sbProbeInfo = vec4(0.0);",
+    );
+    (result, names)
+}
 
-        src
+/// Inject the `sbPick` outputs if the shader calls it. Returns the new source and whether
+/// the shader supports picking.
+pub fn process_pick(source: String) -> (String, bool) {
+    if !source.contains("sbPick(") {
+        return (source, false);
     }
+    // pixels that miss don't call sbPick
+    let result = inject_outputs(
+        source,
+        SKUGGBOX_PICK,
+        " sbPickPosition = vec4(0.0); sbPickNormal = vec4(0.0);",
+    );
+    (result, true)
+}
+
+/// Add extra fragment outputs: `declarations` go right after `#version`, `init` at the start
+/// of `main` and the shader's own output is pinned to location 0 so ours can take the others.
+fn inject_outputs(source: String, declarations: &str, init: &str) -> String {
+    let mut lines: Vec<String> = source.lines().map(str::to_string).collect();
+
+    if let Some(line) = lines.iter_mut().find(|line| {
+        let line = line.trim_start();
+        line.starts_with("out ") && line.contains("vec4")
+    }) {
+        *line = format!("layout(location = 0) {}", line.trim_start());
+    }
+
+    if let Some(line) = lines
+        .iter_mut()
+        .find(|line| line.contains("void main") && line.contains('{'))
+    {
+        let brace = line.find('{').unwrap_or_default();
+        line.insert_str(brace + 1, init);
+    }
+
+    let insert_at = after_version(&source);
+    lines.insert(insert_at, declarations.to_string());
+
+    lines.join("\n")
+}
+
+/// Index of the line after the `#version` directive, where injected declarations go
+fn after_version(source: &str) -> usize {
+    source
+        .lines()
+        .position(|line| line.trim_start().starts_with("#version"))
+        .map_or(0, |idx| idx + 1)
 }
 
 fn is_include_line(s: &str) -> bool {
@@ -175,3 +479,161 @@ fn read_file(shader_path: PathBuf) -> anyhow::Result<String, ShaderError> {
     file.read_to_string(&mut s).unwrap();
     Ok(s)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        find_handles, inject_tile_offset, process_pick, process_probes, PreProcessor,
+        PreProcessorConfig,
+    };
+    use crate::{TempPath, SKUGGBOX_TILE_OFFSET};
+    use std::fs;
+
+    #[test]
+    fn tile_offset_after_version() {
+        let source = "#version 330 core\nout vec4 c;\nvoid main() { c = gl_FragCoord; }";
+        let result = inject_tile_offset(source.to_string());
+        let lines: Vec<&str> = result.lines().collect();
+        assert_eq!(lines[0], "#version 330 core");
+        assert_eq!(lines[1], "uniform vec2 sbTileOffset;");
+        assert!(result.contains("c = sbFragCoord;"));
+        // only the define itself still refers to gl_FragCoord
+        assert!(!lines[4..].iter().any(|line| line.contains("gl_FragCoord")));
+    }
+
+    #[test]
+    fn probes_get_slots() {
+        let source = "#version 330 core\nout vec4 c;\nvoid main() {\n    float d = 1.0;\n    sbProbe(\"dist\", d);\n    sbProbe( \"uv\" , vec2(d, f(d)) );\n    sbProbe(\"dist\", d * 2.0);\n}";
+        let (result, names) = process_probes(source.to_string(), true);
+
+        assert_eq!(names, vec!["dist", "uv"]);
+        assert!(result.contains("sbProbe(0, d);"));
+        assert!(result.contains("sbProbe(1, vec2(d, f(d)));"));
+        assert!(result.contains("sbProbe(0, d * 2.0);"));
+        assert!(result.contains("layout(location = 0) out vec4 c;"));
+        assert!(result.contains("void main() { sbProbeValue = vec4(0.0);"));
+        assert_eq!(result.lines().nth(1), Some("uniform int sbProbeSlot;"));
+    }
+
+    #[test]
+    fn probes_removed_when_disabled() {
+        let source = "void main() {\n    sbProbe(\"a\",\n        1);\n    x = 1;\n}";
+        let (result, names) = process_probes(source.to_string(), false);
+
+        assert!(names.is_empty());
+        assert!(!result.contains("sbProbe"));
+        assert_eq!(result.lines().count(), source.lines().count());
+        assert_eq!(result.lines().nth(3), Some("    x = 1;"));
+    }
+
+    #[test]
+    fn pick_outputs_only_when_used() {
+        let source = "#version 330 core\nout vec4 c;\nvoid main() {\n    sbPick(p, n, 3);\n}";
+        let (result, uses_pick) = process_pick(source.to_string());
+        assert!(uses_pick);
+        assert!(result.contains("layout(location = 4) out vec4 sbPickNormal;"));
+        assert!(result.contains("layout(location = 0) out vec4 c;"));
+        assert!(result.contains("void main() { sbPickPosition = vec4(0.0);"));
+
+        let (probed, _) = process_probes("#version 330 core\nout vec4 c;\nvoid main() {\n    sbProbe(\"a\", 1.0);\n    sbPick(p, n);\n}".to_string(), true);
+        let (result, _) = process_pick(probed);
+        assert_eq!(result.matches("layout(location = 0)").count(), 1);
+
+        let plain = "void main() {}".to_string();
+        assert_eq!(process_pick(plain.clone()), (plain, false));
+    }
+
+    #[test]
+    fn handles_from_pragmas() {
+        let source = "uniform vec3 light;\n#pragma skuggbox(handle: light)\n  #pragma skuggbox(handle:center )\n#pragma skuggbox(camera)\n#pragma skuggbox(handle: light)";
+        assert_eq!(find_handles(source), vec!["light", "center"]);
+    }
+
+    #[test]
+    fn source_map_follows_includes_and_injections() {
+        let dir = TempPath::new("source-map-test");
+        fs::create_dir_all(&dir.0).unwrap();
+        let common = dir.0.join("common.glsl");
+        let main = dir.0.join("main.glsl");
+        fs::write(&common, "float a = 1.0;\nfloat b = 2.0;\n").unwrap();
+        fs::write(
+            &main,
+            "#version 330 core\n#pragma include('common.glsl')\nout vec4 c;\nvoid main() { c = gl_FragCoord; }",
+        )
+        .unwrap();
+
+        let mut pre_processor = PreProcessor::new(PreProcessorConfig {
+            use_camera_integration: false,
+            use_probes: false,
+        });
+        let content = pre_processor.load_file(&main);
+        let lines: Vec<&str> = content.shader_src.lines().collect();
+        let main_line = lines
+            .iter()
+            .position(|line| line.contains("void main"))
+            .unwrap();
+        let b_line = lines
+            .iter()
+            .position(|line| line.contains("float b"))
+            .unwrap();
+
+        let location = content.source_map.locate(main_line + 1).unwrap();
+        assert_eq!(location.path, main.canonicalize().unwrap());
+        assert_eq!(location.line, 4);
+        let location = content.source_map.locate(b_line + 1).unwrap();
+        assert_eq!(location.path, common.canonicalize().unwrap());
+        assert_eq!(location.line, 2);
+        // the tile offset declarations
+        assert_eq!(content.source_map.locate(2), None);
+
+        let main_part = &content.parts[&main.canonicalize().unwrap()];
+        assert_eq!(main_part.includes, vec![common.canonicalize().unwrap()]);
+        assert_eq!(main_part.line_count, 4);
+        assert_eq!(content.parts[&common.canonicalize().unwrap()].byte_size, 30);
+
+        // unsaved text wins over the file
+        pre_processor.set_buffer(common.canonicalize().unwrap(), "float a = 3.0;".to_string());
+        let content = pre_processor.load_file(&main);
+        assert!(content.shader_src.contains("float a = 3.0;"));
+        assert!(!content.shader_src.contains("float b"));
+
+        // missing includes are reported at the include line
+        fs::write(&main, "#version 330 core\n#pragma include('missing.glsl')").unwrap();
+        let content = pre_processor.load_file(&main);
+        assert_eq!(content.diagnostics.len(), 1);
+        assert_eq!(content.diagnostics[0].location.as_ref().unwrap().line, 2);
+    }
+
+    #[test]
+    fn snapshot_source_loads_again() {
+        let dir = TempPath::new("snapshot-source-test");
+        fs::create_dir_all(&dir.0).unwrap();
+        let main = dir.0.join("main.glsl");
+        let snapshot = dir.0.join("snapshot.glsl");
+        fs::write(dir.0.join("common.glsl"), "float a = 1.0;").unwrap();
+        fs::write(
+            &main,
+            "#version 330 core\n#pragma include('common.glsl')\nout vec4 c;\nvoid main() { c = gl_FragCoord * a; }",
+        )
+        .unwrap();
+
+        let pre_processor = PreProcessor::new(PreProcessorConfig {
+            use_camera_integration: false,
+            use_probes: false,
+        });
+        let content = pre_processor.load_file(&main);
+        assert!(content.snapshot_src.contains("float a = 1.0;"));
+        assert!(!content.snapshot_src.contains("sbFragCoord"));
+
+        fs::write(&snapshot, &content.snapshot_src).unwrap();
+        let reloaded = pre_processor.load_file(&snapshot);
+        assert_eq!(reloaded.shader_src, content.shader_src);
+        assert_eq!(reloaded.shader_src.matches(SKUGGBOX_TILE_OFFSET).count(), 1);
+    }
+
+    #[test]
+    fn tile_offset_untouched_without_frag_coord() {
+        let source = "#version 330 core\nvoid main() {}".to_string();
+        assert_eq!(inject_tile_offset(source.clone()), source);
+    }
+}
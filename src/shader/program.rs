@@ -1,6 +1,7 @@
-use crate::VERTEX_SHADER;
+use crate::{MAX_CHANNELS, VERTEX_SHADER};
 use egui::TextBuffer;
 use glow::{HasContext, Program, UniformLocation};
+use std::collections::BTreeMap;
 use std::ffi::CString;
 use std::fmt::Formatter;
 
@@ -26,6 +27,15 @@ impl From<String> for ShaderError {
     }
 }
 
+impl ShaderError {
+    /// The compiler log or file error without the variant around it
+    pub fn message(&self) -> &str {
+        match self {
+            ShaderError::CompilationError { error } | ShaderError::FileError { error } => error,
+        }
+    }
+}
+
 impl std::fmt::Display for ShaderError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}", self)
@@ -37,6 +47,7 @@ pub struct ShaderUniformLocations {
     pub resolution: Option<UniformLocation>,
     pub time: Option<UniformLocation>,
     pub time_delta: Option<UniformLocation>,
+    pub frame: Option<UniformLocation>,
     pub mouse: Option<UniformLocation>,
     /// Direction of the mouse movement in vec2([-1.0, 0.0, 1.0], [-1.0, 0.0, 1.0])
     pub mouse_dir: Option<UniformLocation>,
@@ -44,7 +55,26 @@ pub struct ShaderUniformLocations {
     /// For more full control over the camera, use the `sb_camera_transform` instead
     pub cam_pos: Option<UniformLocation>,
     pub sb_camera_transform: Option<UniformLocation>,
+    /// tan(fov / 2), aspect, near and far of the camera
+    pub sb_camera_projection: Option<UniformLocation>,
+    pub sb_camera_ortho_scale: Option<UniformLocation>,
+    /// Focus distance and aperture for depth of field
+    pub sb_camera_lens: Option<UniformLocation>,
     pub sb_color_a: Option<UniformLocation>,
+    /// Offset added to `gl_FragCoord` when rendering in tiles
+    pub sb_tile_offset: Option<UniformLocation>,
+    /// Pan and zoom of the 2D camera, identity for the other cameras
+    pub sb_view_2d: Option<UniformLocation>,
+    /// Which `sbProbe` slot is read back, only there when probing is on
+    pub sb_probe_slot: Option<UniformLocation>,
+    /// Beats since the start of the timeline, 0 without a BPM
+    pub sb_beat: Option<UniformLocation>,
+    /// `iChannel0` to `iChannel3`
+    pub channels: [Option<UniformLocation>; MAX_CHANNELS],
+    /// The `vec3` uniforms with a gizmo handle, by name
+    pub handles: BTreeMap<String, UniformLocation>,
+    /// Uniforms declared in the project file, by name
+    pub project: BTreeMap<String, UniformLocation>,
 }
 
 #[derive(Clone)]
@@ -88,27 +118,65 @@ impl ShaderProgram {
         let time = gl.get_uniform_location(program, "iTime");
         let resolution = gl.get_uniform_location(program, "iResolution");
         let time_delta = gl.get_uniform_location(program, "iTimeDelta");
+        let frame = gl.get_uniform_location(program, "iFrame");
         let mouse = gl.get_uniform_location(program, "iMouse");
         let mouse_dir = gl.get_uniform_location(program, "iMouseDir");
         let cam_pos = gl.get_uniform_location(program, "iCamPos");
         let sb_camera_transform = gl.get_uniform_location(program, "sbCameraTransform");
+        let sb_camera_projection = gl.get_uniform_location(program, "sbCameraProjection");
+        let sb_camera_ortho_scale = gl.get_uniform_location(program, "sbCameraOrthoScale");
+        let sb_camera_lens = gl.get_uniform_location(program, "sbCameraLens");
         let sb_color_a = gl.get_uniform_location(program, "sbColorA");
+        let sb_tile_offset = gl.get_uniform_location(program, "sbTileOffset");
+        let sb_probe_slot = gl.get_uniform_location(program, "sbProbeSlot");
+        let sb_view_2d = gl.get_uniform_location(program, "sbView2D");
+        let sb_beat = gl.get_uniform_location(program, "sbBeat");
+        let channels =
+            std::array::from_fn(|i| gl.get_uniform_location(program, &format!("iChannel{}", i)));
 
         let locations = ShaderUniformLocations {
             resolution,
             time,
             time_delta,
+            frame,
             mouse,
             mouse_dir,
             cam_pos,
             sb_camera_transform,
+            sb_camera_projection,
+            sb_camera_ortho_scale,
+            sb_camera_lens,
             sb_color_a,
+            sb_tile_offset,
+            sb_probe_slot,
+            sb_view_2d,
+            sb_beat,
+            channels,
+            handles: BTreeMap::new(),
+            project: BTreeMap::new(),
         };
 
         log::debug!("shader locations {:?}", locations);
 
         locations
     }
+
+    /// # Safety
+    ///
+    /// Locations of uniforms the program actually has, out of `names`
+    pub unsafe fn named_uniform_locations<'a>(
+        gl: &glow::Context,
+        program: Program,
+        names: impl IntoIterator<Item = &'a String>,
+    ) -> BTreeMap<String, UniformLocation> {
+        names
+            .into_iter()
+            .filter_map(|name| {
+                gl.get_uniform_location(program, name)
+                    .map(|location| (name.clone(), location))
+            })
+            .collect()
+    }
 }
 
 pub(crate) unsafe fn compile_shader(
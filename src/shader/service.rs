@@ -1,23 +1,35 @@
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::Arc;
 use std::{fs, thread};
 use time::format_description;
 
 use crate::shader::PreProcessor;
-use crate::{PreProcessorConfig, ShaderError, SkuggboxShader};
+use crate::{Diagnostic, PreProcessorConfig, Project, ShaderError, SkuggboxShader, SourceLocation};
 
 /// The ShaderService handles the inputted shader files, constructs an OpenGL compatible shader
 /// as well as builds up a pre-processor for inlining include files etc.
 /// It also holds all file data around the used shaders to be used for reloading.
 pub struct ShaderService {
+    gl: Arc<glow::Context>,
     /// All the shader constructs we're using in this setup.
-    /// Contains the pre-processor and everything else to build and reload a shader
+    /// Contains the pre-processor and everything else to build and reload a shader.
+    /// With a project there is one shader per pass, in the same order.
     pub shaders: Vec<SkuggboxShader>,
+    /// The project file the shaders came from, if any
+    pub project: Option<Project>,
     pub use_camera_integration: bool,
     /// Two way channels for listening and reacting to changes in our shader files
     pre_processor: PreProcessor,
+    sender: Option<Sender<PathBuf>>,
     receiver: Option<Receiver<PathBuf>>,
+    /// Files that already have a watcher
+    watched: Vec<PathBuf>,
+    /// What the editor last wrote to each file, so the watcher doesn't reload our own saves
+    written: BTreeMap<PathBuf, String>,
+    /// Files that changed on disk since the editor last asked
+    changed_files: Vec<PathBuf>,
     pub last_error: Option<ShaderError>,
 }
 
@@ -25,54 +37,125 @@ impl ShaderService {
     pub fn new(gl: Arc<glow::Context>, shader_files: Vec<PathBuf>) -> Self {
         let pre_processor_config = PreProcessorConfig {
             use_camera_integration: false,
+            use_probes: false,
         };
 
         let pre_processor = PreProcessor::new(pre_processor_config);
-        let shaders = SkuggboxShader::from_files(gl, &pre_processor, shader_files);
+        let shaders = SkuggboxShader::from_files(gl.clone(), &pre_processor, shader_files);
 
         Self {
+            gl,
             pre_processor,
             shaders,
+            project: None,
             use_camera_integration: false,
+            sender: None,
             receiver: None,
+            watched: vec![],
+            written: BTreeMap::new(),
+            changed_files: vec![],
             last_error: None,
         }
     }
 
-    pub fn watch(&mut self) {
-        let (sender, receiver): (Sender<PathBuf>, Receiver<PathBuf>) = channel();
+    /// One shader per pass of the project
+    pub fn from_project(gl: Arc<glow::Context>, project: Project) -> Self {
+        let mut service = Self::new(gl, project.shader_files());
+        service.project = Some(project);
+        service
+    }
 
-        self.receiver = Some(receiver);
+    /// Watch all shader files, and the project file. Can be called again to also watch files
+    /// that were added since.
+    pub fn watch(&mut self) {
+        let sender = match &self.sender {
+            Some(sender) => sender.clone(),
+            None => {
+                let (sender, receiver): (Sender<PathBuf>, Receiver<PathBuf>) = channel();
+                self.receiver = Some(receiver);
+                self.sender = Some(sender.clone());
+                sender
+            }
+        };
 
-        let all_shader_files = self
+        let new_files: Vec<PathBuf> = self
             .shaders
             .iter()
             .flat_map(|shader| shader.get_all_files())
+            .chain(self.project.as_ref().map(|project| &project.path))
+            .filter(|path| !self.watched.contains(path))
             .cloned()
             .collect();
+        if new_files.is_empty() {
+            return;
+        }
+        self.watched.extend(new_files.iter().cloned());
 
         let _ = thread::spawn(move || {
-            glsl_watcher::watch_all(sender, all_shader_files);
+            glsl_watcher::watch_all(sender, new_files);
         });
     }
 
+    fn is_project_file(&self, path: &PathBuf) -> bool {
+        self.project
+            .as_ref()
+            .and_then(|project| fs::canonicalize(&project.path).ok())
+            .map_or(false, |project_path| project_path.eq(path))
+    }
+
+    /// Load the project file again. The shaders are rebuilt if the passes changed, a broken
+    /// project is reported and the old one is kept.
+    fn reload_project(&mut self) {
+        let path = match &self.project {
+            Some(project) => project.path.clone(),
+            None => return,
+        };
+        log::debug!("Reloading project {:?}", path);
+
+        match Project::load(&path) {
+            Ok(project) => {
+                let shader_files = project.shader_files();
+                let passes_changed = self
+                    .project
+                    .as_ref()
+                    .map_or(true, |old| old.shader_files() != shader_files);
+                if passes_changed {
+                    self.shaders = SkuggboxShader::from_files(
+                        self.gl.clone(),
+                        &self.pre_processor,
+                        shader_files,
+                    );
+                }
+                if !passes_changed {
+                    for shader in self.shaders.iter_mut() {
+                        shader.find_project_uniforms(&self.gl, Some(&project));
+                    }
+                }
+                self.project = Some(project);
+                self.last_error = None;
+                self.watch();
+            }
+            Err(err) => {
+                log::error!("{:?}", err);
+                self.last_error = Some(ShaderError::FileError {
+                    error: format!("{:#}", err),
+                });
+            }
+        }
+    }
+
     /// This method should be called from the GL-thread.
     /// It is basically the same as watching for file changes and the
     /// reload the shaders whenever that happens.
     pub fn run(&mut self, gl: &glow::Context) -> Result<(), ShaderError> {
         // pull file updates from the channel
-        if let Some(recv) = &self.receiver {
-            if let Ok(changed_path_buf) = recv.try_recv() {
-                for shader in self.shaders.iter_mut() {
-                    if shader.uses_file(&changed_path_buf) {
-                        let main_shader_path = shader.get_main_shader_path();
-                        log::debug!("Reloading shader {:?}", main_shader_path);
-
-                        let reloaded_sahder = self.pre_processor.load_file(main_shader_path);
-                        shader.mark_for_recompilation(reloaded_sahder);
-                    }
-                }
+        let changed = self.receiver.as_ref().and_then(|recv| recv.try_recv().ok());
+        if let Some(changed_path_buf) = changed.filter(|path| !self.is_own_write(path)) {
+            if self.is_project_file(&changed_path_buf) {
+                self.reload_project();
             }
+            self.changed_files.push(changed_path_buf.clone());
+            self.reload_users_of(&changed_path_buf);
         };
 
         for shader in self.shaders.iter_mut() {
@@ -80,7 +163,7 @@ impl ShaderService {
                 match shader.try_to_compile() {
                     Ok(_) => {
                         log::debug!("Shader compiled");
-                        shader.find_shader_uniforms(gl);
+                        shader.find_shader_uniforms(gl, self.project.as_ref());
                         self.last_error = None;
                     }
                     Err(e) => {
@@ -93,6 +176,93 @@ impl ShaderService {
         Ok(())
     }
 
+    fn reload_users_of(&mut self, path: &PathBuf) {
+        for shader in self.shaders.iter_mut() {
+            if shader.uses_file(path) {
+                let main_shader_path = shader.get_main_shader_path();
+                log::debug!("Reloading shader {:?}", main_shader_path);
+
+                let reloaded_shader = self.pre_processor.load_file(main_shader_path);
+                shader.mark_for_recompilation(reloaded_shader);
+            }
+        }
+    }
+
+    /// The watcher reporting a save from the editor, the file still holds what we wrote
+    fn is_own_write(&self, path: &Path) -> bool {
+        self.written.get(path).map_or(false, |source| {
+            fs::read_to_string(path).ok().as_ref() == Some(source)
+        })
+    }
+
+    /// Compile the shaders that use `path` with unsaved text from the editor
+    pub fn set_buffer(&mut self, path: &Path, source: String) {
+        self.pre_processor.set_buffer(path.to_path_buf(), source);
+        self.reload_users_of(&path.to_path_buf());
+    }
+
+    /// Write editor text to disk and compile it. The watcher event that follows is ignored.
+    pub fn save_buffer(&mut self, path: &Path, source: String) -> std::io::Result<()> {
+        fs::write(path, &source)?;
+        log::info!("Saved {:?}", path);
+        self.pre_processor.remove_buffer(path);
+        self.written.insert(path.to_path_buf(), source);
+        self.reload_users_of(&path.to_path_buf());
+        Ok(())
+    }
+
+    /// Go back to the file on disk after the editor dropped its unsaved text
+    pub fn discard_buffer(&mut self, path: &Path) {
+        if self.pre_processor.remove_buffer(path) {
+            self.reload_users_of(&path.to_path_buf());
+        }
+    }
+
+    /// Files that changed on disk since the last call
+    pub fn take_changed_files(&mut self) -> Vec<PathBuf> {
+        std::mem::take(&mut self.changed_files)
+    }
+
+    /// Every file the shaders are made of. The main file of each shader comes first, then
+    /// the includes.
+    pub fn source_files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = vec![];
+        let main_files = self.shaders.iter().map(|shader| {
+            let path = shader.get_main_shader_path();
+            path.canonicalize().unwrap_or_else(|_| path.clone())
+        });
+        let parts = self
+            .shaders
+            .iter()
+            .flat_map(|shader| shader.get_all_files())
+            .cloned();
+        for path in main_files.chain(parts) {
+            if !files.contains(&path) {
+                files.push(path);
+            }
+        }
+        files
+    }
+
+    /// Include problems and messages from the last failed compile of every shader
+    pub fn diagnostics(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.shaders.iter().flat_map(|shader| {
+            shader
+                .content
+                .diagnostics
+                .iter()
+                .chain(shader.diagnostics.iter())
+        })
+    }
+
+    /// Where the first error with a known file and line is, in the file the user wrote it in.
+    /// Warnings are skipped.
+    pub fn first_error_location(&self) -> Option<&SourceLocation> {
+        self.diagnostics()
+            .filter(|diagnostic| diagnostic.is_error())
+            .find_map(|diagnostic| diagnostic.location.as_ref())
+    }
+
     /// Reloading re-constructs all shaders.
     pub fn reload(&mut self, config: PreProcessorConfig) {
         self.pre_processor.config = config;
@@ -102,6 +272,31 @@ impl ShaderService {
         }
     }
 
+    pub fn pre_processor_config(&self) -> PreProcessorConfig {
+        self.pre_processor.config.clone()
+    }
+
+    /// Path of the project, or of the first shader without one.
+    /// This is what settings and outputs are stored next to.
+    pub fn main_path(&self) -> Option<&PathBuf> {
+        match &self.project {
+            Some(project) => Some(&project.path),
+            None => self
+                .shaders
+                .first()
+                .map(|shader| shader.get_main_shader_path()),
+        }
+    }
+
+    /// Index of the shader that is drawn to the screen, the last pass of a project
+    pub fn output_index(&self) -> usize {
+        self.project.as_ref().map_or(0, Project::output_index)
+    }
+
+    pub fn output_shader(&self) -> Option<&SkuggboxShader> {
+        self.shaders.get(self.output_index())
+    }
+
     pub fn source(&self) {
         for shader in &self.shaders {
             log::info!("{}", shader.content.shader_id);
@@ -109,44 +304,71 @@ impl ShaderService {
         }
     }
 
+    /// Returns a timestamped path inside `dir_name` next to the main path, i.e.
+    /// `shader_dir/<dir_name>/<prefix>-<datetime>.<extension>`.
+    /// The directory is created if it doesn't exist yet.
+    pub fn timestamped_path(
+        &self,
+        dir_name: &str,
+        prefix: &str,
+        extension: &str,
+    ) -> Option<PathBuf> {
+        let path = self.main_path()?.parent()?;
+        let output_dir = path.join(dir_name);
+
+        // Create directory if it doesn't exist
+        if !output_dir.is_dir() {
+            fs::create_dir(output_dir.clone()).expect("Failed to create output dir");
+            log::debug!("Created output dir {:?}", output_dir);
+        }
+
+        let format =
+            format_description::parse("[year][month][day]_[hour][minute][second]").unwrap();
+
+        let datetime = time::OffsetDateTime::now_local()
+            .unwrap()
+            .format(&format)
+            .unwrap();
+
+        Some(output_dir.join(format!("{}-{}.{}", prefix, datetime, extension)))
+    }
+
     /// Saves the current state to disk
     /// Make a `snapshots` dir inside the current directory and save the file with
-    /// datetime.glsl
+    /// datetime.glsl. A project is saved as a `datetime` dir that can be opened as a project.
     /// TODO(mathias): Save diff instead of the whole file and allow going back-forth between diffs
     pub fn save_snapshot(&self) {
         if self.last_error.is_none() {
             log::debug!("Snapshot: save as");
 
+            if let Some(project) = &self.project {
+                let sources: Vec<String> = self
+                    .shaders
+                    .iter()
+                    .map(|shader| shader.content.snapshot_src.clone())
+                    .collect();
+                let dir = self
+                    .timestamped_path("snapshots", "snapshot", "glsl")
+                    .map(|path| path.with_extension(""));
+                if let Some(dir) = dir {
+                    match project.save_snapshot(&dir, &sources) {
+                        Ok(path) => log::info!("Snapshot: Saved project to {:?}", path),
+                        Err(err) => log::error!("Snapshot: Failed to save {:?}: {:?}", dir, err),
+                    }
+                }
+                return;
+            }
+
             let source = self
                 .shaders
                 .iter()
-                .map(|shader| shader.content.shader_src.clone())
+                .map(|shader| shader.content.snapshot_src.clone())
                 .collect::<Vec<String>>()
                 .join("");
 
-            // create dir if it doesn't exist yet
-            let base_shader = self.shaders.first().unwrap();
-
-            if let Some(path) = base_shader.content.main_shader_path.parent() {
-                let snapshot_dir = path.join("snapshots");
-                dbg!(&snapshot_dir);
-
-                // Create directory if it doesn't exist
-                if !snapshot_dir.is_dir() {
-                    fs::create_dir(snapshot_dir.clone()).expect("Failed to create snapshot dir");
-                    log::debug!("Created snapshot dir {:?}", snapshot_dir);
-                }
-
-                // Save snapshot into snapshot dir
-                let format =
-                    format_description::parse("[year][month][day]_[hour][minute][second]").unwrap();
-
-                let datetime = time::OffsetDateTime::now_local()
-                    .unwrap()
-                    .format(&format)
-                    .unwrap();
-
-                let snapshot_filepath = snapshot_dir.join(format!("snapshot-{}.glsl", datetime));
+            // Save snapshot into snapshot dir
+            if let Some(snapshot_filepath) = self.timestamped_path("snapshots", "snapshot", "glsl")
+            {
                 log::info!("Snapshot: Saving to {:?}", &snapshot_filepath.clone());
                 fs::write(snapshot_filepath, source).unwrap();
             };
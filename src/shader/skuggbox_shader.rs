@@ -1,14 +1,26 @@
-use crate::{PreProcessor, ShaderError, ShaderProgram, ShaderUniformLocations};
+use crate::{
+    Diagnostic, PreProcessor, Project, ShaderError, ShaderProgram, ShaderUniformLocations,
+    SourceMap,
+};
 use glow::Program;
 use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::SystemTime;
 
 #[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub struct Part {
     pub shader_path: PathBuf,
     pub shader_src: String,
     pub shader_name: String,
+    /// Where the lines of `shader_src` came from, includes are already inlined
+    pub source_map: SourceMap,
+    /// Files this one includes directly, in order, by canonical path
+    pub includes: Vec<PathBuf>,
+    /// Size of the file itself, without its includes
+    pub line_count: usize,
+    pub byte_size: usize,
+    pub loaded_at: SystemTime,
 }
 
 /// The textual components that makes up what we need to process and build an OpenGL shader
@@ -24,6 +36,19 @@ pub struct ShaderContent {
     pub parts: BTreeMap<PathBuf, Part>,
     /// contains the final shader after it's been pre-processed
     pub shader_src: String,
+    /// The shader with its includes inlined, before skuggbox adds its own uniforms. This is
+    /// what snapshots save, so they can be loaded again.
+    pub snapshot_src: String,
+    /// Maps the lines of `shader_src` back to the files they were written in
+    pub source_map: SourceMap,
+    /// Includes that couldn't be loaded
+    pub diagnostics: Vec<Diagnostic>,
+    /// Names of the `sbProbe` calls, the index is the probe slot
+    pub probes: Vec<String>,
+    /// `vec3` uniforms that get a draggable handle in the viewport
+    pub handles: Vec<String>,
+    /// The shader calls `sbPick`, so clicking in the scene can read back what was hit
+    pub uses_pick: bool,
     pub ready_to_compile: bool,
 }
 
@@ -35,6 +60,8 @@ pub struct SkuggboxShader {
     pub program: Option<Program>,
     pub locations: ShaderUniformLocations,
     pub ready_to_compile: bool,
+    /// Messages from the last failed compile
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl SkuggboxShader {
@@ -56,6 +83,7 @@ impl SkuggboxShader {
                     program: None,
                     locations: ShaderUniformLocations::default(),
                     ready_to_compile,
+                    diagnostics: vec![],
                 }
             })
             .collect()
@@ -95,6 +123,7 @@ impl SkuggboxShader {
         match ShaderProgram::from_frag_src(&self.gl, self.content.shader_src.clone()) {
             Ok(program) => {
                 self.program = Some(program);
+                self.diagnostics.clear();
 
                 log::debug!(
                     "Fragment shader size: {:?} bytes",
@@ -104,15 +133,31 @@ impl SkuggboxShader {
             }
             Err(err) => {
                 log::warn!("{:?}", err);
+                self.diagnostics = Diagnostic::from_log(&err, &self.content.source_map);
                 Err(ShaderError::CompilationError { error: err })
             }
         }
     }
 
-    /// Detected uniforms in the shader source
-    pub fn find_shader_uniforms(&mut self, gl: &glow::Context) {
+    /// Detected uniforms in the shader source, and the uniforms of the project if there is one
+    pub fn find_shader_uniforms(&mut self, gl: &glow::Context, project: Option<&Project>) {
         if let Some(program) = self.program {
             self.locations = unsafe { ShaderProgram::uniform_locations(gl, program) };
+            self.locations.handles = unsafe {
+                ShaderProgram::named_uniform_locations(gl, program, &self.content.handles)
+            };
+            self.find_project_uniforms(gl, project);
+        }
+    }
+
+    /// Look up the uniforms of a new or changed project without recompiling
+    pub fn find_project_uniforms(&mut self, gl: &glow::Context, project: Option<&Project>) {
+        if let Some(program) = self.program {
+            let names = project
+                .into_iter()
+                .flat_map(|project| project.uniforms.keys());
+            self.locations.project =
+                unsafe { ShaderProgram::named_uniform_locations(gl, program, names) };
         }
     }
 }
@@ -1,28 +1,90 @@
-use crate::camera::{CameraModel, OrbitCamera};
-use crate::{Mouse, ShaderError, Timer};
-use glam::Vec3;
+use crate::camera::{CameraKind, CameraModel, Projection};
+use crate::{
+    frame_time, CameraBookmark, CameraMovement, CameraPath, CameraPose, CameraRecording,
+    CameraSample, CommandPalette, DisplayTransform, Editor, FrameTimes, Gizmos, Inspector,
+    KeyBindings, Mouse, Picking, PosterConfig, Probes, RecordingMode, RenderResolution, SceneView,
+    ShaderError, TargetFormat, Timeline, Timer, UpscaleFilter, WindowOptions,
+};
+use glam::{IVec2, Mat4, Vec3};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use winit::event::{ModifiersState, VirtualKeyCode};
 
 pub struct AppState {
     pub width: i32,
     pub height: i32,
+    /// Size of the offscreen target the scene is rendered into
+    pub render_resolution: RenderResolution,
+    pub upscale_filter: UpscaleFilter,
+    /// Pixel format of the scene target, float formats keep values above 1.0
+    pub scene_format: TargetFormat,
+    pub display: DisplayTransform,
+    /// Where the scene ended up in the window during the last frame
+    pub scene_view: SceneView,
     /// App state - is the application running?
     pub is_running: bool,
     pub timer: Timer,
     pub delta_time: f32,
     pub playback_time: f32,
+    /// Frame counter, kept in step with `playback_time`. Exposed as `iFrame`.
+    pub frame: u32,
+    /// Frames per second used for the fixed timestep and for single frame stepping
+    pub frame_rate: u32,
+    pub time_step: TimeStep,
+    pub timeline: Timeline,
     pub mouse: Mouse,
     pub modifier: ActionModifier,
+    /// Modifier keys held right now, for matching key chords
+    pub held_modifiers: ModifiersState,
+    /// Keys bound to a `CameraMove` that are held down, so letting go stops the same movement
+    /// whatever modifiers changed in between
+    pub held_moves: Vec<(VirtualKeyCode, CameraMovement)>,
+    pub key_bindings: KeyBindings,
+    pub command_palette: CommandPalette,
+    /// Show every action with its key binding
+    pub help_visible: bool,
+    /// Tree of the files that make up the shaders
+    pub file_tree_visible: bool,
+    /// Command template for opening files in an external editor, from the config file
+    pub external_editor: Option<String>,
+    /// The built-in shader editor
+    pub editor: Editor,
     /// Running or paused?
     pub play_mode: PlayMode,
     pub ui_visible: bool,
-    pub is_fullscreen: bool,
+    /// Show the frame time overlay, also when the rest of the UI is hidden
+    pub profiler_visible: bool,
+    pub frame_times: FrameTimes,
+    pub inspector: Inspector,
+    pub probes: Probes,
+    pub gizmos: Gizmos,
+    pub picking: Picking,
+    /// The cursor is over a window or panel, clicks there are for the UI and not the scene
+    pub pointer_over_ui: bool,
+    pub window: WindowOptions,
     pub camera: Box<dyn CameraModel>,
+    /// What `camera` is, used to create a fresh one on reset
+    pub camera_kind: CameraKind,
+    /// Saved views, recalled with the number keys
+    pub camera_bookmarks: Vec<CameraBookmark>,
+    /// Keyframed camera flight, overrides `camera` while enabled
+    pub camera_path: CameraPath,
+    /// Camera moves captured while playing, overrides `camera` and the path while replaying
+    pub camera_recording: CameraRecording,
+    pub recording_mode: RecordingMode,
     // TODO(mathias): Move the camera pos into the camera model
     pub camera_pos: Vec3,
     pub shader_error: Option<ShaderError>,
 
     pub scene_vars: SceneVars,
+    /// Where the floating windows were when the session was saved, by title
+    pub window_positions: BTreeMap<String, [f32; 2]>,
+    /// Current values of the uniforms declared by the project
+    pub project_uniforms: BTreeMap<String, Vec<f32>>,
+
+    pub poster: PosterConfig,
+    /// Render a poster with the `poster` settings at the end of the current frame
+    pub poster_requested: bool,
 }
 
 impl Default for AppState {
@@ -30,25 +92,200 @@ impl Default for AppState {
         Self {
             width: 1024,
             height: 768,
+            render_resolution: RenderResolution::default(),
+            upscale_filter: UpscaleFilter::default(),
+            scene_format: TargetFormat::default(),
+            display: DisplayTransform::default(),
+            scene_view: SceneView::default(),
             is_running: true,
             timer: Timer::default(),
             delta_time: 0.0,
             playback_time: 0.0,
+            frame: 0,
+            frame_rate: 60,
+            time_step: TimeStep::Variable,
+            timeline: Timeline::default(),
             mouse: Mouse::default(),
             modifier: ActionModifier::Normal,
+            held_modifiers: ModifiersState::empty(),
+            held_moves: vec![],
+            key_bindings: KeyBindings::default(),
+            command_palette: CommandPalette::default(),
+            help_visible: false,
+            file_tree_visible: false,
+            external_editor: None,
+            editor: Editor::default(),
             play_mode: PlayMode::Playing,
             ui_visible: true,
-            is_fullscreen: false,
-            camera: Box::from(OrbitCamera::default()),
+            profiler_visible: false,
+            frame_times: FrameTimes::default(),
+            inspector: Inspector::default(),
+            probes: Probes::default(),
+            gizmos: Gizmos::default(),
+            picking: Picking::default(),
+            pointer_over_ui: false,
+            window: WindowOptions::default(),
+            camera: CameraKind::default().create(),
+            camera_kind: CameraKind::default(),
+            camera_bookmarks: vec![],
+            camera_path: CameraPath::default(),
+            camera_recording: CameraRecording::default(),
+            recording_mode: RecordingMode::Off,
             camera_pos: Vec3::default(),
             shader_error: None,
             scene_vars: Default::default(),
+            window_positions: BTreeMap::new(),
+            project_uniforms: BTreeMap::new(),
+            poster: PosterConfig::default(),
+            poster_requested: false,
         }
     }
 }
 
+impl AppState {
+    /// Render target pixel under the mouse cursor, if it's over the scene
+    pub fn cursor_pixel(&self) -> Option<IVec2> {
+        let pixel = self.scene_view.window_to_render(self.mouse.cursor)?;
+        Some(pixel.floor().as_ivec2())
+    }
+
+    /// Delta time of the coming frame. Call after `timer.start()`.
+    pub fn step_delta_time(&self) -> f32 {
+        match self.time_step {
+            TimeStep::Variable => self.timer.delta_time,
+            TimeStep::Fixed => 1.0 / self.frame_rate as f32,
+        }
+    }
+
+    /// Move playback one frame forward. Called once per rendered frame while playing.
+    /// Honours the playback speed and wraps around in the loop region of the timeline.
+    pub fn advance_playback(&mut self) {
+        let speed = self.timeline.speed;
+        match self.time_step {
+            TimeStep::Variable => {
+                self.frame += 1;
+                self.playback_time += self.delta_time * speed;
+            }
+            TimeStep::Fixed if speed == 1.0 => {
+                self.frame += 1;
+                self.playback_time = frame_time(self.frame, self.frame_rate);
+            }
+            TimeStep::Fixed => {
+                self.playback_time += speed / self.frame_rate as f32;
+                self.frame = (self.playback_time * self.frame_rate as f32) as u32;
+            }
+        }
+
+        if let Some(time) = self.timeline.wrap(self.playback_time) {
+            self.set_playback_time(time);
+        }
+    }
+
+    /// Jump to a point in time and bring the frame counter along.
+    /// With a fixed timestep the time is snapped to the closest frame.
+    pub fn set_playback_time(&mut self, time: f32) {
+        let time = time.max(0.0);
+        self.frame = (time * self.frame_rate as f32).round() as u32;
+        self.playback_time = match self.time_step {
+            TimeStep::Variable => time,
+            TimeStep::Fixed => frame_time(self.frame, self.frame_rate),
+        };
+    }
+
+    /// Pose of the interactive camera, ignoring the camera path
+    pub fn camera_pose(&mut self) -> CameraPose {
+        let fov = self.camera.projection().fov;
+        CameraPose::from_transform(self.camera.calculate_uniform_data(), fov)
+    }
+
+    /// Switch to another camera model, keeping the projection
+    pub fn select_camera(&mut self, kind: CameraKind) {
+        if self.camera_kind != kind {
+            log::info!("Camera: {}", kind.name());
+            let projection = self.camera.projection().clone();
+            self.camera_kind = kind;
+            self.camera = kind.create();
+            *self.camera.projection_mut() = projection;
+            for (_, movement) in &self.held_moves {
+                self.camera.hold(movement, true);
+            }
+        }
+    }
+
+    /// Move the interactive camera to `pose`, FOV included
+    pub fn set_camera_pose(&mut self, pose: &CameraPose) {
+        self.camera.set_pose(pose);
+        self.camera.projection_mut().fov = pose.fov;
+    }
+
+    /// The pose from the camera path at the current time, if it's driving the camera
+    pub fn camera_path_pose(&self) -> Option<CameraPose> {
+        if !self.camera_path.enabled {
+            return None;
+        }
+        self.camera_path.sample(self.playback_time)
+    }
+
+    /// The recorded camera at the current time, if it's being replayed
+    pub fn camera_replay(&self) -> Option<CameraSample> {
+        if self.recording_mode != RecordingMode::Replaying {
+            return None;
+        }
+        self.camera_recording.sample(self.playback_time)
+    }
+
+    /// Value of `sbCameraTransform` for the current frame. A replayed recording goes before
+    /// the camera path, which goes before the interactive camera.
+    pub fn camera_transform(&mut self) -> Mat4 {
+        if let Some(sample) = self.camera_replay() {
+            return sample.transform;
+        }
+        match self.camera_path_pose() {
+            Some(pose) => pose.transform(),
+            None => self.camera.calculate_uniform_data(),
+        }
+    }
+
+    /// Projection of the current frame, with the FOV from the recording or camera path
+    pub fn camera_projection(&self) -> Projection {
+        let mut projection = self.camera.projection().clone();
+        if let Some(sample) = self.camera_replay() {
+            projection.fov = sample.fov;
+        } else if let Some(pose) = self.camera_path_pose() {
+            projection.fov = pose.fov;
+        }
+        projection
+    }
+
+    /// Capture the interactive camera when recording. Called once per frame after the
+    /// camera has been updated. Stops recording when playback jumps back.
+    pub fn record_camera(&mut self) {
+        if self.recording_mode != RecordingMode::Recording {
+            return;
+        }
+        let transform = self.camera.calculate_uniform_data();
+        let fov = self.camera.projection().fov;
+        if !self
+            .camera_recording
+            .push(self.playback_time, transform, fov)
+        {
+            self.recording_mode = RecordingMode::Off;
+            log::info!("Camera: Recording stopped, playback jumped back");
+        }
+    }
+
+    /// Step a number of frames forward, or back if negative. The frame counter is rebased on
+    /// the playback time first, they drift apart with a variable timestep or playback speed.
+    pub fn step_frames(&mut self, frames: i32) {
+        let frame = (self.playback_time * self.frame_rate as f32).round() as i64;
+        self.frame = (frame + frames as i64).max(0) as u32;
+        self.playback_time = frame_time(self.frame, self.frame_rate);
+    }
+}
+
 /// Bad naming but these are the values we can set from within skuggbox like colors
-#[derive(Default)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SceneVars {
     pub color_a: [f32; 3],
 }
@@ -61,7 +298,19 @@ pub enum ActionModifier {
     Fast, // ctrl
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl ActionModifier {
+    /// How much faster or slower an action runs with the modifier held
+    pub fn factor(&self) -> f32 {
+        match self {
+            ActionModifier::SuperSlow => 0.03125,
+            ActionModifier::Slow => 0.125,
+            ActionModifier::Normal => 1.0,
+            ActionModifier::Fast => 8.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PlayMode {
     Playing,
     Paused,
@@ -73,6 +322,15 @@ impl Default for PlayMode {
     }
 }
 
+/// How `playback_time` moves forward while playing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeStep {
+    /// Follow the wall clock
+    Variable,
+    /// Exactly 1 / `frame_rate` per frame, no matter how long the frame took to render
+    Fixed,
+}
+
 pub enum PlaybackControl {
     Forward(f32),
     Rewind(f32),
@@ -84,12 +342,7 @@ pub fn seek(
     modifier: &ActionModifier,
     playback_control: PlaybackControl,
 ) -> f32 {
-    let factor = match modifier {
-        ActionModifier::SuperSlow => 0.03125,
-        ActionModifier::Slow => 0.125,
-        ActionModifier::Normal => 1.0,
-        ActionModifier::Fast => 8.0,
-    };
+    let factor = modifier.factor();
 
     match playback_control {
         PlaybackControl::Forward(t) => playback_time + t * factor,
@@ -97,3 +350,84 @@ pub fn seek(
         PlaybackControl::Stop => 0.0,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{AppState, TimeStep};
+
+    #[test]
+    fn fixed_timestep_ignores_frame_duration() {
+        let mut state = AppState {
+            time_step: TimeStep::Fixed,
+            frame_rate: 50,
+            ..Default::default()
+        };
+        for _ in 0..100 {
+            state.delta_time = state.step_delta_time();
+            state.advance_playback();
+        }
+        assert_eq!(state.frame, 100);
+        assert_eq!(state.playback_time, 2.0);
+    }
+
+    #[test]
+    fn playback_speed_and_loop() {
+        let mut state = AppState {
+            time_step: TimeStep::Fixed,
+            frame_rate: 10,
+            ..Default::default()
+        };
+        state.timeline.set_speed(0.5);
+        state.timeline.set_loop_start(0.0);
+        state.timeline.set_loop_end(1.0);
+
+        for _ in 0..10 {
+            state.advance_playback();
+        }
+        assert_eq!(state.frame, 5);
+        assert!((state.playback_time - 0.5).abs() < 1e-5);
+
+        for _ in 0..12 {
+            state.advance_playback();
+        }
+        assert!((state.playback_time - 0.1).abs() < 1e-5);
+    }
+
+    #[test]
+    fn frame_follows_seek() {
+        let mut state = AppState::default();
+        state.set_playback_time(1.5);
+        assert_eq!(state.frame, 90);
+        assert_eq!(state.playback_time, 1.5);
+
+        state.time_step = TimeStep::Fixed;
+        state.set_playback_time(1.0 / 7.0);
+        assert_eq!(state.frame, 9);
+        assert_eq!(state.playback_time, 0.15);
+    }
+
+    #[test]
+    fn step_frames_stops_at_zero() {
+        let mut state = AppState::default();
+        state.step_frames(3);
+        assert_eq!(state.frame, 3);
+        assert_eq!(state.playback_time, 0.05);
+        state.step_frames(-5);
+        assert_eq!(state.frame, 0);
+        assert_eq!(state.playback_time, 0.0);
+    }
+
+    #[test]
+    fn step_frames_after_variable_playback() {
+        let mut state = AppState::default();
+        for _ in 0..10 {
+            state.delta_time = 0.1;
+            state.advance_playback();
+        }
+        assert_eq!(state.frame, 10);
+
+        state.step_frames(1);
+        assert_eq!(state.frame, 61);
+        assert!((state.playback_time - 61.0 / 60.0).abs() < 1e-5);
+    }
+}
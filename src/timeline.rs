@@ -0,0 +1,208 @@
+use serde::{Deserialize, Serialize};
+
+pub const MIN_PLAYBACK_SPEED: f32 = 0.1;
+pub const MAX_PLAYBACK_SPEED: f32 = 4.0;
+
+/// A named point in time the user can jump to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Marker {
+    pub name: String,
+    pub time: f32,
+}
+
+/// A-B region that playback wraps around in
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LoopRegion {
+    pub start: f32,
+    pub end: f32,
+    pub enabled: bool,
+}
+
+impl LoopRegion {
+    pub fn len(&self) -> f32 {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() <= 0.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Timeline {
+    /// Length of the timeline in seconds, only affects the scrubber
+    pub duration: f32,
+    /// Always sorted by time
+    pub markers: Vec<Marker>,
+    pub loop_region: Option<LoopRegion>,
+    /// Playback speed multiplier
+    pub speed: f32,
+    /// Tempo of the soundtrack, draws bar lines on the scrubber and drives `sbBeat`
+    pub bpm: Option<f32>,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self {
+            duration: 60.0,
+            markers: vec![],
+            loop_region: None,
+            speed: 1.0,
+            bpm: None,
+        }
+    }
+}
+
+impl Timeline {
+    /// Add a marker at `time` with a generated name, returns its index
+    pub fn add_marker(&mut self, time: f32) -> usize {
+        let name = format!("M{}", self.markers.len() + 1);
+        self.markers.push(Marker { name, time });
+        self.markers.sort_by(|a, b| a.time.total_cmp(&b.time));
+        self.markers
+            .iter()
+            .position(|marker| marker.time == time)
+            .unwrap_or_default()
+    }
+
+    pub fn remove_marker(&mut self, index: usize) {
+        if index < self.markers.len() {
+            self.markers.remove(index);
+        }
+    }
+
+    /// First marker after `time`
+    pub fn next_marker(&self, time: f32) -> Option<&Marker> {
+        self.markers.iter().find(|marker| marker.time > time)
+    }
+
+    /// Last marker before `time`. A small margin lets repeated jumps skip past the current one.
+    pub fn previous_marker(&self, time: f32) -> Option<&Marker> {
+        self.markers
+            .iter()
+            .rev()
+            .find(|marker| marker.time < time - 0.001)
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.clamp(MIN_PLAYBACK_SPEED, MAX_PLAYBACK_SPEED);
+    }
+
+    pub fn set_loop_start(&mut self, time: f32) {
+        let end = self.loop_region.map_or(self.duration, |region| region.end);
+        self.loop_region = Some(LoopRegion {
+            start: time,
+            end: end.max(time),
+            enabled: true,
+        });
+    }
+
+    pub fn set_loop_end(&mut self, time: f32) {
+        let start = self.loop_region.map_or(0.0, |region| region.start);
+        self.loop_region = Some(LoopRegion {
+            start: start.min(time),
+            end: time,
+            enabled: true,
+        });
+    }
+
+    pub fn toggle_loop(&mut self) {
+        match &mut self.loop_region {
+            Some(region) => region.enabled = !region.enabled,
+            None => {
+                self.loop_region = Some(LoopRegion {
+                    start: 0.0,
+                    end: self.duration,
+                    enabled: true,
+                })
+            }
+        }
+    }
+
+    /// Beats since the start at `time`, 0 without a BPM
+    pub fn beat(&self, time: f32) -> f32 {
+        self.bpm.map_or(0.0, |bpm| time * bpm / 60.0)
+    }
+
+    /// Returns the time to jump to if playback has run past the end of an active loop region
+    pub fn wrap(&self, time: f32) -> Option<f32> {
+        match self.loop_region {
+            Some(region) if region.enabled && !region.is_empty() && time >= region.end => {
+                Some(region.start + (time - region.end) % region.len())
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LoopRegion, Timeline};
+
+    #[test]
+    fn markers_stay_sorted() {
+        let mut timeline = Timeline::default();
+        timeline.add_marker(5.0);
+        assert_eq!(timeline.add_marker(2.0), 0);
+        assert_eq!(timeline.markers[0].name, "M2");
+        assert_eq!(timeline.markers[1].name, "M1");
+    }
+
+    #[test]
+    fn jump_between_markers() {
+        let mut timeline = Timeline::default();
+        timeline.add_marker(1.0);
+        timeline.add_marker(3.0);
+
+        assert_eq!(timeline.next_marker(1.0).unwrap().time, 3.0);
+        assert!(timeline.next_marker(3.0).is_none());
+        assert_eq!(timeline.previous_marker(3.0).unwrap().time, 1.0);
+        assert_eq!(timeline.previous_marker(2.0).unwrap().time, 1.0);
+        assert!(timeline.previous_marker(1.0).is_none());
+    }
+
+    #[test]
+    fn loop_wraps_playback() {
+        let mut timeline = Timeline::default();
+        assert_eq!(timeline.wrap(100.0), None);
+
+        timeline.loop_region = Some(LoopRegion {
+            start: 2.0,
+            end: 4.0,
+            enabled: true,
+        });
+        assert_eq!(timeline.wrap(3.0), None);
+        assert_eq!(timeline.wrap(4.5), Some(2.5));
+        assert_eq!(timeline.wrap(7.0), Some(3.0));
+
+        timeline.toggle_loop();
+        assert_eq!(timeline.wrap(4.5), None);
+    }
+
+    #[test]
+    fn loop_points_keep_order() {
+        let mut timeline = Timeline::default();
+        timeline.set_loop_end(3.0);
+        timeline.set_loop_start(5.0);
+        let region = timeline.loop_region.unwrap();
+        assert_eq!((region.start, region.end), (5.0, 5.0));
+    }
+
+    #[test]
+    fn speed_is_clamped() {
+        let mut timeline = Timeline::default();
+        timeline.set_speed(10.0);
+        assert_eq!(timeline.speed, 4.0);
+        timeline.set_speed(0.0);
+        assert_eq!(timeline.speed, 0.1);
+    }
+
+    #[test]
+    fn beats_follow_bpm() {
+        let mut timeline = Timeline::default();
+        assert_eq!(timeline.beat(3.0), 0.0);
+        timeline.bpm = Some(120.0);
+        assert_eq!(timeline.beat(3.0), 6.0);
+    }
+}
@@ -1,10 +1,62 @@
-use std::time::Instant;
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Source of time for the `Timer`.
+/// Abstracted so tests and offline rendering can drive time by hand.
+pub trait Clock {
+    /// Time elapsed since some fixed point in the past
+    fn now(&self) -> Duration;
+}
+
+/// Wall clock time
+pub struct SystemClock {
+    origin: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time.
+#[derive(Clone, Default)]
+pub struct ManualClock {
+    now: Rc<Cell<Duration>>,
+}
+
+impl ManualClock {
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+}
+
+/// Time of a frame in seconds. Derived from the frame index alone so it never drifts.
+pub fn frame_time(frame: u32, fps: u32) -> f32 {
+    (frame as f64 / fps as f64) as f32
+}
 
 pub struct Timer {
+    clock: Box<dyn Clock>,
     /// current frame
-    time: Instant,
+    time: Duration,
     /// last frame
-    last_time: Instant,
+    last_time: Duration,
 
     pub delta_time: f32,
 }
@@ -17,16 +69,22 @@ impl Default for Timer {
 
 impl Timer {
     pub fn new() -> Self {
+        Self::with_clock(Box::<SystemClock>::default())
+    }
+
+    pub fn with_clock(clock: Box<dyn Clock>) -> Self {
+        let now = clock.now();
         Self {
-            time: Instant::now(),
-            last_time: Instant::now(),
+            clock,
+            time: now,
+            last_time: now,
             delta_time: 0.0,
         }
     }
 
     /// Start of frame
     pub fn start(&mut self) {
-        self.time = Instant::now();
+        self.time = self.clock.now();
         self.delta_time = (self.time - self.last_time).as_secs_f32();
     }
 
@@ -35,3 +93,31 @@ impl Timer {
         self.last_time = self.time;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{frame_time, ManualClock, Timer};
+    use std::time::Duration;
+
+    #[test]
+    fn timer_measures_injected_clock() {
+        let clock = ManualClock::default();
+        let mut timer = Timer::with_clock(Box::new(clock.clone()));
+
+        clock.advance(Duration::from_millis(250));
+        timer.start();
+        assert_eq!(timer.delta_time, 0.25);
+        timer.stop();
+
+        clock.advance(Duration::from_millis(500));
+        timer.start();
+        assert_eq!(timer.delta_time, 0.5);
+    }
+
+    #[test]
+    fn frame_time_is_exact() {
+        assert_eq!(frame_time(0, 60), 0.0);
+        assert_eq!(frame_time(60, 60), 1.0);
+        assert_eq!(frame_time(90, 60), 1.5);
+    }
+}
@@ -1,4 +1,13 @@
-use crate::{Action, AppState, PlayMode, ShaderService};
+use crate::camera::CameraKind;
+use crate::{
+    highlight_glsl, Action, ActionCategory, AppState, Editor, Encoding, PlayMode, Project,
+    RecordingMode, RenderResolution, Severity, ShaderContent, ShaderService, TargetFormat,
+    TimeStep, TokenKind, Tonemapper, UpscaleFilter, FRAME_HISTORY_LEN, MAX_PLAYBACK_SPEED,
+    MIN_PLAYBACK_SPEED, RENDER_SCALES,
+};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::time::{Instant, SystemTime};
 
 pub fn top_bar(
     ui: &mut egui::Ui,
@@ -9,7 +18,14 @@ pub fn top_bar(
     ui.horizontal(|ui| {
         // show the current time / beat
         // TODO: Change "time" to "beat" when we can switch timing mode
-        let time = format!("time: {:6.2}", app_state.playback_time);
+        let mut time = format!(
+            "time: {:6.2} frame: {:5}",
+            app_state.playback_time, app_state.frame
+        );
+        if app_state.timeline.bpm.is_some() {
+            let beat = app_state.timeline.beat(app_state.playback_time);
+            time.push_str(&format!(" beat: {:6.2}", beat));
+        }
         ui.label(time);
 
         if ui.button("⏹").clicked() {
@@ -33,6 +49,20 @@ pub fn top_bar(
             actions.push(Action::TimeForward(1.0))
         }
 
+        // single frame stepping
+        if ui.button("|◀").clicked() {
+            actions.push(Action::FrameStepBack)
+        }
+        if ui.button("▶|").clicked() {
+            actions.push(Action::FrameStepForward)
+        }
+
+        let mut fixed = matches!(app_state.time_step, TimeStep::Fixed);
+        let fixed_label = format!("fixed {} fps", app_state.frame_rate);
+        if ui.checkbox(&mut fixed, fixed_label).changed() {
+            actions.push(Action::ToggleTimeStep)
+        }
+
         ui.spacing();
         // show camera mode
         let cam_mode_str = match shader_service.use_camera_integration {
@@ -41,6 +71,48 @@ pub fn top_bar(
         };
         ui.label(format!("Camera mode: {}", cam_mode_str));
 
+        egui::ComboBox::from_id_source("camera_kind")
+            .selected_text(app_state.camera_kind.name())
+            .show_ui(ui, |ui| {
+                for kind in CameraKind::ALL {
+                    let selected = app_state.camera_kind == kind;
+                    if ui.selectable_label(selected, kind.name()).clicked() {
+                        actions.push(Action::CameraSelect(kind));
+                    }
+                }
+            });
+
+        ui.menu_button("projection", |ui| projection_menu(ui, app_state));
+        ui.menu_button("views", |ui| views_menu(ui, app_state, actions));
+        ui.menu_button("gizmos", |ui| gizmos_menu(ui, app_state, actions));
+
+        ui.spacing();
+        render_resolution_combo(ui, app_state, actions);
+
+        ui.spacing();
+        if ui
+            .selectable_label(app_state.profiler_visible, "stats")
+            .clicked()
+        {
+            actions.push(Action::ToggleProfiler);
+        }
+        if ui
+            .selectable_label(app_state.inspector.enabled, "inspect")
+            .clicked()
+        {
+            actions.push(Action::ToggleInspector);
+        }
+        if ui
+            .selectable_label(app_state.picking.enabled, "pick")
+            .clicked()
+        {
+            actions.push(Action::TogglePicking);
+        }
+
+        ui.spacing();
+
+        ui.menu_button("display", |ui| display_menu(ui, app_state));
+
         ui.spacing();
 
         ui.with_layout(egui::Layout::left_to_right(egui::Align::LEFT), |ui| {
@@ -48,3 +120,1369 @@ pub fn top_bar(
         });
     });
 }
+
+/// Field of view, orthographic mode, clipping and depth of field of the current camera
+fn projection_menu(ui: &mut egui::Ui, app_state: &mut AppState) {
+    let projection = app_state.camera.projection_mut();
+
+    ui.checkbox(&mut projection.orthographic, "orthographic");
+    if projection.orthographic {
+        ui.add(
+            egui::Slider::new(&mut projection.ortho_scale, 0.1..=50.0)
+                .logarithmic(true)
+                .text("scale"),
+        );
+    } else {
+        ui.add(
+            egui::Slider::new(&mut projection.fov, 10.0..=170.0)
+                .text("fov")
+                .suffix("°"),
+        );
+    }
+
+    ui.horizontal(|ui| {
+        ui.label("near");
+        ui.add(
+            egui::DragValue::new(&mut projection.near)
+                .speed(0.01)
+                .clamp_range(0.0..=projection.far),
+        );
+        ui.label("far");
+        ui.add(
+            egui::DragValue::new(&mut projection.far)
+                .speed(0.5)
+                .clamp_range(projection.near..=10000.0),
+        );
+    });
+
+    ui.separator();
+    ui.add(
+        egui::Slider::new(&mut projection.focus_distance, 0.1..=100.0)
+            .logarithmic(true)
+            .text("focus distance"),
+    );
+    ui.add(egui::Slider::new(&mut projection.aperture, 0.0..=1.0).text("aperture"));
+
+    if ui.button("reset").clicked() {
+        *projection = Default::default();
+    }
+}
+
+/// Saved camera views. Ctrl + number saves and shift + number recalls.
+fn views_menu(ui: &mut egui::Ui, app_state: &mut AppState, actions: &mut Vec<Action>) {
+    for bookmark in &mut app_state.camera_bookmarks {
+        ui.horizontal(|ui| {
+            if ui.button(bookmark.slot.to_string()).clicked() {
+                actions.push(Action::CameraBookmarkRecall(bookmark.slot));
+            }
+            ui.add(egui::TextEdit::singleline(&mut bookmark.name).desired_width(100.0));
+            if ui.button("🗑").clicked() {
+                actions.push(Action::CameraBookmarkRemove(bookmark.slot));
+            }
+        });
+    }
+
+    let free_slot = (1..=9).find(|slot| {
+        !app_state
+            .camera_bookmarks
+            .iter()
+            .any(|bookmark| bookmark.slot == *slot)
+    });
+    if let Some(slot) = free_slot {
+        if ui.button("save current view").clicked() {
+            actions.push(Action::CameraBookmarkSave(slot));
+        }
+    }
+}
+
+/// Overlay toggle and the positions of the point handles
+fn gizmos_menu(ui: &mut egui::Ui, app_state: &mut AppState, actions: &mut Vec<Action>) {
+    let gizmos = &mut app_state.gizmos;
+    if ui.checkbox(&mut gizmos.enabled.clone(), "show").clicked() {
+        actions.push(Action::ToggleGizmos);
+    }
+    ui.add(
+        egui::Slider::new(&mut gizmos.grid_extent, 1..=50)
+            .text("grid size")
+            .suffix(" units"),
+    );
+
+    if gizmos.handles.is_empty() {
+        ui.label("Add handles with #pragma skuggbox(handle: name)");
+        return;
+    }
+
+    ui.separator();
+    for handle in &mut gizmos.handles {
+        ui.horizontal(|ui| {
+            ui.label(&handle.name);
+            for value in [
+                &mut handle.position.x,
+                &mut handle.position.y,
+                &mut handle.position.z,
+            ] {
+                ui.add(egui::DragValue::new(value).speed(0.01));
+            }
+        });
+    }
+}
+
+/// Scene format and the display transform applied on the way to the window
+fn display_menu(ui: &mut egui::Ui, app_state: &mut AppState) {
+    let display = &mut app_state.display;
+
+    ui.add(
+        egui::Slider::new(&mut display.exposure, -8.0..=8.0)
+            .text("exposure")
+            .suffix(" EV"),
+    );
+
+    egui::ComboBox::from_label("tonemapper")
+        .selected_text(display.tonemapper.name())
+        .show_ui(ui, |ui| {
+            for tonemapper in Tonemapper::ALL {
+                ui.selectable_value(&mut display.tonemapper, tonemapper, tonemapper.name());
+            }
+        });
+
+    ui.horizontal(|ui| {
+        ui.selectable_value(&mut display.encoding, Encoding::None, "none");
+        ui.selectable_value(&mut display.encoding, Encoding::Srgb, "sRGB");
+        ui.selectable_value(&mut display.encoding, Encoding::Gamma, "gamma");
+    });
+    if display.encoding == Encoding::Gamma {
+        ui.add(egui::Slider::new(&mut display.gamma, 1.0..=3.0).text("gamma"));
+    }
+    ui.checkbox(&mut display.dither, "dither");
+
+    ui.separator();
+    ui.horizontal(|ui| {
+        for (format, name) in [
+            (TargetFormat::Rgba8, "RGBA8"),
+            (TargetFormat::Rgba16f, "RGBA16F"),
+            (TargetFormat::Rgba32f, "RGBA32F"),
+        ] {
+            ui.selectable_value(&mut app_state.scene_format, format, name);
+        }
+    });
+
+    if ui.button("reset").clicked() {
+        app_state.display = Default::default();
+    }
+}
+
+fn render_resolution_combo(ui: &mut egui::Ui, app_state: &AppState, actions: &mut Vec<Action>) {
+    let label = |resolution: &RenderResolution| match resolution {
+        RenderResolution::Scale(scale) => format!("{}x", scale),
+        RenderResolution::Fixed { width, height } => format!("{}x{}", width, height),
+    };
+    let fixed_sizes = [(1280, 720), (1920, 1080), (3840, 2160)];
+
+    egui::ComboBox::from_id_source("render_resolution")
+        .selected_text(label(&app_state.render_resolution))
+        .show_ui(ui, |ui| {
+            let scales = RENDER_SCALES
+                .iter()
+                .map(|scale| RenderResolution::Scale(*scale));
+            let fixed = fixed_sizes
+                .iter()
+                .map(|(width, height)| RenderResolution::Fixed {
+                    width: *width,
+                    height: *height,
+                });
+            for resolution in scales.chain(fixed) {
+                let selected = app_state.render_resolution == resolution;
+                if ui.selectable_label(selected, label(&resolution)).clicked() {
+                    actions.push(Action::SetRenderResolution(resolution));
+                }
+            }
+        });
+
+    let linear = matches!(app_state.upscale_filter, UpscaleFilter::Linear);
+    if ui.selectable_label(linear, "linear").clicked() {
+        actions.push(Action::ToggleUpscaleFilter);
+    }
+
+    let view = &app_state.scene_view;
+    ui.label(format!("{}x{}", view.render_width, view.render_height));
+}
+
+/// Scrubber with markers, loop region and playback speed
+pub fn timeline_panel(ui: &mut egui::Ui, app_state: &mut AppState, actions: &mut Vec<Action>) {
+    let following = app_state.camera_path.enabled;
+    let recording_mode = app_state.recording_mode;
+    let timeline = &mut app_state.timeline;
+
+    ui.horizontal(|ui| {
+        ui.label("duration");
+        ui.add(
+            egui::DragValue::new(&mut timeline.duration)
+                .clamp_range(1.0..=3600.0)
+                .suffix(" s"),
+        );
+
+        let mut speed = timeline.speed;
+        let speed_slider = egui::Slider::new(&mut speed, MIN_PLAYBACK_SPEED..=MAX_PLAYBACK_SPEED)
+            .logarithmic(true)
+            .text("speed");
+        if ui.add(speed_slider).changed() {
+            actions.push(Action::TimeSpeed(speed));
+        }
+
+        ui.separator();
+        if ui.button("+ marker").clicked() {
+            actions.push(Action::MarkerAdd);
+        }
+        if ui.button("A").on_hover_text("Loop from here").clicked() {
+            actions.push(Action::LoopSetStart);
+        }
+        if ui.button("B").on_hover_text("Loop until here").clicked() {
+            actions.push(Action::LoopSetEnd);
+        }
+        let looping = timeline.loop_region.map_or(false, |region| region.enabled);
+        if ui.selectable_label(looping, "loop").clicked() {
+            actions.push(Action::LoopToggle);
+        }
+
+        ui.separator();
+        if ui.button("+ camera key").clicked() {
+            actions.push(Action::CameraKeyframeAdd);
+        }
+        if ui
+            .selectable_label(following, "camera path")
+            .on_hover_text("Drive the camera from the keyframes")
+            .clicked()
+        {
+            actions.push(Action::CameraPathToggle);
+        }
+
+        let recording = recording_mode == RecordingMode::Recording;
+        let rec = egui::RichText::new("⏺ rec");
+        let rec = if recording {
+            rec.color(egui::Color32::RED)
+        } else {
+            rec
+        };
+        if ui
+            .selectable_label(recording, rec)
+            .on_hover_text("Record the camera while playing")
+            .clicked()
+        {
+            actions.push(Action::CameraRecordToggle);
+        }
+        let replaying = recording_mode == RecordingMode::Replaying;
+        if ui.selectable_label(replaying, "replay").clicked() {
+            actions.push(Action::CameraReplayToggle);
+        }
+    });
+
+    let size = egui::vec2(ui.available_width(), 28.0);
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+    let duration = timeline.duration.max(0.001);
+    let x_for = |time: f32| rect.left() + (time / duration).clamp(0.0, 1.0) * rect.width();
+
+    let painter = ui.painter_at(rect);
+    let visuals = ui.visuals();
+    painter.rect_filled(rect, 2.0, visuals.extreme_bg_color);
+
+    if let Some(region) = timeline.loop_region {
+        let color = if region.enabled {
+            visuals.selection.bg_fill.linear_multiply(0.5)
+        } else {
+            visuals.faint_bg_color
+        };
+        let loop_rect =
+            egui::Rect::from_x_y_ranges(x_for(region.start)..=x_for(region.end), rect.y_range());
+        painter.rect_filled(loop_rect, 0.0, color);
+    }
+
+    // one tick per second, or fewer if they get too crowded
+    let tick_step = (duration / (rect.width() / 8.0)).ceil().max(1.0);
+    let mut tick = 0.0;
+    while tick <= duration {
+        let x = x_for(tick);
+        painter.line_segment(
+            [
+                egui::pos2(x, rect.bottom() - 4.0),
+                egui::pos2(x, rect.bottom()),
+            ],
+            visuals.widgets.noninteractive.fg_stroke,
+        );
+        tick += tick_step;
+    }
+
+    // a line per bar of four beats, as long as they're far enough apart to tell apart
+    if let Some(bpm) = timeline.bpm.filter(|bpm| *bpm > 0.0) {
+        let bar = 4.0 * 60.0 / bpm;
+        if x_for(bar) - rect.left() >= 4.0 {
+            let mut time = bar;
+            while time < duration {
+                let x = x_for(time);
+                painter.line_segment(
+                    [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+                    visuals.widgets.noninteractive.bg_stroke,
+                );
+                time += bar;
+            }
+        }
+    }
+
+    for marker in &timeline.markers {
+        let x = x_for(marker.time);
+        painter.line_segment(
+            [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+            egui::Stroke::new(1.0, egui::Color32::YELLOW),
+        );
+        painter.text(
+            egui::pos2(x + 2.0, rect.top()),
+            egui::Align2::LEFT_TOP,
+            &marker.name,
+            egui::FontId::proportional(10.0),
+            egui::Color32::YELLOW,
+        );
+    }
+
+    if let Some((start, end)) = app_state.camera_recording.range() {
+        let span = egui::Rect::from_x_y_ranges(
+            x_for(start)..=x_for(end).max(x_for(start) + 1.0),
+            rect.top()..=rect.top() + 3.0,
+        );
+        painter.rect_filled(span, 0.0, egui::Color32::RED.linear_multiply(0.7));
+    }
+
+    for keyframe in &app_state.camera_path.keyframes {
+        let center = egui::pos2(x_for(keyframe.time), rect.bottom() - 6.0);
+        let color = egui::Color32::LIGHT_BLUE;
+        let diamond = vec![
+            center + egui::vec2(0.0, -4.0),
+            center + egui::vec2(4.0, 0.0),
+            center + egui::vec2(0.0, 4.0),
+            center + egui::vec2(-4.0, 0.0),
+        ];
+        painter.add(egui::Shape::convex_polygon(
+            diamond,
+            color,
+            egui::Stroke::NONE,
+        ));
+    }
+
+    let playhead = x_for(app_state.playback_time);
+    painter.line_segment(
+        [
+            egui::pos2(playhead, rect.top()),
+            egui::pos2(playhead, rect.bottom()),
+        ],
+        egui::Stroke::new(2.0, visuals.strong_text_color()),
+    );
+
+    if response.clicked() || response.dragged() {
+        if let Some(pos) = response.interact_pointer_pos() {
+            let time = (pos.x - rect.left()) / rect.width() * duration;
+            actions.push(Action::TimeSeek(time.clamp(0.0, duration)));
+        }
+    }
+
+    if !timeline.markers.is_empty() {
+        egui::CollapsingHeader::new("markers").show(ui, |ui| {
+            for (index, marker) in timeline.markers.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut marker.name).desired_width(80.0));
+                    if ui.button(format!("{:6.2}", marker.time)).clicked() {
+                        actions.push(Action::TimeSeek(marker.time));
+                    }
+                    if ui.button("🗑").clicked() {
+                        actions.push(Action::MarkerRemove(index));
+                    }
+                });
+            }
+        });
+    }
+
+    if !app_state.camera_path.is_empty() {
+        camera_path_editor(ui, app_state, actions);
+    }
+
+    if !app_state.camera_recording.is_empty() {
+        camera_recording_tools(ui, app_state, actions);
+    }
+}
+
+/// Smoothing, resampling and export of the recorded camera
+fn camera_recording_tools(ui: &mut egui::Ui, app_state: &AppState, actions: &mut Vec<Action>) {
+    egui::CollapsingHeader::new("camera recording").show(ui, |ui| {
+        let recording = &app_state.camera_recording;
+        let (start, end) = recording.range().unwrap_or_default();
+        ui.label(format!(
+            "{} samples from {:.2} s to {:.2} s",
+            recording.samples.len(),
+            start,
+            end
+        ));
+
+        let smoothing_id = ui.id().with("smoothing");
+        let interval_id = ui.id().with("interval");
+        let mut smoothing = ui.data_mut(|data| *data.get_temp_mut_or(smoothing_id, 0.25f32));
+        let mut interval = ui.data_mut(|data| *data.get_temp_mut_or(interval_id, 0.5f32));
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut smoothing)
+                    .speed(0.01)
+                    .clamp_range(0.0..=5.0)
+                    .suffix(" s"),
+            );
+            if ui.button("smooth").clicked() {
+                actions.push(Action::CameraRecordingSmooth(smoothing));
+            }
+
+            ui.separator();
+            ui.add(
+                egui::DragValue::new(&mut interval)
+                    .speed(0.01)
+                    .clamp_range(0.05..=10.0)
+                    .suffix(" s"),
+            );
+            if ui
+                .button("to camera keys")
+                .on_hover_text("Replace the camera path with keyframes from the recording")
+                .clicked()
+            {
+                actions.push(Action::CameraRecordingToPath(interval));
+            }
+
+            ui.separator();
+            if ui.button("export").clicked() {
+                actions.push(Action::CameraRecordingExport);
+            }
+            if ui.button("🗑").clicked() {
+                actions.push(Action::CameraRecordingClear);
+            }
+        });
+
+        ui.data_mut(|data| {
+            data.insert_temp(smoothing_id, smoothing);
+            data.insert_temp(interval_id, interval);
+        });
+    });
+}
+
+/// Keyframes of the camera path with their time and FOV
+fn camera_path_editor(ui: &mut egui::Ui, app_state: &mut AppState, actions: &mut Vec<Action>) {
+    let duration = app_state.timeline.duration;
+    let path = &mut app_state.camera_path;
+    let mut retimed = false;
+
+    egui::CollapsingHeader::new("camera keys").show(ui, |ui| {
+        for (index, keyframe) in path.keyframes.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                if ui.button("go").clicked() {
+                    actions.push(Action::TimeSeek(keyframe.time));
+                }
+                let time = egui::DragValue::new(&mut keyframe.time)
+                    .speed(0.01)
+                    .clamp_range(0.0..=duration)
+                    .suffix(" s");
+                retimed |= ui.add(time).changed();
+                ui.add(
+                    egui::DragValue::new(&mut keyframe.pose.fov)
+                        .speed(0.2)
+                        .clamp_range(1.0..=179.0)
+                        .suffix("°"),
+                );
+                let p = keyframe.pose.position;
+                ui.label(format!("{:.2} {:.2} {:.2}", p.x, p.y, p.z));
+                if ui
+                    .button("set")
+                    .on_hover_text("Use the current view")
+                    .clicked()
+                {
+                    actions.push(Action::CameraKeyframeUpdate(index));
+                }
+                if ui.button("🗑").clicked() {
+                    actions.push(Action::CameraKeyframeRemove(index));
+                }
+            });
+        }
+    });
+
+    if retimed {
+        path.sort();
+    }
+}
+
+/// Frame time graph in the upper right corner. Drawn as its own area so it stays up when
+/// the panels are hidden.
+pub fn profiler_overlay(ctx: &egui::Context, app_state: &AppState) {
+    let times = &app_state.frame_times;
+    let series = [
+        ("cpu", &times.cpu, egui::Color32::WHITE),
+        ("gpu scene", &times.gpu_scene, egui::Color32::LIGHT_GREEN),
+        ("gpu ui", &times.gpu_ui, egui::Color32::LIGHT_BLUE),
+    ];
+
+    egui::Area::new("profiler")
+        .anchor(egui::Align2::RIGHT_TOP, egui::vec2(-8.0, 40.0))
+        .interactable(false)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.label(format!(
+                    "{:.0} fps  {:.2} ms",
+                    times.fps(),
+                    times.frame.latest().unwrap_or_default()
+                ));
+
+                egui::Grid::new("profiler_stats").show(ui, |ui| {
+                    ui.label("");
+                    ui.label("min");
+                    ui.label("avg");
+                    ui.label("max");
+                    ui.end_row();
+                    for (name, history, color) in &series {
+                        ui.colored_label(*color, *name);
+                        if history.is_empty() {
+                            ui.label("n/a");
+                        } else {
+                            ui.label(format!("{:.2}", history.min()));
+                            ui.label(format!("{:.2}", history.avg()));
+                            ui.label(format!("{:.2}", history.max()));
+                        }
+                        ui.end_row();
+                    }
+                });
+
+                // scale to at least 30 fps so a fast shader doesn't look noisy
+                let ceiling = series
+                    .iter()
+                    .map(|(_, history, _)| history.max())
+                    .fold(1000.0 / 30.0, f32::max);
+                let (rect, _) =
+                    ui.allocate_exact_size(egui::vec2(240.0, 80.0), egui::Sense::hover());
+                let painter = ui.painter_at(rect);
+                painter.rect_filled(rect, 0.0, egui::Color32::from_black_alpha(120));
+
+                // 60 fps reference line
+                let y_at = |ms: f32| rect.bottom() - rect.height() * (ms / ceiling).min(1.0);
+                painter.hline(
+                    rect.x_range(),
+                    y_at(1000.0 / 60.0),
+                    egui::Stroke::new(1.0, egui::Color32::DARK_GRAY),
+                );
+
+                for (_, history, color) in &series {
+                    let count = history.samples().count();
+                    let points = history
+                        .samples()
+                        .enumerate()
+                        .map(|(i, ms)| {
+                            let x = rect.right()
+                                - (count - i) as f32 * rect.width() / FRAME_HISTORY_LEN as f32;
+                            egui::pos2(x, y_at(ms))
+                        })
+                        .collect();
+                    painter.add(egui::Shape::line(points, egui::Stroke::new(1.0, *color)));
+                }
+                ui.label(format!("graph max {:.1} ms", ceiling));
+            });
+        });
+}
+
+fn rgba_to_color32(value: [f32; 4]) -> egui::Color32 {
+    let [r, g, b, _] = value.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+    egui::Color32::from_rgb(r, g, b)
+}
+
+fn format_rgba(value: Option<[f32; 4]>) -> String {
+    match value {
+        Some([r, g, b, a]) => format!("{:.4} {:.4} {:.4} {:.4}", r, g, b, a),
+        None => "-".to_string(),
+    }
+}
+
+/// Pixel values under the cursor, the zoom lens and the pinned probe points
+pub fn inspector_window(ctx: &egui::Context, app_state: &AppState, actions: &mut Vec<Action>) {
+    let inspector = &app_state.inspector;
+
+    // mark the pins in the scene
+    let painter = ctx.layer_painter(egui::LayerId::new(
+        egui::Order::Foreground,
+        egui::Id::new("inspector_pins"),
+    ));
+    let pixels_per_point = ctx.pixels_per_point();
+    for pin in &inspector.pins {
+        let pos = app_state.scene_view.render_to_window(pin.pixel) / pixels_per_point;
+        let pos = egui::pos2(pos.x, pos.y);
+        painter.circle_stroke(pos, 4.0, egui::Stroke::new(1.5, egui::Color32::YELLOW));
+        painter.text(
+            pos + egui::vec2(6.0, -6.0),
+            egui::Align2::LEFT_BOTTOM,
+            &pin.name,
+            egui::FontId::monospace(10.0),
+            egui::Color32::YELLOW,
+        );
+    }
+
+    egui::Window::new("Inspector")
+        .default_pos(window_pos(app_state, "Inspector", egui::pos2(8.0, 40.0)))
+        .resizable(false)
+        .show(ctx, |ui| {
+            match inspector.hovered {
+                Some(pixel) => ui.label(format!("pixel {} {}", pixel.x, pixel.y)),
+                None => ui.label("pixel -"),
+            };
+            ui.monospace(format_rgba(inspector.hovered_value));
+
+            // nearest filtered zoom, one rect per pixel
+            let size = inspector.lens_size();
+            let cell = (144.0 / size as f32).floor().max(2.0);
+            let (rect, _) = ui.allocate_exact_size(
+                egui::vec2(cell * size as f32, cell * size as f32),
+                egui::Sense::hover(),
+            );
+            let painter = ui.painter_at(rect);
+            painter.rect_filled(rect, 0.0, egui::Color32::from_gray(20));
+            for (i, value) in inspector.lens.iter().enumerate() {
+                if let Some(value) = value {
+                    let (x, y) = (i as i32 % size, i as i32 / size);
+                    let min = rect.min + egui::vec2(x as f32 * cell, y as f32 * cell);
+                    let pixel = egui::Rect::from_min_size(min, egui::vec2(cell, cell));
+                    painter.rect_filled(pixel, 0.0, rgba_to_color32(*value));
+                }
+            }
+            let center = rect.min + egui::Vec2::splat(inspector.lens_radius as f32 * cell);
+            painter.rect_stroke(
+                egui::Rect::from_min_size(center, egui::vec2(cell, cell)),
+                0.0,
+                egui::Stroke::new(1.0, egui::Color32::WHITE),
+            );
+
+            if ui
+                .add_enabled(inspector.hovered.is_some(), egui::Button::new("pin (K)"))
+                .clicked()
+            {
+                actions.push(Action::InspectorPin);
+            }
+
+            if inspector.pins.is_empty() {
+                return;
+            }
+            ui.separator();
+            egui::Grid::new("inspector_pins").show(ui, |ui| {
+                for (index, pin) in inspector.pins.iter().enumerate() {
+                    let (swatch, _) =
+                        ui.allocate_exact_size(egui::vec2(12.0, 12.0), egui::Sense::hover());
+                    let color = pin.value.map(rgba_to_color32).unwrap_or_default();
+                    ui.painter().rect_filled(swatch, 2.0, color);
+                    ui.label(&pin.name);
+                    ui.label(format!("{} {}", pin.pixel.x, pin.pixel.y));
+                    ui.monospace(format_rgba(pin.value));
+                    if ui.small_button("x").clicked() {
+                        actions.push(Action::InspectorRemovePin(index));
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+}
+
+/// Where a floating window starts out, restored from the last session if it was open then
+fn window_pos(app_state: &AppState, title: &str, default: egui::Pos2) -> egui::Pos2 {
+    app_state
+        .window_positions
+        .get(title)
+        .map_or(default, |[x, y]| egui::pos2(*x, *y))
+}
+
+/// Sliders for the uniforms declared in the project file
+pub fn uniforms_window(ctx: &egui::Context, app_state: &mut AppState, project: &Project) {
+    egui::Window::new("Uniforms")
+        .default_pos(window_pos(app_state, "Uniforms", egui::pos2(8.0, 120.0)))
+        .show(ctx, |ui| {
+            egui::Grid::new("project_uniforms").show(ui, |ui| {
+                for (name, definition) in &project.uniforms {
+                    let value = match app_state.project_uniforms.get_mut(name) {
+                        Some(value) => value,
+                        None => continue,
+                    };
+                    ui.label(name);
+                    ui.horizontal(|ui| {
+                        for component in value.iter_mut() {
+                            match (definition.min, definition.max) {
+                                (Some(min), Some(max)) => {
+                                    ui.add(egui::Slider::new(component, min..=max));
+                                }
+                                (min, max) => {
+                                    let range = min.unwrap_or(f32::MIN)..=max.unwrap_or(f32::MAX);
+                                    ui.add(
+                                        egui::DragValue::new(component)
+                                            .clamp_range(range)
+                                            .speed(0.01),
+                                    );
+                                }
+                            }
+                        }
+                        if ui.small_button("⟲").on_hover_text("Reset").clicked() {
+                            *value = definition.value.components();
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+        });
+}
+
+/// Values of the `sbProbe` calls at the selected pixel
+pub fn probe_window(ctx: &egui::Context, app_state: &AppState) {
+    let probes = &app_state.probes;
+    egui::Window::new("Probes")
+        .default_pos(window_pos(app_state, "Probes", egui::pos2(8.0, 320.0)))
+        .show(ctx, |ui| {
+            let pixel = match probes.pixel {
+                Some(pixel) => pixel,
+                None => {
+                    ui.label("Middle click the scene to pick a pixel");
+                    return;
+                }
+            };
+            ui.label(format!("pixel {} {}", pixel.x, pixel.y));
+
+            if probes.values.is_empty() {
+                ui.label("No sbProbe(\"name\", value); calls in the shader");
+                return;
+            }
+            egui::Grid::new("probe_values")
+                .striped(true)
+                .show(ui, |ui| {
+                    for probe in &probes.values {
+                        ui.label(&probe.name);
+                        ui.weak(probe.type_name());
+                        if probe.reached {
+                            ui.monospace(probe.format());
+                        } else {
+                            ui.weak(probe.format());
+                        }
+                        ui.end_row();
+                    }
+                });
+        });
+}
+
+/// Position, normal and id the shader reported through `sbPick` for the clicked pixel
+pub fn pick_window(ctx: &egui::Context, app_state: &AppState, actions: &mut Vec<Action>) {
+    egui::Window::new("Pick")
+        .default_pos(window_pos(app_state, "Pick", egui::pos2(8.0, 480.0)))
+        .show(ctx, |ui| {
+            let result = match &app_state.picking.result {
+                Some(result) => result,
+                None => {
+                    ui.label("Click the scene to pick a point");
+                    ui.weak("The shader reports hits with sbPick(position, normal[, id]);");
+                    return;
+                }
+            };
+            ui.label(format!("pixel {} {}", result.pixel.x, result.pixel.y));
+            if !result.hit {
+                ui.weak("no hit");
+                return;
+            }
+
+            let vec3 = |v: glam::Vec3| format!("{:.4}, {:.4}, {:.4}", v.x, v.y, v.z);
+            egui::Grid::new("pick_result").show(ui, |ui| {
+                ui.label("position");
+                ui.monospace(vec3(result.position));
+                ui.end_row();
+                ui.label("normal");
+                ui.monospace(vec3(result.normal));
+                ui.end_row();
+                if let Some(id) = result.id {
+                    ui.label("id");
+                    ui.monospace(format!("{}", id));
+                    ui.end_row();
+                }
+            });
+
+            if app_state.camera.target().is_some() && ui.button("orbit around this point").clicked()
+            {
+                actions.push(Action::CameraOrbitAround(result.position));
+            }
+        });
+}
+
+/// Search every action by name and run it, Ctrl+Shift+P by default
+pub fn command_palette(ctx: &egui::Context, app_state: &mut AppState, actions: &mut Vec<Action>) {
+    let bindings = &app_state.key_bindings;
+    let palette = &mut app_state.command_palette;
+    let chords = |action: &Action| {
+        let chords: Vec<String> = bindings
+            .chords(action)
+            .iter()
+            .map(|chord| chord.to_string())
+            .collect();
+        chords.join(", ")
+    };
+
+    let mut close = false;
+    egui::Window::new("Commands")
+        .title_bar(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 48.0))
+        .default_width(480.0)
+        .show(ctx, |ui| {
+            let (escape, enter, up, down) = ui.input(|i| {
+                (
+                    i.key_pressed(egui::Key::Escape),
+                    i.key_pressed(egui::Key::Enter),
+                    i.key_pressed(egui::Key::ArrowUp),
+                    i.key_pressed(egui::Key::ArrowDown),
+                )
+            });
+            if escape {
+                close = true;
+                return;
+            }
+
+            if let Some(pending) = &palette.pending {
+                let info = pending.info();
+                ui.label(format!(
+                    "{}: {}",
+                    info.name,
+                    info.parameter.unwrap_or_default()
+                ));
+                ui.add(
+                    egui::TextEdit::singleline(&mut palette.parameter).desired_width(f32::INFINITY),
+                )
+                .request_focus();
+                if let Some(error) = &palette.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                if enter {
+                    actions.extend(palette.confirm_parameter());
+                }
+                return;
+            }
+
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut palette.query)
+                    .hint_text("Search actions")
+                    .desired_width(f32::INFINITY),
+            );
+            response.request_focus();
+            if response.changed() {
+                palette.selected = 0;
+            }
+
+            let matches = palette.matches();
+            if down {
+                palette.selected = (palette.selected + 1).min(matches.len().saturating_sub(1));
+            }
+            if up {
+                palette.selected = palette.selected.saturating_sub(1);
+            }
+
+            let mut chosen = None;
+            egui::ScrollArea::vertical()
+                .max_height(360.0)
+                .show(ui, |ui| {
+                    for (index, (action, label)) in matches.iter().enumerate() {
+                        let selected = index == palette.selected;
+                        let response = ui
+                            .horizontal(|ui| {
+                                let response = ui.selectable_label(selected, label);
+                                ui.with_layout(
+                                    egui::Layout::right_to_left(egui::Align::Center),
+                                    |ui| {
+                                        ui.weak(chords(action));
+                                    },
+                                );
+                                response
+                            })
+                            .inner
+                            .on_hover_text(action.info().description);
+                        if selected && (up || down) {
+                            response.scroll_to_me(None);
+                        }
+                        if response.clicked() {
+                            chosen = Some(action.clone());
+                        }
+                    }
+                });
+            if enter {
+                chosen = matches
+                    .get(palette.selected)
+                    .map(|(action, _)| action.clone());
+            }
+            if let Some(action) = chosen {
+                actions.extend(palette.choose(action));
+            }
+        });
+
+    if close {
+        palette.open = false;
+    }
+}
+
+/// The include tree of every shader with the size of each file, when it was loaded and how
+/// many problems it has. Clicking a file shows it in the editor.
+pub fn file_tree_window(
+    ctx: &egui::Context,
+    app_state: &mut AppState,
+    shader_service: &ShaderService,
+    actions: &mut Vec<Action>,
+) {
+    // first line, number of problems and the worst one by file
+    let mut problems: BTreeMap<&Path, (usize, usize, Severity)> = BTreeMap::new();
+    for diagnostic in shader_service.diagnostics() {
+        let location = match &diagnostic.location {
+            Some(location) => location,
+            None => continue,
+        };
+        let entry = problems.entry(location.path.as_path()).or_insert((
+            location.line,
+            0,
+            diagnostic.severity,
+        ));
+        if diagnostic.severity < entry.2 {
+            *entry = (location.line, entry.1, diagnostic.severity);
+        }
+        entry.1 += 1;
+    }
+
+    egui::Window::new("Files")
+        .default_pos(window_pos(app_state, "Files", egui::pos2(8.0, 120.0)))
+        .open(&mut app_state.file_tree_visible)
+        .show(ctx, |ui| {
+            let passes = shader_service
+                .project
+                .as_ref()
+                .map(|project| &project.passes);
+            for (index, shader) in shader_service.shaders.iter().enumerate() {
+                if let Some(pass) = passes.and_then(|passes| passes.get(index)) {
+                    ui.strong(&pass.name);
+                }
+                let root = shader.get_main_shader_path();
+                let root = root.canonicalize().unwrap_or_else(|_| root.clone());
+                file_tree_node(ui, &shader.content, &root, &problems, actions);
+            }
+        });
+}
+
+fn file_tree_node(
+    ui: &mut egui::Ui,
+    content: &ShaderContent,
+    path: &Path,
+    problems: &BTreeMap<&Path, (usize, usize, Severity)>,
+    actions: &mut Vec<Action>,
+) {
+    let part = match content.parts.get(path) {
+        Some(part) => part,
+        None => {
+            ui.weak(format!("{} (not loaded)", path.display()));
+            return;
+        }
+    };
+    let problem = problems.get(path).copied();
+
+    let row = |ui: &mut egui::Ui, actions: &mut Vec<Action>| {
+        let line = problem.map_or(1, |(line, _, _)| line);
+        if ui
+            .selectable_label(false, &part.shader_name)
+            .on_hover_text(path.display().to_string())
+            .clicked()
+        {
+            actions.push(Action::OpenFile(path.to_path_buf(), line));
+        }
+        if ui
+            .small_button("↗")
+            .on_hover_text("Open in your editor")
+            .clicked()
+        {
+            actions.push(Action::OpenFileExternal(path.to_path_buf(), line));
+        }
+        if let Some((line, count, severity)) = problem {
+            ui.colored_label(severity_color(severity), format!("⚠ {}", count))
+                .on_hover_text(format!("first at line {}", line));
+        }
+        ui.weak(format!(
+            "{} lines  {}  {}",
+            part.line_count,
+            format_bytes(part.byte_size),
+            format_age(part.loaded_at)
+        ));
+    };
+
+    if part.includes.is_empty() {
+        ui.horizontal(|ui| row(ui, actions));
+        return;
+    }
+    let id = ui.make_persistent_id(path);
+    egui::collapsing_header::CollapsingState::load_with_default_open(ui.ctx(), id, true)
+        .show_header(ui, |ui| row(ui, actions))
+        .body(|ui| {
+            for include in &part.includes {
+                file_tree_node(ui, content, include, problems, actions);
+            }
+        });
+}
+
+fn format_bytes(bytes: usize) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        _ => format!("{:.1} KiB", bytes as f32 / 1024.0),
+    }
+}
+
+/// How long ago, e.g. `5s ago` or `3m ago`
+fn format_age(time: SystemTime) -> String {
+    let seconds = time.elapsed().map_or(0, |age| age.as_secs());
+    match seconds {
+        0..=59 => format!("{}s ago", seconds),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        _ => format!("{}h ago", seconds / 3600),
+    }
+}
+
+/// Every action by category with its key bindings
+pub fn help_window(ctx: &egui::Context, app_state: &mut AppState) {
+    let bindings = &app_state.key_bindings;
+    let mut window = egui::Window::new("Help");
+    if let Some([x, y]) = app_state.window_positions.get("Help") {
+        window = window.default_pos(egui::pos2(*x, *y));
+    }
+    window
+        .open(&mut app_state.help_visible)
+        .default_size(egui::vec2(560.0, 480.0))
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                let all = Action::all();
+                for category in ActionCategory::ALL {
+                    ui.heading(category.name());
+                    egui::Grid::new(category.name())
+                        .striped(true)
+                        .num_columns(3)
+                        .show(ui, |ui| {
+                            for action in all
+                                .iter()
+                                .filter(|action| action.info().category == category)
+                            {
+                                let info = action.info();
+                                let chords: Vec<String> = bindings
+                                    .chords(action)
+                                    .iter()
+                                    .map(|chord| chord.to_string())
+                                    .collect();
+                                ui.label(info.name);
+                                ui.monospace(chords.join(", "));
+                                ui.weak(info.description);
+                                ui.end_row();
+                            }
+                        });
+                }
+            });
+        });
+}
+
+/// Side panel with the built-in editor, one tab per shader file
+pub fn editor_panel(
+    ctx: &egui::Context,
+    app_state: &mut AppState,
+    shader_service: &ShaderService,
+    actions: &mut Vec<Action>,
+) {
+    egui::SidePanel::right("editor")
+        .resizable(true)
+        .default_width(ctx.screen_rect().width() * 0.45)
+        .show(ctx, |ui| {
+            editor_tabs(ui, &mut app_state.editor);
+            ui.separator();
+            code_editor(ui, &mut app_state.editor, shader_service, actions, false);
+        });
+}
+
+/// Bonzomatic style live coding: the code on a dimmed background over the running shader, with
+/// the first error in a single status line
+pub fn editor_overlay(
+    ctx: &egui::Context,
+    app_state: &mut AppState,
+    shader_service: &ShaderService,
+    actions: &mut Vec<Action>,
+) {
+    let frame = egui::Frame::none()
+        .fill(egui::Color32::from_black_alpha(120))
+        .inner_margin(egui::Margin::same(12.0));
+    egui::CentralPanel::default().frame(frame).show(ctx, |ui| {
+        ui.with_layout(egui::Layout::bottom_up(egui::Align::LEFT), |ui| {
+            match status_line(app_state, shader_service) {
+                Some((status, severity)) => {
+                    let status = egui::RichText::new(status)
+                        .monospace()
+                        .color(severity_color(severity));
+                    // only errors can be jumped to
+                    if severity != Severity::Error {
+                        ui.label(status);
+                    } else if ui
+                        .add(egui::Label::new(status).sense(egui::Sense::click()))
+                        .on_hover_text("Click to open it in your editor")
+                        .clicked()
+                    {
+                        actions.push(Action::OpenFirstError);
+                    }
+                }
+                None => {
+                    ui.label(
+                        egui::RichText::new("ok")
+                            .monospace()
+                            .color(egui::Color32::GREEN),
+                    );
+                }
+            }
+
+            ui.with_layout(egui::Layout::top_down(egui::Align::LEFT), |ui| {
+                if app_state.editor.tabs.len() > 1 {
+                    editor_tabs(ui, &mut app_state.editor);
+                }
+                code_editor(ui, &mut app_state.editor, shader_service, actions, true);
+            });
+        });
+    });
+}
+
+/// The first compile error as `file:line: message`, or the first line of any other error.
+/// While the shader works it is the first warning, if there is one.
+fn status_line(app_state: &AppState, shader_service: &ShaderService) -> Option<(String, Severity)> {
+    let failed = app_state.shader_error.is_some();
+    let severity = match failed {
+        true => Severity::Error,
+        false => Severity::Warning,
+    };
+    let mut diagnostics = shader_service
+        .diagnostics()
+        .filter(|diagnostic| !failed || diagnostic.is_error());
+    if let Some(diagnostic) = diagnostics.next() {
+        let more = diagnostics.count();
+        let place = match &diagnostic.location {
+            Some(location) => format!(
+                "{}:{}",
+                location
+                    .path
+                    .file_name()
+                    .map_or_else(String::new, |name| name.to_string_lossy().to_string()),
+                location.line
+            ),
+            None => format!("line {}", diagnostic.line),
+        };
+        let mut status = format!("{}: {}", place, diagnostic.message);
+        if more > 0 {
+            status.push_str(&format!(" (+{} more)", more));
+        }
+        return Some((status, severity));
+    }
+
+    let error = app_state.shader_error.as_ref()?;
+    let status = error
+        .message()
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    Some((status, Severity::Error))
+}
+
+fn severity_color(severity: Severity) -> egui::Color32 {
+    match severity {
+        Severity::Error => egui::Color32::RED,
+        Severity::Warning => egui::Color32::YELLOW,
+    }
+}
+
+fn editor_tabs(ui: &mut egui::Ui, editor: &mut Editor) {
+    ui.horizontal_wrapped(|ui| {
+        for (index, tab) in editor.tabs.iter().enumerate() {
+            let mut label = tab.name.clone();
+            if tab.is_dirty() {
+                label.push_str(" •");
+            }
+            let mut hover = tab.path.display().to_string();
+            if tab.conflict {
+                label.insert_str(0, "⚠ ");
+                hover.push_str("\nChanged on disk, saving overwrites it");
+            }
+            if ui
+                .selectable_label(editor.active == index, label)
+                .on_hover_text(hover)
+                .clicked()
+            {
+                editor.active = index;
+            }
+        }
+    });
+}
+
+/// Include problems and compile errors of a file in the editor by line
+fn file_diagnostics<'a>(
+    shader_service: &'a ShaderService,
+    path: &Path,
+) -> Vec<(usize, Severity, &'a str)> {
+    let mut lines: Vec<(usize, Severity, &str)> = shader_service
+        .diagnostics()
+        .filter_map(|diagnostic| {
+            let location = diagnostic.location.as_ref()?;
+            (location.path == path).then_some((
+                location.line,
+                diagnostic.severity,
+                diagnostic.message.as_str(),
+            ))
+        })
+        .collect();
+    lines.dedup();
+    lines
+}
+
+fn code_editor(
+    ui: &mut egui::Ui,
+    editor: &mut Editor,
+    shader_service: &ShaderService,
+    actions: &mut Vec<Action>,
+    overlay: bool,
+) {
+    let index = editor.active;
+    let goto_line = editor.goto_line.take();
+    let tab = match editor.tabs.get_mut(index) {
+        Some(tab) => tab,
+        None => {
+            ui.weak("No shader files");
+            return;
+        }
+    };
+    let diagnostics = file_diagnostics(shader_service, &tab.path);
+    let id = egui::Id::new("editor").with(&tab.path);
+
+    // egui would type these into the text
+    if ui.memory(|memory| memory.has_focus(id)) {
+        if ui.input_mut(|input| input.consume_key(egui::Modifiers::COMMAND, egui::Key::Enter)) {
+            actions.push(Action::EditorCompile);
+        }
+        if ui.input_mut(|input| input.consume_key(egui::Modifiers::COMMAND, egui::Key::S)) {
+            actions.push(Action::EditorSave);
+        }
+    }
+
+    if let Some(line) = goto_line {
+        let offset: usize = tab
+            .text
+            .split('\n')
+            .take(line.saturating_sub(1))
+            .map(|text| text.chars().count() + 1)
+            .sum();
+        let mut state = egui::text_edit::TextEditState::load(ui.ctx(), id).unwrap_or_default();
+        let cursor = egui::text::CCursor::new(offset);
+        state.set_ccursor_range(Some(egui::text::CCursorRange::one(cursor)));
+        state.store(ui.ctx(), id);
+        ui.memory_mut(|memory| memory.request_focus(id));
+    }
+
+    let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+    // over the shader the text is white and lets a bit of it through
+    let (plain, opacity) = match overlay {
+        true => (egui::Color32::WHITE, 0.85),
+        false => (ui.visuals().text_color(), 1.0),
+    };
+    let width = ui.available_width();
+    let mut changed = false;
+
+    egui::ScrollArea::both()
+        .id_source("editor_scroll")
+        .auto_shrink([false; 2])
+        .show(ui, |ui| {
+            ui.horizontal_top(|ui| {
+                let line_count = tab.text.split('\n').count();
+                let mut gutter = egui::text::LayoutJob::default();
+                for line in 1..=line_count {
+                    let color = diagnostics
+                        .iter()
+                        .filter(|(problem, _, _)| *problem == line)
+                        .map(|(_, severity, _)| *severity)
+                        .min()
+                        .map_or_else(|| ui.visuals().weak_text_color(), severity_color);
+                    let format = egui::TextFormat::simple(font_id.clone(), color);
+                    gutter.append(&format!("{:>4}\n", line), 0.0, format);
+                }
+                ui.label(gutter);
+
+                let mut layouter = |ui: &egui::Ui, text: &str, _wrap_width: f32| {
+                    let job = glsl_layout_job(text, font_id.clone(), plain, opacity);
+                    ui.fonts(|fonts| fonts.layout_job(job))
+                };
+                let output = egui::TextEdit::multiline(&mut tab.text)
+                    .id(id)
+                    .code_editor()
+                    .frame(false)
+                    .margin(egui::Vec2::ZERO)
+                    .desired_width(width)
+                    .layouter(&mut layouter)
+                    .show(ui);
+                changed = output.response.changed();
+
+                // the message goes after the end of the line it is about
+                let painter = ui.painter();
+                for (line, severity, message) in &diagnostics {
+                    let row = match output.galley.rows.get(line - 1) {
+                        Some(row) => row.rect.translate(output.text_draw_pos.to_vec2()),
+                        None => continue,
+                    };
+                    let highlight =
+                        egui::Rect::from_x_y_ranges(output.response.rect.x_range(), row.y_range());
+                    let color = severity_color(*severity);
+                    painter.rect_filled(highlight, 0.0, color.gamma_multiply(0.15));
+                    // the overlay has the status line instead
+                    if overlay {
+                        continue;
+                    }
+                    painter.text(
+                        egui::pos2(row.right() + 24.0, row.center().y),
+                        egui::Align2::LEFT_CENTER,
+                        message,
+                        font_id.clone(),
+                        color,
+                    );
+                }
+
+                if let Some(line) = goto_line {
+                    if let Some(row) = output.galley.rows.get(line.saturating_sub(1)) {
+                        let rect = row.rect.translate(output.text_draw_pos.to_vec2());
+                        ui.scroll_to_rect(rect, Some(egui::Align::Center));
+                    }
+                }
+            });
+        });
+
+    if changed {
+        editor.edited(index, Instant::now());
+    }
+}
+
+/// Colour GLSL the way `highlight_glsl` splits it, on one line per row
+fn glsl_layout_job(
+    text: &str,
+    font_id: egui::FontId,
+    plain: egui::Color32,
+    opacity: f32,
+) -> egui::text::LayoutJob {
+    let mut job = egui::text::LayoutJob::default();
+    for (range, kind) in highlight_glsl(text) {
+        let color = match kind {
+            TokenKind::Plain => plain,
+            TokenKind::Comment => egui::Color32::from_rgb(120, 140, 120),
+            TokenKind::Keyword => egui::Color32::from_rgb(198, 120, 221),
+            TokenKind::Type => egui::Color32::from_rgb(86, 182, 194),
+            TokenKind::Builtin => egui::Color32::from_rgb(97, 175, 239),
+            TokenKind::Number => egui::Color32::from_rgb(209, 154, 102),
+            TokenKind::Preprocessor => egui::Color32::from_rgb(229, 192, 123),
+        };
+        job.append(
+            &text[range],
+            0.0,
+            egui::TextFormat::simple(font_id.clone(), color.gamma_multiply(opacity)),
+        );
+    }
+    job.wrap.max_width = f32::INFINITY;
+    job
+}
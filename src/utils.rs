@@ -21,9 +21,56 @@ pub fn include_statement_from_string(shader_name: String) -> String {
     format!("#pragma include({});", shader_name)
 }
 
+/// Parse a size given as `<width>x<height>`, e.g. `1920x1080`
+pub fn parse_size(value: &str) -> Result<(u32, u32), String> {
+    let (width, height) = value
+        .split_once(['x', 'X'])
+        .ok_or_else(|| format!("Expected <width>x<height>, got '{}'", value))?;
+    let width = width.trim().parse::<u32>().map_err(|e| e.to_string())?;
+    let height = height.trim().parse::<u32>().map_err(|e| e.to_string())?;
+    if width == 0 || height == 0 {
+        return Err(format!("Size must be non-zero, got '{}'", value));
+    }
+    Ok((width, height))
+}
+
+/// Parse a position given as `<x>,<y>`, e.g. `100,-20`
+pub fn parse_position(value: &str) -> Result<(i32, i32), String> {
+    let (x, y) = value
+        .split_once(',')
+        .ok_or_else(|| format!("Expected <x>,<y>, got '{}'", value))?;
+    let x = x.trim().parse::<i32>().map_err(|e| e.to_string())?;
+    let y = y.trim().parse::<i32>().map_err(|e| e.to_string())?;
+    Ok((x, y))
+}
+
+/// A file or directory in the temp dir for a test, unique to the process and removed when
+/// dropped, also when the test fails
+#[cfg(test)]
+pub(crate) struct TempPath(pub std::path::PathBuf);
+
+#[cfg(test)]
+impl TempPath {
+    pub fn new(name: &str) -> Self {
+        let name = format!("skuggbox-{}-{}", std::process::id(), name);
+        Self(std::env::temp_dir().join(name))
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        if self.0.is_dir() {
+            let _ = std::fs::remove_dir_all(&self.0);
+        } else {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{pragma_shader_name, string_between};
+    use super::{parse_position, parse_size, pragma_shader_name, string_between};
 
     #[test]
     fn is_string_between() {
@@ -51,4 +98,20 @@ mod tests {
             "some-shader.glsl".to_string()
         );
     }
+
+    #[test]
+    fn is_size() {
+        assert_eq!(parse_size("16384x9216"), Ok((16384, 9216)));
+        assert_eq!(parse_size("640X480"), Ok((640, 480)));
+        assert!(parse_size("640").is_err());
+        assert!(parse_size("0x480").is_err());
+        assert!(parse_size("ax480").is_err());
+    }
+
+    #[test]
+    fn is_position() {
+        assert_eq!(parse_position("100,-20"), Ok((100, -20)));
+        assert_eq!(parse_position(" 0 , 5 "), Ok((0, 5)));
+        assert!(parse_position("100x20").is_err());
+    }
 }
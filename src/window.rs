@@ -5,18 +5,106 @@ use glutin::context::{
     PossiblyCurrentContext, Version,
 };
 use glutin::display::{GetGlDisplay, GlDisplay};
-use glutin::surface::{GlSurface, Surface, WindowSurface};
+use glutin::surface::{GlSurface, Surface, SwapInterval, WindowSurface};
+use serde::{Deserialize, Serialize};
 use std::ffi::CString;
+use std::num::NonZeroU32;
 use std::sync::Arc;
 
 use glutin_winit::{DisplayBuilder, GlWindow};
 use raw_window_handle::HasRawWindowHandle;
-use winit::dpi::LogicalSize;
+use winit::dpi::{LogicalSize, PhysicalPosition};
 use winit::event_loop::EventLoop;
-use winit::window::{Window, WindowBuilder};
+use winit::monitor::MonitorHandle;
+use winit::window::{Fullscreen, Window, WindowBuilder, WindowLevel};
 
 use crate::{AppConfig, AppState};
 
+/// How the window fills the screen when fullscreen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+pub enum FullscreenMode {
+    /// A borderless window covering the monitor, quick to switch in and out of
+    #[default]
+    Borderless,
+    /// Switch the monitor to the video mode with the most pixels and highest refresh rate
+    Exclusive,
+}
+
+/// How the window is set up. Read from the `[window]` table of the config file, overridden by
+/// the command line and changed at runtime by actions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowOptions {
+    /// Initial inner size in logical pixels
+    pub size: Option<(u32, u32)>,
+    /// Initial position in physical pixels
+    pub position: Option<(i32, i32)>,
+    pub always_on_top: bool,
+    /// No title bar or border
+    pub borderless: bool,
+    pub fullscreen: bool,
+    pub fullscreen_mode: FullscreenMode,
+    /// Index of the monitor to go fullscreen on, the one the window is on when not set
+    pub monitor: Option<usize>,
+    pub vsync: bool,
+}
+
+impl Default for WindowOptions {
+    fn default() -> Self {
+        Self {
+            size: None,
+            position: None,
+            always_on_top: false,
+            borderless: false,
+            fullscreen: false,
+            fullscreen_mode: FullscreenMode::default(),
+            monitor: None,
+            vsync: true,
+        }
+    }
+}
+
+impl WindowOptions {
+    fn window_level(&self) -> WindowLevel {
+        match self.always_on_top {
+            true => WindowLevel::AlwaysOnTop,
+            false => WindowLevel::Normal,
+        }
+    }
+
+    /// Fullscreen setting for winit. `monitors` are all available ones, `current` is used when
+    /// no monitor is chosen or the chosen one isn't there.
+    fn fullscreen(
+        &self,
+        mut monitors: impl Iterator<Item = MonitorHandle>,
+        current: Option<MonitorHandle>,
+    ) -> Option<Fullscreen> {
+        if !self.fullscreen {
+            return None;
+        }
+        let monitor = self
+            .monitor
+            .and_then(|index| monitors.nth(index))
+            .or(current);
+        match self.fullscreen_mode {
+            FullscreenMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+            FullscreenMode::Exclusive => {
+                let mode = monitor?.video_modes().max_by_key(|mode| {
+                    let size = mode.size();
+                    (size.width * size.height, mode.refresh_rate_millihertz())
+                });
+                match mode {
+                    Some(mode) => Some(Fullscreen::Exclusive(mode)),
+                    None => {
+                        log::warn!("No video modes to go fullscreen with, using borderless");
+                        Some(Fullscreen::Borderless(None))
+                    }
+                }
+            }
+        }
+    }
+}
+
 /// Encapsulates everything needed for setting up the window and gl_context
 pub struct AppWindow {
     // glutin
@@ -36,10 +124,22 @@ impl AppWindow {
         // TODO: Move event loop out of AppWindow
         let event_loop = EventLoop::new();
 
+        let options = &app_state.window;
+        let fullscreen = options.fullscreen(
+            event_loop.available_monitors(),
+            event_loop.primary_monitor(),
+        );
+
         // Let winit create a window builder
-        let window_builder = WindowBuilder::new()
+        let mut window_builder = WindowBuilder::new()
             .with_title("Skuggbox")
-            .with_inner_size(LogicalSize::new(app_state.width, app_state.height));
+            .with_inner_size(LogicalSize::new(app_state.width, app_state.height))
+            .with_window_level(options.window_level())
+            .with_decorations(!options.borderless)
+            .with_fullscreen(fullscreen);
+        if let Some((x, y)) = options.position {
+            window_builder = window_builder.with_position(PhysicalPosition::new(x, y));
+        }
 
         let template = ConfigTemplateBuilder::new();
 
@@ -108,7 +208,7 @@ impl AppWindow {
     /// Forge a gl_context (PossiblyCurrentContext) out of the not_current_context
     /// NOTE: This should only be called during the Event::Resume part of the event loop as per this doc
     /// https://github.com/rust-windowing/glutin/blob/master/glutin_examples/src/lib.rs#L16
-    pub fn create_window_context(&mut self) -> Arc<Context> {
+    pub fn create_window_context(&mut self, vsync: bool) -> Arc<Context> {
         let window = self.window.as_ref().unwrap();
         let attrs = window.build_surface_attributes(<_>::default());
         let gl_config = &self.gl_config;
@@ -134,11 +234,54 @@ impl AppWindow {
             })
         };
         self.gl_context = Some(gl_context);
+        self.set_vsync(&gl_surface, vsync);
         // Return the gl context and the WindowSurface which is used to swap buffers
         self.gl_surface = Some(gl_surface);
         Arc::new(gl)
     }
 
+    fn set_vsync(&self, surface: &Surface<WindowSurface>, vsync: bool) {
+        let interval = match vsync {
+            true => SwapInterval::Wait(NonZeroU32::new(1).unwrap()),
+            false => SwapInterval::DontWait,
+        };
+        if let Some(gl_context) = &self.gl_context {
+            if let Err(err) = surface.set_swap_interval(gl_context, interval) {
+                log::warn!("Failed to set vsync: {:?}", err);
+            }
+        }
+    }
+
+    /// Apply whatever differs from `previous` to the window and surface
+    pub fn apply_options(&self, options: &WindowOptions, previous: &WindowOptions) {
+        let window = match &self.window {
+            Some(window) => window,
+            None => return,
+        };
+        if options.always_on_top != previous.always_on_top {
+            window.set_window_level(options.window_level());
+        }
+        if options.borderless != previous.borderless {
+            window.set_decorations(!options.borderless);
+        }
+        if (options.fullscreen, options.fullscreen_mode, options.monitor)
+            != (
+                previous.fullscreen,
+                previous.fullscreen_mode,
+                previous.monitor,
+            )
+        {
+            let fullscreen =
+                options.fullscreen(window.available_monitors(), window.current_monitor());
+            window.set_fullscreen(fullscreen);
+        }
+        if options.vsync != previous.vsync {
+            if let Some(surface) = &self.gl_surface {
+                self.set_vsync(surface, options.vsync);
+            }
+        }
+    }
+
     /// Only call when you know that the gl context is initialized or you'll have a panic
     pub fn swap_buffers(&self) {
         let surface = self.gl_surface.as_ref().unwrap();